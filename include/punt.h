@@ -0,0 +1,188 @@
+/*
+ * C API of the punt crate, available with the `ffi` feature. See the documentation of the `ffi`
+ * module for how to build the library.
+ *
+ * All functions returning int return PUNT_OK on success and a stable error code otherwise, which
+ * punt_error_message() describes.
+ */
+
+#ifndef PUNT_H
+#define PUNT_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * Return value of successful calls.
+ */
+#define PUNT_OK 0
+
+/*
+ * Return code of `Error::InvalidRequest`.
+ */
+#define PUNT_INVALID_REQUEST 1
+
+/*
+ * Return code of `Error::TargetNotFound`.
+ */
+#define PUNT_TARGET_NOT_FOUND 2
+
+/*
+ * Return code of `Error::UnsupportedTarget`.
+ */
+#define PUNT_UNSUPPORTED_TARGET 3
+
+/*
+ * Return code of `Error::TooManyMatches`.
+ */
+#define PUNT_TOO_MANY_MATCHES 4
+
+/*
+ * Return code of `Error::EraseError`.
+ */
+#define PUNT_ERASE_ERROR 5
+
+/*
+ * Return code of `Error::VerificationError`.
+ */
+#define PUNT_VERIFICATION_ERROR 6
+
+/*
+ * Return code of `Error::IoError`.
+ */
+#define PUNT_IO_ERROR 7
+
+/*
+ * Return code of `Error::MalformedResponse`.
+ */
+#define PUNT_MALFORMED_RESPONSE 8
+
+/*
+ * Return code of `Error::TextualImage`.
+ */
+#define PUNT_TEXTUAL_IMAGE 9
+
+/*
+ * Return code of `Error::IncompatibleBootloader`.
+ */
+#define PUNT_INCOMPATIBLE_BOOTLOADER 10
+
+/*
+ * Return code of `Error::Io`.
+ */
+#define PUNT_IO 11
+
+/*
+ * Opaque handle to a connected target, created by [`punt_open`] and freed by [`punt_close`].
+ *
+ * [`punt_open`]: fn.punt_open.html
+ * [`punt_close`]: fn.punt_close.html
+ */
+typedef struct PuntTarget PuntTarget;
+
+/*
+ * Progress callback receiving the user data pointer, the progress so far and the total, both in
+ * bytes.
+ */
+typedef void (*PuntProgressCallback)(void*, size_t, size_t);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * Opens the target with the given serial number, or the only connected target if `serial` is
+ * null, and stores its handle in `target`.
+ *
+ * # Safety
+ *
+ * `serial` has to be null or point to a null-terminated string. `target` has to point to writable
+ * memory for a pointer.
+ */
+int punt_open(const char *serial, PuntTarget **target);
+
+/*
+ * Closes a target opened with [`punt_open`]. Does nothing if `target` is null.
+ *
+ * # Safety
+ *
+ * `target` has to be null or a handle returned by [`punt_open`] which has not been closed yet.
+ *
+ * [`punt_open`]: fn.punt_open.html
+ */
+void punt_close(PuntTarget *target);
+
+/*
+ * Queries the start address and size of the application flash.
+ *
+ * # Safety
+ *
+ * `target` has to be a valid handle and `base` and `size` have to point to writable memory.
+ */
+int punt_application_area(PuntTarget *target, uint32_t *base, size_t *size);
+
+/*
+ * Erases the pages needed for the data, programs and verifies it. The progress callback, if not
+ * null, is called with `user_data` after every step.
+ *
+ * # Safety
+ *
+ * `target` has to be a valid handle and `data` has to point to `length` readable bytes.
+ */
+int punt_flash(PuntTarget *target,
+               const uint8_t *data,
+               size_t length,
+               uint32_t address,
+               PuntProgressCallback callback,
+               void *user_data);
+
+/*
+ * Reads `length` bytes of memory starting at `address` into `buffer`. The progress callback, if
+ * not null, is called with `user_data` after every step.
+ *
+ * # Safety
+ *
+ * `target` has to be a valid handle and `buffer` has to point to `length` writable bytes.
+ */
+int punt_read(PuntTarget *target,
+              uint8_t *buffer,
+              size_t length,
+              uint32_t address,
+              PuntProgressCallback callback,
+              void *user_data);
+
+/*
+ * Lets the target exit from the bootloader and start its application. The handle has to be
+ * closed afterwards.
+ *
+ * # Safety
+ *
+ * `target` has to be a valid handle.
+ */
+int punt_exit(PuntTarget *target);
+
+/*
+ * Returns a static, null-terminated description of a return code.
+ *
+ * # Examples
+ *
+ * ```rust
+ * use punt::ffi::punt_error_message;
+ * use std::ffi::CStr;
+ *
+ * let message = unsafe { CStr::from_ptr(punt_error_message(2)) };
+ * assert_eq!(message.to_str(), Ok("Target not found"));
+ * ```
+ */
+const char *punt_error_message(int code);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PUNT_H */
@@ -0,0 +1,213 @@
+//! Contains an asynchronous interface to a target, available with the `async` feature.
+
+use crate::bootloader_info::BootloaderInfo;
+use crate::error::{Error, Result};
+use crate::operation::Operation;
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use futures_channel::{mpsc, oneshot};
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::thread;
+
+/// Asynchronous wrapper around a [`TargetHandle`].
+///
+/// USB transfers are blocking, so every call is carried out on a separate worker thread, while the
+/// returned future or stream can be awaited from any executor. Calls are serialised: a call only
+/// starts once all previous ones have finished.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{AsyncTargetHandle, Transport};
+///
+/// async fn update<T>(handle: &AsyncTargetHandle<T>, image: Vec<u8>) -> punt::Result<()>
+/// where
+///     T: Transport + Send + 'static,
+/// {
+///     let base = handle.bootloader_info().await?.application_base;
+///     handle.erase_area(base, image.len()).await?.execute().await?;
+///     handle.program_at(image.clone(), base).await?.execute().await?;
+///     handle.verify(image, base).await
+/// }
+/// ```
+///
+/// [`TargetHandle`]: ../struct.TargetHandle.html
+pub struct AsyncTargetHandle<T: Transport> {
+    handle: Arc<Mutex<TargetHandle<T>>>,
+}
+
+impl<T: Transport> Clone for AsyncTargetHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+        }
+    }
+}
+
+impl<T: Transport> From<TargetHandle<T>> for AsyncTargetHandle<T> {
+    fn from(handle: TargetHandle<T>) -> Self {
+        Self {
+            handle: Arc::new(Mutex::new(handle)),
+        }
+    }
+}
+
+impl<T: Transport + Send + 'static> AsyncTargetHandle<T> {
+    /// Queries bootloader information from the target.
+    pub fn bootloader_info(&self) -> Task<BootloaderInfo> {
+        self.spawn(|handle| handle.bootloader_info())
+    }
+
+    /// Queries a CRC32 from the target for a given memory area.
+    pub fn read_crc(&self, start: u32, length: usize) -> Task<u32> {
+        self.spawn(move |handle| handle.read_crc(start, length))
+    }
+
+    /// Verifies the supplied buffer against the target memory beginning at the supplied address
+    /// with a CRC32 check.
+    pub fn verify(&self, data: Vec<u8>, address: u32) -> Task<()> {
+        self.spawn(move |handle| handle.verify(&data, address))
+    }
+
+    /// Reads `length` bytes from the target's memory, starting at the given address.
+    pub fn read_at(&self, address: u32, length: usize) -> Task<Vec<u8>> {
+        self.spawn(move |handle| {
+            let mut buffer = vec![0u8; length];
+            handle.read_at(&mut buffer, address)?.execute()?;
+            Ok(buffer)
+        })
+    }
+
+    /// Erases the minimum number of pages to ensure the supplied area is completely erased. The
+    /// returned task completes once the request has been checked and yields the erase's progress.
+    pub fn erase_area(&self, start: u32, length: usize) -> Task<Progress> {
+        self.spawn_operation(
+            move |handle, started| match handle.erase_area(start, length) {
+                Ok(operation) => forward(operation, started),
+                Err(error) => drop(started.send(Err(error))),
+            },
+        )
+    }
+
+    /// Programs a buffer's contents into already erased flash at the given address. The returned
+    /// task completes once the request has been checked and yields the programming progress.
+    pub fn program_at(&self, data: Vec<u8>, address: u32) -> Task<Progress> {
+        self.spawn_operation(
+            move |handle, started| match handle.program_at(&data, address) {
+                Ok(operation) => forward(operation, started),
+                Err(error) => drop(started.send(Err(error))),
+            },
+        )
+    }
+
+    /// Lets the target exit from the bootloader and start its application.
+    pub fn exit_bootloader(&self) -> Task<()> {
+        self.spawn(|handle| handle.exit_bootloader())
+    }
+
+    /// Runs a closure with exclusive access to the handle on a worker thread.
+    fn spawn<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut TargetHandle<T>) -> Result<R> + Send + 'static,
+    ) -> Task<R> {
+        let (sender, receiver) = oneshot::channel();
+        let handle = self.handle.clone();
+        thread::spawn(move || {
+            let mut handle = handle.lock().unwrap_or_else(PoisonError::into_inner);
+            let _ = sender.send(f(&mut handle));
+        });
+        Task { receiver }
+    }
+
+    /// Runs an operation on a worker thread. The closure has to send the operation's progress
+    /// stream, or the error preventing the operation from starting, via the given sender.
+    fn spawn_operation(
+        &self,
+        f: impl FnOnce(&mut TargetHandle<T>, oneshot::Sender<Result<Progress>>) + Send + 'static,
+    ) -> Task<Progress> {
+        let (sender, receiver) = oneshot::channel();
+        let handle = self.handle.clone();
+        thread::spawn(move || {
+            let mut handle = handle.lock().unwrap_or_else(PoisonError::into_inner);
+            f(&mut handle, sender);
+        });
+        Task { receiver }
+    }
+}
+
+/// Hands out a progress stream for an operation and then carries out the operation, forwarding its
+/// progress. Stops early if the stream is dropped.
+fn forward(operation: impl Operation, started: oneshot::Sender<Result<Progress>>) {
+    let (sender, receiver) = mpsc::unbounded();
+    let progress = Progress {
+        receiver,
+        total: operation.total(),
+    };
+    if started.send(Ok(progress)).is_err() {
+        return;
+    }
+    for status in operation {
+        if sender.unbounded_send(status).is_err() {
+            break;
+        }
+    }
+}
+
+/// Result of a call to an [`AsyncTargetHandle`], available once the worker thread has finished.
+///
+/// [`AsyncTargetHandle`]: struct.AsyncTargetHandle.html
+pub struct Task<R> {
+    receiver: oneshot::Receiver<Result<R>>,
+}
+
+impl<R> Future for Task<R> {
+    type Output = Result<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The sender is only dropped without sending if the worker thread panicked
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(Error::IoError(rusb::Error::Other))))
+    }
+}
+
+/// Stream of progress updates of a running operation, equivalent to iterating over an
+/// [`Operation`].
+///
+/// [`Operation`]: ../trait.Operation.html
+pub struct Progress {
+    receiver: mpsc::UnboundedReceiver<Result<usize>>,
+    total: usize,
+}
+
+impl Progress {
+    /// Returns the total value in terms of which the progress is expressed. See
+    /// [`Operation::total`] for details.
+    ///
+    /// [`Operation::total`]: ../trait.Operation.html#tymethod.total
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Waits for the operation to finish. Returns the first error to occur.
+    pub async fn execute(mut self) -> Result<()> {
+        while let Some(status) =
+            std::future::poll_fn(|cx| Pin::new(&mut self.receiver).poll_next(cx)).await
+        {
+            status?;
+        }
+        Ok(())
+    }
+}
+
+impl Stream for Progress {
+    type Item = Result<usize>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
@@ -0,0 +1,138 @@
+//! Cargo runner flashing an ELF file to a target with the punt bootloader and starting it.
+//!
+//! To use it, add the following to `.cargo/config.toml` of a firmware project:
+//!
+//! ```toml
+//! [target.thumbv7m-none-eabi]
+//! runner = "punt-run"
+//! ```
+//!
+//! `cargo run` then builds the firmware, erases the pages it occupies, programs and verifies it
+//! and lets the target start the application.
+
+use punt::{Discovery, EraseStrategy, Error, FirmwareImage, FlashSession, Phase, Verify};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: punt-run [OPTIONS] ELF [ARGUMENTS]
+
+Flashes an ELF file to the connected target and starts it. Arguments after the ELF file, which
+Cargo passes on from `cargo run -- ARGUMENTS`, are ignored.
+
+Options:
+  -s, --serial SERIAL           Select the target by serial number (wildcards * and ?)
+  -p, --port PORT               Select the target by port path, e.g. 1-4.2
+  --no-exit                     Stay in the bootloader after flashing
+  -h, --help                    Show this help
+
+The environment variables PUNT_SERIAL, PUNT_VID_PID and PUNT_PORT are taken into account as
+well.";
+
+fn main() {
+    let mut discovery = match Discovery::from_env() {
+        Ok(discovery) => discovery,
+        Err(error) => fail(error),
+    };
+    let mut exit = true;
+    let mut elf = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-s" | "--serial" => discovery.serial = Some(option_value(&arg, args.next())),
+            "-p" | "--port" => discovery.port = Some(option_value(&arg, args.next())),
+            "--no-exit" => exit = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => usage(&format!("Unknown option {}", arg)),
+            _ => {
+                elf = Some(arg);
+                break;
+            }
+        }
+    }
+    let elf = elf.unwrap_or_else(|| usage("No ELF file given"));
+
+    let data = fs::read(&elf).unwrap_or_else(|error| {
+        eprintln!("Error: Cannot read {}: {}", elf, error);
+        process::exit(66);
+    });
+    let image = FirmwareImage::from_elf(&data).unwrap_or_else(|error| {
+        eprintln!("Error: Cannot parse {}: {}", elf, error);
+        process::exit(65);
+    });
+
+    if let Err(error) = flash(&discovery, &image, exit) {
+        fail(error);
+    }
+}
+
+/// Flashes the image to the selected target and, if requested, starts the application.
+fn flash(discovery: &Discovery, image: &FirmwareImage, exit: bool) -> Result<(), Error> {
+    let context = discovery.context()?;
+    let target = discovery.pick_target(&context)?;
+    let mut handle = target.open()?;
+    eprintln!("Flashing target {}", handle.serial());
+
+    let summary = FlashSession::new(&mut handle)
+        .erase(EraseStrategy::MinimalPages)
+        .verify(Verify::Crc)
+        .on_progress(progress)
+        .run(image)?;
+    eprintln!(
+        "Programmed {} bytes in {:.1} s",
+        summary.bytes_programmed,
+        summary.duration.as_secs_f64()
+    );
+
+    if exit {
+        handle.exit_bootloader()?;
+        eprintln!("Application started");
+    }
+    Ok(())
+}
+
+/// Draws a progress bar on standard error, ending the line once a phase is complete.
+fn progress(phase: Phase, done: usize, total: usize) {
+    const WIDTH: usize = 40;
+    let label = match phase {
+        Phase::Erase => "Erasing",
+        Phase::Program => "Programming",
+        Phase::Verify => "Verifying",
+    };
+    let filled = (done * WIDTH).checked_div(total).unwrap_or(WIDTH);
+    let mut stderr = io::stderr();
+    let _ = write!(
+        stderr,
+        "\r{:<12} [{}{}] {}/{}",
+        label,
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        done,
+        total
+    );
+    if done >= total {
+        let _ = writeln!(stderr);
+    }
+}
+
+fn option_value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| usage(&format!("Missing value for {}", option)))
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(64);
+}
+
+fn fail(error: Error) -> ! {
+    eprintln!("Error: {}", error);
+    if let Some(hint) = error.recovery_hint() {
+        eprintln!("{}.", hint);
+    }
+    process::exit(100 + i32::from(error.code()));
+}
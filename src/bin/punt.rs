@@ -0,0 +1,266 @@
+//! Command line interface to the punt bootloader.
+
+use punt::{
+    CustomContext, Discovery, EraseStrategy, Error, FirmwareImage, FlashSession, Operation, Phase,
+    TargetHandle, UsbContext, Verify,
+};
+use rusb::DeviceHandle;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "\
+Usage: punt [OPTIONS] COMMAND [ARGUMENTS]
+
+Commands:
+  list                          List connected targets
+  info                          Show bootloader information
+  flash FILE [ADDRESS]          Erase, program and verify a firmware file
+  read ADDRESS LENGTH FILE      Read memory into a binary file
+  erase ADDRESS LENGTH          Erase the pages overlapping an area
+  verify FILE [ADDRESS]         Compare a firmware file with the flash contents
+  exit                          Leave the bootloader and start the application
+
+Options:
+  -s, --serial SERIAL           Select the target by serial number (wildcards * and ?)
+  -p, --port PORT               Select the target by port path, e.g. 1-4.2
+  -h, --help                    Show this help
+
+Files ending in .hex or .ihex are read as Intel HEX, files ending in .srec, .s19 or .mot as
+Motorola S-records, files ending in .elf as ELF and all others as raw binaries placed at ADDRESS,
+which defaults to the start of the application flash. The environment variables PUNT_SERIAL, PUNT_VID_PID and PUNT_PORT are
+taken into account as well.";
+
+/// Error of the command line interface, either with the arguments or from the library.
+enum CliError {
+    Usage(String),
+    File(String),
+    Punt(Error),
+}
+
+impl From<Error> for CliError {
+    fn from(error: Error) -> Self {
+        CliError::Punt(error)
+    }
+}
+
+type Handle = TargetHandle<DeviceHandle<CustomContext>>;
+
+fn main() {
+    if let Err(error) = run(env::args().skip(1).collect()) {
+        let code = match error {
+            CliError::Usage(message) => {
+                eprintln!("{}\n\n{}", message, USAGE);
+                64
+            }
+            CliError::File(message) => {
+                eprintln!("Error: {}", message);
+                66
+            }
+            CliError::Punt(error) => {
+                eprintln!("Error: {}", error);
+                if let Some(hint) = error.recovery_hint() {
+                    eprintln!("{}.", hint);
+                }
+                // Stable error codes, offset to stay clear of the generic ones above
+                100 + i32::from(error.code())
+            }
+        };
+        process::exit(code);
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), CliError> {
+    let mut discovery = Discovery::from_env()?;
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-s" | "--serial" => discovery.serial = Some(option_value(&arg, args.next())?),
+            "-p" | "--port" => discovery.port = Some(option_value(&arg, args.next())?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("Unknown option {}", arg)))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let (command, arguments) = match positional.split_first() {
+        Some((command, arguments)) => (command.as_str(), arguments),
+        None => return Err(CliError::Usage("No command given".to_string())),
+    };
+    let open = || -> Result<Handle, Error> {
+        let context = discovery.context()?;
+        discovery.pick_target(&context)?.open()
+    };
+
+    match (command, arguments) {
+        ("list", []) => list(&discovery.context()?),
+        ("info", []) => {
+            print!("{}", open()?.bootloader_info()?);
+            Ok(())
+        }
+        ("flash", [file, rest @ ..]) if rest.len() <= 1 => {
+            let mut handle = open()?;
+            let image = load_image(&mut handle, file, rest.first())?;
+            let summary = FlashSession::new(&mut handle)
+                .erase(EraseStrategy::MinimalPages)
+                .verify(Verify::Crc)
+                .on_progress(|phase, done, total| {
+                    let label = match phase {
+                        Phase::Erase => "Erasing",
+                        Phase::Program => "Programming",
+                        Phase::Verify => "Verifying",
+                    };
+                    progress(label, done, total);
+                })
+                .run(&image)?;
+            println!(
+                "Erased {} pages and programmed {} bytes in {:.1} s.",
+                summary.pages_erased,
+                summary.bytes_programmed,
+                summary.duration.as_secs_f64()
+            );
+            Ok(())
+        }
+        ("read", [address, length, file]) => {
+            let (address, length) = (parse_number(address)?, parse_number(length)?);
+            let mut buffer = vec![0; length as usize];
+            let mut handle = open()?;
+            run_operation("Reading", handle.read_at(&mut buffer, address)?)?;
+            fs::write(file, &buffer)
+                .map_err(|error| CliError::File(format!("Cannot write {}: {}", file, error)))
+        }
+        ("erase", [address, length]) => {
+            let (address, length) = (parse_number(address)?, parse_number(length)?);
+            let mut handle = open()?;
+            run_operation("Erasing", handle.erase_area(address, length as usize)?)?;
+            Ok(())
+        }
+        ("verify", [file, rest @ ..]) if rest.len() <= 1 => {
+            let mut handle = open()?;
+            let image = load_image(&mut handle, file, rest.first())?;
+            handle.verify_image(&image)?;
+            println!("Flash contents match {}.", file);
+            Ok(())
+        }
+        ("exit", []) => Ok(open()?.exit_bootloader()?),
+        ("list" | "info" | "flash" | "read" | "erase" | "verify" | "exit", _) => Err(
+            CliError::Usage(format!("Wrong arguments for command {}", command)),
+        ),
+        _ => Err(CliError::Usage(format!("Unknown command {}", command))),
+    }
+}
+
+/// Prints all connected targets, one per line.
+fn list(context: &CustomContext) -> Result<(), CliError> {
+    let records = context.list_targets()?;
+    if records.is_empty() {
+        println!("No targets found.");
+    }
+    for record in records {
+        let port = port_path(record.bus_number, &record.port_numbers);
+        let serial = record.serial.as_deref().unwrap_or("(unknown serial)");
+        match record.bootloader_info {
+            Some(info) => println!(
+                "{}  port {}  bootloader {} ({})",
+                serial, port, info.version, info.identifier
+            ),
+            None => println!("{}  port {}", serial, port),
+        }
+    }
+    Ok(())
+}
+
+/// Formats a port path like `Target::port_path` does, e.g. `1-4.2`.
+fn port_path(bus_number: u8, port_numbers: &[u8]) -> String {
+    let ports: Vec<String> = port_numbers.iter().map(u8::to_string).collect();
+    format!("{}-{}", bus_number, ports.join("."))
+}
+
+/// Reads a firmware file, choosing the format by its extension.
+fn load_image(
+    handle: &mut Handle,
+    file: &str,
+    address: Option<&String>,
+) -> Result<FirmwareImage, CliError> {
+    let extension = Path::new(file)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let read_error = |error: io::Error| CliError::File(format!("Cannot read {}: {}", file, error));
+    let parse_error = |error| CliError::File(format!("Cannot parse {}: {}", file, error));
+
+    match extension.as_deref() {
+        Some("hex" | "ihex" | "srec" | "s19" | "mot" | "elf") if address.is_some() => Err(
+            CliError::Usage("An address can only be given for binary files".to_string()),
+        ),
+        Some("hex" | "ihex") => {
+            FirmwareImage::from_ihex(&fs::read_to_string(file).map_err(read_error)?)
+                .map_err(parse_error)
+        }
+        Some("elf") => {
+            FirmwareImage::from_elf(&fs::read(file).map_err(read_error)?).map_err(parse_error)
+        }
+        Some("srec" | "s19" | "mot") => {
+            FirmwareImage::from_srec(&fs::read_to_string(file).map_err(read_error)?)
+                .map_err(parse_error)
+        }
+        _ => {
+            let address = match address {
+                Some(address) => parse_number(address)?,
+                None => handle.bootloader_info()?.application_base,
+            };
+            let data = fs::read(file).map_err(read_error)?;
+            Ok(FirmwareImage::from_binary(address, data))
+        }
+    }
+}
+
+/// Runs an operation to completion with a progress bar.
+fn run_operation(label: &str, mut operation: impl Operation) -> Result<(), CliError> {
+    let total = operation.total();
+    for done in &mut operation {
+        progress(label, done?, total);
+    }
+    Ok(())
+}
+
+/// Draws a progress bar on standard error, ending the line once complete.
+fn progress(label: &str, done: usize, total: usize) {
+    const WIDTH: usize = 40;
+    let filled = (done * WIDTH).checked_div(total).unwrap_or(WIDTH);
+    let mut stderr = io::stderr();
+    let _ = write!(
+        stderr,
+        "\r{:<12} [{}{}] {}/{}",
+        label,
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        done,
+        total
+    );
+    if done >= total {
+        let _ = writeln!(stderr);
+    }
+}
+
+/// Returns the value following an option.
+fn option_value(option: &str, value: Option<String>) -> Result<String, CliError> {
+    value.ok_or_else(|| CliError::Usage(format!("Missing value for {}", option)))
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+fn parse_number(text: &str) -> Result<u32, CliError> {
+    let result = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    result.map_err(|_| CliError::Usage(format!("Invalid number {}", text)))
+}
@@ -1,11 +1,13 @@
 //! Contains data structures for information the bootloader reports back to the connecting PC.
 
-use crate::flash::Page;
+use crate::flash::{MemoryMap, Page};
+use std::convert::TryInto;
 use std::fmt;
 use std::ops::RangeInclusive;
 
 /// Suppository information read back from the bootloader.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BootloaderInfo {
     /// Build number of the bootloader.
     pub build_number: u32,
@@ -29,8 +31,12 @@ pub struct BootloaderInfo {
 impl BootloaderInfo {
     /// Returns a range containing all application pages
     pub fn application_pages(&self) -> RangeInclusive<Page> {
-        Page::from_address(self.application_base)
-            ..=Page::from_address(self.application_base + self.application_size as u32 - 1)
+        self.memory_map().application_pages()
+    }
+
+    /// Returns the layout of the target's flash.
+    pub fn memory_map(&self) -> MemoryMap {
+        MemoryMap::new(self.application_base, self.application_size)
     }
 }
 
@@ -54,7 +60,8 @@ impl fmt::Display for BootloaderInfo {
 }
 
 /// Represents a version number as used for the bootloader firmware version.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     /// The major version, incremented for breaking changes.
     pub major: u8,
@@ -66,8 +73,149 @@ pub struct Version {
     pub patch: u8,
 }
 
+impl Version {
+    /// Major versions of bootloaders this crate is able to communicate with. The protocol only
+    /// changes incompatibly with the major version.
+    pub const SUPPORTED_MAJOR_VERSIONS: RangeInclusive<u8> = 0..=1;
+
+    /// Returns whether a bootloader of this version speaks a protocol supported by this crate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::bootloader_info::Version;
+    ///
+    /// let version = Version { major: 1, minor: 4, patch: 0 };
+    /// assert!(version.is_supported());
+    /// assert!(!Version { major: 2, ..version }.is_supported());
+    /// ```
+    pub fn is_supported(&self) -> bool {
+        Self::SUPPORTED_MAJOR_VERSIONS.contains(&self.major)
+    }
+}
+
 impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
     }
 }
+
+/// The 96-bit unique device identifier factory-programmed into every STM32 microcontroller.
+///
+/// The identifier is displayed as 24 hexadecimal digits, most significant byte first, which is
+/// the form ST's tools use.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UniqueId(pub [u8; 12]);
+
+impl UniqueId {
+    /// Address of the identifier in the STM32F1's system memory.
+    pub(crate) const ADDRESS: u32 = 0x1fff_f7e8;
+
+    /// Returns the identifier's bytes in memory order, i.e. least significant byte first.
+    pub fn as_bytes(&self) -> &[u8; 12] {
+        &self.0
+    }
+
+    /// Returns the identifier as the three 32-bit words it is stored as, lowest address first.
+    pub fn words(&self) -> [u32; 3] {
+        let word = |i: usize| u32::from_le_bytes(self.0[i * 4..i * 4 + 4].try_into().unwrap());
+        [word(0), word(1), word(2)]
+    }
+}
+
+impl fmt::Display for UniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter().rev() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Readout protection level of the microcontroller's flash.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReadProtection {
+    /// The flash can be read by a debugger and by the system bootloader.
+    Disabled,
+
+    /// The flash can only be read by code running from it, such as the punt bootloader. Disabling
+    /// the protection erases the complete flash.
+    Enabled,
+}
+
+/// The STM32F1's user option bytes, as read from its system memory.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptionBytes {
+    /// Readout protection byte. Only `0xa5` disables readout protection.
+    pub rdp: u8,
+
+    /// User option byte configuring the watchdog and the reset behaviour in low-power modes.
+    pub user: u8,
+
+    /// The two user data bytes.
+    pub data: [u8; 2],
+
+    /// Write protection bits, `WRP0` in the least significant byte. Each cleared bit protects a
+    /// group of flash pages against erasing and programming.
+    pub write_protection: u32,
+}
+
+impl OptionBytes {
+    /// Address of the option bytes in the STM32F1's system memory.
+    pub(crate) const ADDRESS: u32 = 0x1fff_f800;
+
+    /// Size of the option bytes including their complements.
+    pub(crate) const SIZE: usize = 16;
+
+    /// Decodes the option bytes from memory, where each byte is followed by its complement.
+    /// Returns `None` if a complement does not match.
+    pub(crate) fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let mut values = [0u8; Self::SIZE / 2];
+        for (value, pair) in values.iter_mut().zip(bytes.chunks_exact(2)) {
+            if pair[0] != !pair[1] {
+                return None;
+            }
+            *value = pair[0];
+        }
+        Some(Self {
+            rdp: values[0],
+            user: values[1],
+            data: [values[2], values[3]],
+            write_protection: u32::from_le_bytes(values[4..8].try_into().unwrap()),
+        })
+    }
+
+    /// Encodes the option bytes as they are stored in memory.
+    pub(crate) fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut values = vec![self.rdp, self.user, self.data[0], self.data[1]];
+        values.extend_from_slice(&self.write_protection.to_le_bytes());
+        let mut bytes = [0u8; Self::SIZE];
+        for (pair, value) in bytes.chunks_exact_mut(2).zip(values) {
+            pair.copy_from_slice(&[value, !value]);
+        }
+        bytes
+    }
+
+    /// Returns the readout protection level.
+    pub fn read_protection(&self) -> ReadProtection {
+        match self.rdp {
+            0xa5 => ReadProtection::Disabled,
+            _ => ReadProtection::Enabled,
+        }
+    }
+}
+
+impl Default for OptionBytes {
+    /// Returns the option bytes of a factory-new device: no readout or write protection.
+    fn default() -> Self {
+        Self {
+            rdp: 0xa5,
+            user: 0xff,
+            data: [0xff, 0xff],
+            write_protection: 0xffff_ffff,
+        }
+    }
+}
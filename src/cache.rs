@@ -0,0 +1,47 @@
+//! Host-side cache for flash contents read from the target.
+
+use crate::flash::Page;
+use std::collections::BTreeMap;
+
+/// Page-granular cache of flash memory contents.
+///
+/// Each entry holds the full contents of one flash page as last read from the target. Entries are
+/// dropped whenever the corresponding page is erased or programmed.
+#[derive(Debug, Default)]
+pub(crate) struct ReadCache {
+    pages: BTreeMap<Page, Vec<u8>>,
+}
+
+impl ReadCache {
+    /// Returns the cached contents of a page, if present.
+    pub(crate) fn get(&self, page: &Page) -> Option<&[u8]> {
+        self.pages.get(page).map(Vec::as_slice)
+    }
+
+    /// Stores the contents of a page.
+    pub(crate) fn insert(&mut self, page: Page, data: Vec<u8>) {
+        self.pages.insert(page, data);
+    }
+
+    /// Drops a single page from the cache.
+    pub(crate) fn invalidate_page(&mut self, page: &Page) {
+        self.pages.remove(page);
+    }
+
+    /// Drops all pages overlapping the given memory area from the cache.
+    pub(crate) fn invalidate_area(&mut self, start: u32, length: usize) {
+        if length == 0 {
+            return;
+        }
+        let first: u8 = Page::from_address(start).into();
+        let last: u8 = Page::from_address(start + length as u32 - 1).into();
+        for index in first..=last {
+            self.invalidate_page(&Page::from_index(index));
+        }
+    }
+
+    /// Drops all cached pages.
+    pub(crate) fn clear(&mut self) {
+        self.pages.clear();
+    }
+}
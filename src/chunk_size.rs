@@ -0,0 +1,257 @@
+//! Adaptive sizing of the chunks memory reads and writes are split into.
+
+use std::time::Duration;
+
+/// Smallest chunk size the adaptive sizing shrinks to before giving up.
+const MIN_CHUNK_SIZE: usize = 8;
+
+/// Number of consecutive successful transfers over which the throughput of a chunk size is
+/// measured before trying another one.
+const WINDOW: u32 = 16;
+
+/// Factor by which a smaller chunk size has to be faster than the best one so far to be kept. A
+/// larger chunk size is kept unless the best one so far is faster by this factor, since larger
+/// chunks need fewer commands.
+const MARGIN: f64 = 1.1;
+
+/// Shortest duration a window of transfers is assumed to take, so that throughputs stay finite
+/// even if the clock did not advance, e.g. in dry-run mode.
+const MIN_WINDOW_TIME: Duration = Duration::from_micros(1);
+
+/// Direction the chunk size is currently being tuned in.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Direction {
+    Grow,
+    Shrink,
+}
+
+/// Tracks the chunk size for one transfer direction.
+///
+/// The throughput of each chunk size is measured over a window of successful transfers. Starting
+/// out with the given size, larger chunks are tried for as long as they are not slower, up to the
+/// maximum. If the first larger size already is slower, smaller chunks are tried instead for as
+/// long as they are faster. The fastest size found is kept from then on. A transfer error shrinks
+/// the chunk size immediately, after which tuning starts over from the reduced size.
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkSize {
+    max: usize,
+    current: usize,
+    successes: u32,
+    bytes: usize,
+    elapsed: Duration,
+    /// Fastest size measured since tuning started and its throughput in bytes per second.
+    best: Option<(usize, f64)>,
+    direction: Direction,
+    /// Whether a size other than the first one measured turned out to be faster.
+    moved: bool,
+    /// Whether the direction has been reversed already.
+    turned: bool,
+    settled: bool,
+}
+
+impl ChunkSize {
+    /// Starts out with the `initial` chunk size, which may grow up to `max`. Sizes are always kept
+    /// even, since flash is programmed halfword-wise.
+    pub(crate) fn new(initial: usize, max: usize) -> Self {
+        let max = max & !1;
+        let current = (initial & !1).clamp(MIN_CHUNK_SIZE.min(max), max);
+        Self {
+            max,
+            current,
+            successes: 0,
+            bytes: 0,
+            elapsed: Duration::default(),
+            best: None,
+            direction: if current < max {
+                Direction::Grow
+            } else {
+                Direction::Shrink
+            },
+            moved: false,
+            turned: false,
+            settled: false,
+        }
+    }
+
+    /// The chunk size to use for the next transfer.
+    pub(crate) fn get(&self) -> usize {
+        self.current
+    }
+
+    /// Records a successful transfer of the given number of bytes and the time the transfer alone
+    /// took.
+    pub(crate) fn success(&mut self, bytes: usize, elapsed: Duration) {
+        if self.settled {
+            return;
+        }
+        self.successes += 1;
+        self.bytes += bytes;
+        self.elapsed += elapsed;
+        if self.successes < WINDOW {
+            return;
+        }
+
+        let throughput = self.bytes as f64 / self.elapsed.max(MIN_WINDOW_TIME).as_secs_f64();
+        self.reset_window();
+
+        let keep = match self.best {
+            None => true,
+            Some((best_size, best_throughput)) if self.current > best_size => {
+                throughput * MARGIN >= best_throughput
+            }
+            Some((_, best_throughput)) => throughput > best_throughput * MARGIN,
+        };
+        if keep {
+            self.moved |= self.best.is_some();
+            self.best = Some((self.current, throughput));
+            self.step();
+            return;
+        }
+
+        // The last step made things worse, so go back to the best size found. If that was the size
+        // tuning started with, the other direction may still be worth a try.
+        self.current = self.best.map_or(self.current, |(size, _)| size);
+        if self.moved || self.turned {
+            self.settled = true;
+        } else {
+            self.turned = true;
+            self.direction = match self.direction {
+                Direction::Grow => Direction::Shrink,
+                Direction::Shrink => Direction::Grow,
+            };
+            self.step();
+        }
+    }
+
+    /// Records a failed transfer and shrinks the chunk size. Returns `false` if the chunk size
+    /// already was at its minimum, i.e. retrying with smaller chunks is not possible.
+    pub(crate) fn failure(&mut self) -> bool {
+        self.reset_window();
+        if self.current <= MIN_CHUNK_SIZE {
+            return false;
+        }
+        self.current = ((self.current / 2) & !1).max(MIN_CHUNK_SIZE);
+        // Measurements of larger sizes no longer apply, so start over from the reduced size. Only
+        // growing makes sense, as the next smaller size is what the next error leads to anyway.
+        self.best = None;
+        self.direction = Direction::Grow;
+        self.moved = false;
+        self.turned = true;
+        self.settled = false;
+        true
+    }
+
+    /// Moves on to the next size in the current direction, or settles if there is none.
+    fn step(&mut self) {
+        let next = match self.direction {
+            Direction::Grow => (self.current * 2).min(self.max),
+            Direction::Shrink => ((self.current / 2) & !1).max(MIN_CHUNK_SIZE),
+        };
+        if next == self.current {
+            self.settled = true;
+        } else {
+            self.current = next;
+        }
+    }
+
+    /// Discards the measurements of the current window.
+    fn reset_window(&mut self) {
+        self.successes = 0;
+        self.bytes = 0;
+        self.elapsed = Duration::default();
+    }
+}
+
+/// Adaptive chunk sizes for both transfer directions.
+#[derive(Debug, Clone)]
+pub(crate) struct AdaptiveChunkSizes {
+    pub(crate) read: ChunkSize,
+    pub(crate) program: ChunkSize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds a full window of transfers at the current size, each taking as long as `time` returns
+    /// for the size.
+    fn run_window(chunk_size: &mut ChunkSize, time: impl Fn(usize) -> Duration) {
+        let size = chunk_size.get();
+        for _ in 0..WINDOW {
+            chunk_size.success(size, time(size));
+        }
+    }
+
+    /// Runs windows until the size settles, returning the sizes tried.
+    fn tune(chunk_size: &mut ChunkSize, time: impl Fn(usize) -> Duration) -> Vec<usize> {
+        let mut sizes = vec![chunk_size.get()];
+        while !chunk_size.settled {
+            run_window(chunk_size, &time);
+            sizes.push(chunk_size.get());
+        }
+        sizes
+    }
+
+    #[test]
+    fn grows_beyond_the_initial_size_on_fast_links() {
+        // Every transfer has a fixed overhead, so larger chunks are always faster
+        let mut chunk_size = ChunkSize::new(64, 4096);
+        let sizes = tune(&mut chunk_size, |size| {
+            Duration::from_micros(1000 + size as u64)
+        });
+        assert_eq!(sizes, [64, 128, 256, 512, 1024, 2048, 4096, 4096]);
+        assert_eq!(chunk_size.get(), 4096);
+    }
+
+    #[test]
+    fn keeps_the_fastest_size() {
+        // Transfers above 256 bytes become disproportionately slow
+        let mut chunk_size = ChunkSize::new(64, 4096);
+        tune(&mut chunk_size, |size| {
+            let penalty = if size > 256 { 10 * size as u64 } else { 0 };
+            Duration::from_micros(1000 + size as u64 + penalty)
+        });
+        assert_eq!(chunk_size.get(), 256);
+    }
+
+    #[test]
+    fn shrinks_if_smaller_chunks_are_faster() {
+        // Only small transfers get through quickly
+        let mut chunk_size = ChunkSize::new(64, 64);
+        tune(&mut chunk_size, |size| {
+            let size = size as u64;
+            Duration::from_micros(if size > 16 { size * size } else { 10 })
+        });
+        assert_eq!(chunk_size.get(), 16);
+    }
+
+    #[test]
+    fn stays_at_the_maximum_without_a_clear_gain() {
+        let mut chunk_size = ChunkSize::new(64, 64);
+        let sizes = tune(&mut chunk_size, |size| Duration::from_micros(size as u64));
+        assert_eq!(sizes, [64, 32, 64]);
+    }
+
+    #[test]
+    fn zero_durations_do_not_break_tuning() {
+        let mut chunk_size = ChunkSize::new(64, 256);
+        let sizes = tune(&mut chunk_size, |_| Duration::default());
+        assert_eq!(sizes, [64, 128, 256, 256]);
+    }
+
+    #[test]
+    fn failures_shrink_and_regrow() {
+        let mut chunk_size = ChunkSize::new(64, 64);
+        assert!(chunk_size.failure());
+        assert_eq!(chunk_size.get(), 32);
+        assert!(chunk_size.failure());
+        assert!(chunk_size.failure());
+        assert_eq!(chunk_size.get(), MIN_CHUNK_SIZE);
+        assert!(!chunk_size.failure());
+
+        tune(&mut chunk_size, |size| {
+            Duration::from_micros(1000 + size as u64)
+        });
+        assert_eq!(chunk_size.get(), 64);
+    }
+}
@@ -1,9 +1,23 @@
 use crate::error::{Error, Result};
-use crate::target::Target;
+use crate::hotplug::{TargetEvent, TargetWatcher};
+use crate::reboot::BootloaderTrigger;
+use crate::target::{DeviceIdentity, Target, TargetRecord};
+use crate::target_handle::TargetHandle;
+use rusb::DeviceHandle;
 use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Base trait for a USB context.
 pub trait UsbContext: rusb::UsbContext {
+    /// Returns the USB identity by which targets are recognised. This is the identity of the
+    /// upstream punt bootloader unless overridden, e.g. by a [`CustomContext`].
+    ///
+    /// [`CustomContext`]: struct.CustomContext.html
+    fn device_identity(&self) -> DeviceIdentity {
+        DeviceIdentity::default()
+    }
+
     /// Returns information about all connected targets in bootloader mode. USB devices not in
     /// bootloader mode cannot be detected, since their protocol for entering bootloader mode is
     /// not specified.
@@ -20,46 +34,433 @@ pub trait UsbContext: rusb::UsbContext {
             .collect())
     }
 
+    /// Returns a record for every connected target in bootloader mode, e.g. to take an inventory
+    /// of the connected boards. Each target is opened briefly to query its bootloader information.
+    ///
+    /// It returns [`Error::IoError`] on USB errors during device enumeration. Errors while reading
+    /// from a single target only leave out the respective information in its record.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// for record in context.list_targets()? {
+    ///     println!(
+    ///         "{} on bus {} port {:?}",
+    ///         record.serial.as_deref().unwrap_or("?"),
+    ///         record.bus_number,
+    ///         record.port_numbers
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    fn list_targets(&self) -> Result<Vec<TargetRecord>> {
+        Ok(self.find_targets()?.iter().map(Target::record).collect())
+    }
+
+    /// Returns all connected targets in bootloader mode whose serial number matches a pattern.
+    /// In the pattern, `*` stands for any number of characters and `?` for a single character, so
+    /// e.g. `ABC*` matches all serial numbers starting with `ABC`. A pattern without wildcards has
+    /// to match the serial number exactly.
+    ///
+    /// This is useful for listing the candidates after [`pick_target`] failed with
+    /// [`Error::TooManyMatches`]. It returns [`Error::IoError`] for any libusb errors occurring
+    /// during enumeration or while reading a serial number.
+    ///
+    /// [`pick_target`]: #method.pick_target
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    /// [`Error::TooManyMatches`]: enum.Error.html#variant.TooManyMatches
+    fn find_targets_matching(&self, pattern: &str) -> Result<Vec<Target<Self>>> {
+        let mut matching = Vec::new();
+        for target in self.find_targets()? {
+            if serial_matches(pattern, &target.serial()?) {
+                matching.push(target);
+            }
+        }
+        Ok(matching)
+    }
+
     /// Returns one target if either
     ///
-    /// * A serial number is supplied which matches one of the connected targets' serial numbers or
-    /// * Only one target is connected and either no serial number is supplied or the serial number
-    ///   matches.
+    /// * A serial number pattern is supplied which matches exactly one of the connected targets'
+    ///   serial numbers or
+    /// * Only one target is connected and no serial number pattern is supplied.
+    ///
+    /// Patterns may contain the wildcards `*` and `?`, see [`find_targets_matching`] for details.
     ///
     /// It can return the following errors:
     /// * [`Error::TargetNotFound`] if no target is found based on the criteria above,
     /// * [`Error::TooManyMatches`] if more than one target is connected but no serial number is
-    ///   supplied, and
+    ///   supplied or more than one target matches the pattern, and
     /// * [`Error::IoError`] for any libusb errors occurring during USB transfers.
     ///
     /// Just like with [`find_targets`], only targets in bootloader mode are considered.
     ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, Error, UsbContext};
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// match context.pick_target(Some("3976*")) {
+    ///     Ok(target) => println!("Found {}", target.serial()?),
+    ///     Err(Error::TooManyMatches) => {
+    ///         for target in context.find_targets_matching("3976*")? {
+    ///             println!("Candidate: {}", target.serial()?);
+    ///         }
+    ///     }
+    ///     Err(error) => return Err(error),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// [`find_targets`]: #method.find_targets
+    /// [`find_targets_matching`]: #method.find_targets_matching
     /// [`Error::IoError`]: enum.Error.html#variant.IoError
     /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
     /// [`Error::TooManyMatches`]: enum.Error.html#variant.TooManyMatches
     fn pick_target(&self, serial: Option<&str>) -> Result<Target<Self>> {
-        let targets = self.find_targets()?;
-        if let Some(serial) = serial {
-            targets
-                .into_iter()
-                .find_map(|t| match t.serial() {
-                    Ok(s) if s == serial => Some(Ok(t)),
-                    Err(e) => Some(Err(e)),
-                    _ => None,
-                })
-                .unwrap_or(Err(Error::TargetNotFound))
-        } else if targets.len() > 1 {
-            // More than one target and no serial given
+        let targets = match serial {
+            Some(pattern) => self.find_targets_matching(pattern)?,
+            None => self.find_targets()?,
+        };
+        if targets.len() > 1 {
             Err(Error::TooManyMatches)
         } else {
-            // One or zero targets found and no serial given. Return first one if existant.
+            // One or zero targets found. Return first one if existant.
             targets.into_iter().next().ok_or(Error::TargetNotFound)
         }
     }
+
+    /// Returns one target, letting the caller choose if more than one is connected, e.g. by asking
+    /// the user in a CLI or GUI frontend.
+    ///
+    /// If several targets are connected, `choose` receives a record of each, with its serial
+    /// number, location and bootloader information, and returns the index of the selected one, or
+    /// `None` to select none. A single connected target is returned without calling `choose`.
+    ///
+    /// It returns [`Error::TargetNotFound`] if no target is connected or none is selected, and
+    /// [`Error::IoError`] for any libusb errors occurring during enumeration.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    /// use std::io::BufRead;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let target = context.pick_target_with(|candidates| {
+    ///     for (i, candidate) in candidates.iter().enumerate() {
+    ///         println!("{}: {}", i, candidate.serial.as_deref().unwrap_or("?"));
+    ///     }
+    ///     let line = std::io::stdin().lock().lines().next()?.ok()?;
+    ///     line.trim().parse().ok()
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
+    fn pick_target_with<F>(&self, choose: F) -> Result<Target<Self>>
+    where
+        F: FnOnce(&[TargetRecord]) -> Option<usize>,
+    {
+        let mut targets = self.find_targets()?;
+        let index = match targets.len() {
+            0 => return Err(Error::TargetNotFound),
+            1 => 0,
+            _ => {
+                let records: Vec<TargetRecord> = targets.iter().map(Target::record).collect();
+                choose(&records)
+                    .filter(|&index| index < targets.len())
+                    .ok_or(Error::TargetNotFound)?
+            }
+        };
+        Ok(targets.swap_remove(index))
+    }
+
+    /// Returns the target plugged into the given physical port, e.g. `1-4.2` for port 2 of a hub
+    /// plugged into port 4 of the root hub of bus 1. See [`Target::port_path`] for the format.
+    ///
+    /// Unlike serial numbers, ports identify the slots of a fixture, so this also works for
+    /// factory-fresh boards with blank or identical serial numbers.
+    ///
+    /// It returns [`Error::TargetNotFound`] if no target in bootloader mode is plugged into the
+    /// port and [`Error::IoError`] for any libusb errors occurring during enumeration.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.pick_target_by_port("1-4.2")?.open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Target::port_path`]: struct.Target.html#method.port_path
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
+    fn pick_target_by_port(&self, port_path: &str) -> Result<Target<Self>> {
+        self.find_targets()?
+            .into_iter()
+            .find_map(|t| match t.port_path() {
+                Ok(p) if p == port_path => Some(Ok(t)),
+                Err(e) => Some(Err(e)),
+                _ => None,
+            })
+            .unwrap_or(Err(Error::TargetNotFound))
+    }
+
+    /// Picks a target like [`pick_target`] and opens a connection to it.
+    ///
+    /// In addition to the errors returned by [`pick_target`], USB errors while opening the target
+    /// are returned as [`Error::IoError`].
+    ///
+    /// [`pick_target`]: #method.pick_target
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    fn open_target(&self, serial: Option<&str>) -> Result<TargetHandle<DeviceHandle<Self>>> {
+        self.pick_target(serial)?.open()
+    }
+
+    /// Starts watching for targets being connected and disconnected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, TargetEvent, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let mut watcher = context.watch_targets()?;
+    /// loop {
+    ///     match watcher.next_event(Duration::from_secs(1))? {
+    ///         Some(TargetEvent::Arrived(target)) => println!("+ {}", target.serial()?),
+    ///         Some(TargetEvent::Left(serial)) => println!("- {}", serial),
+    ///         None => (),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    fn watch_targets(&self) -> Result<TargetWatcher<Self>>
+    where
+        Self: 'static,
+    {
+        TargetWatcher::new(self.clone())
+    }
+
+    /// Waits for a target to be connected, e.g. after the application has been told to enter the
+    /// bootloader. If a serial number pattern is supplied, only a target with a matching serial
+    /// number is accepted, see [`find_targets_matching`] for the pattern syntax. Targets which are
+    /// already connected are returned immediately.
+    ///
+    /// Returns [`Error::TargetNotFound`] if no matching target appears within `timeout`.
+    ///
+    /// [`find_targets_matching`]: #method.find_targets_matching
+    /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
+    fn wait_for_target(&self, serial: Option<&str>, timeout: Duration) -> Result<Target<Self>>
+    where
+        Self: 'static,
+    {
+        self.wait_for_target_with(timeout, |target| match serial {
+            Some(serial) => target
+                .serial()
+                .is_ok_and(|target_serial| serial_matches(serial, &target_serial)),
+            None => true,
+        })
+    }
+
+    /// Waits for a target accepted by `predicate` to be connected, e.g. after power-cycling a
+    /// board. Targets which are already connected are checked first. Hotplug notifications are
+    /// used where libusb supports them, otherwise the connected devices are polled.
+    ///
+    /// Returns [`Error::TargetNotFound`] if no accepted target appears within `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let target = context.wait_for_target_with(Duration::from_secs(10), |target| {
+    ///     target.port_path().is_ok_and(|port| port.starts_with("1-4."))
+    /// })?;
+    /// let mut target_handle = target.open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
+    fn wait_for_target_with<F>(&self, timeout: Duration, mut predicate: F) -> Result<Target<Self>>
+    where
+        Self: 'static,
+        F: FnMut(&Target<Self>) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut watcher = self.watch_targets()?;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match watcher.next_event(remaining)? {
+                Some(TargetEvent::Arrived(target)) if predicate(&target) => return Ok(target),
+                Some(_) => (),
+                None => return Err(Error::TargetNotFound),
+            }
+        }
+    }
+
+    /// Asks a running application to restart into the bootloader, then waits at most `timeout`
+    /// for the bootloader to enumerate on the same port and returns it. See [`BootloaderTrigger`]
+    /// for how the request is sent.
+    ///
+    /// The application is identified by its USB vendor and product ID. Returns
+    /// [`Error::TargetNotFound`] if no such device is connected or the bootloader does not appear
+    /// in time, and [`Error::TooManyMatches`] if several devices with this ID are connected.
+    ///
+    /// [`BootloaderTrigger`]: enum.BootloaderTrigger.html
+    /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
+    /// [`Error::TooManyMatches`]: enum.Error.html#variant.TooManyMatches
+    fn reboot_into_bootloader(
+        &self,
+        application: (u16, u16),
+        trigger: &BootloaderTrigger,
+        timeout: Duration,
+    ) -> Result<Target<Self>>
+    where
+        Self: 'static,
+    {
+        let mut devices = Vec::new();
+        for device in self.devices()?.iter() {
+            let device_desc = device.device_descriptor()?;
+            if (device_desc.vendor_id(), device_desc.product_id()) == application {
+                devices.push(device);
+            }
+        }
+        let device = match devices.len() {
+            0 => return Err(Error::TargetNotFound),
+            1 => devices.remove(0),
+            _ => return Err(Error::TooManyMatches),
+        };
+        let bus_number = device.bus_number();
+        let port_numbers = device.port_numbers()?;
+
+        trigger.send(&device)?;
+        self.wait_for_target_with(timeout, |target| {
+            target.bus_number() == bus_number
+                && target
+                    .port_numbers()
+                    .is_ok_and(|numbers| numbers == port_numbers)
+        })
+    }
+}
+
+/// Checks a serial number against a pattern in which `*` matches any number of characters and `?`
+/// matches a single character.
+pub(crate) fn serial_matches(pattern: &str, serial: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let serial: Vec<char> = serial.chars().collect();
+
+    // Greedy matching, backtracking to the most recent `*` on a mismatch
+    let (mut p, mut s) = (0, 0);
+    let mut backtrack = None;
+    while s < serial.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, s));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == serial[s] => {
+                p += 1;
+                s += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    s = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 /// A punt context, necessary for USB communication.
 pub type Context = rusb::Context;
 
 impl UsbContext for Context {}
+
+/// A USB context for targets whose bootloader has been re-branded with a USB identity of its own.
+///
+/// It is used like a [`Context`], but recognises targets by the given [`DeviceIdentity`] instead
+/// of the one of the upstream punt bootloader.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{CustomContext, DeviceIdentity, UsbContext};
+///
+/// # fn main() -> punt::Result<()> {
+/// let context = CustomContext::new(DeviceIdentity {
+///     vendor_id: 0x1209,
+///     product_id: 0x0001,
+///     manufacturer: "Example Inc.".to_string(),
+///     product: "Example bootloader".to_string(),
+/// })?;
+/// let mut target_handle = context.open_target(None)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Context`]: type.Context.html
+/// [`DeviceIdentity`]: struct.DeviceIdentity.html
+#[derive(Debug, Clone)]
+pub struct CustomContext {
+    context: Context,
+    identity: Arc<DeviceIdentity>,
+}
+
+impl CustomContext {
+    /// Creates a new libusb context recognising targets by the given identity. Returns
+    /// [`Error::IoError`] if libusb cannot be initialised.
+    ///
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    pub fn new(identity: DeviceIdentity) -> Result<Self> {
+        Ok(Self::with_context(Context::new()?, identity))
+    }
+
+    /// Uses an existing libusb context, recognising targets by the given identity.
+    pub fn with_context(context: Context, identity: DeviceIdentity) -> Self {
+        Self {
+            context,
+            identity: Arc::new(identity),
+        }
+    }
+}
+
+impl rusb::UsbContext for CustomContext {
+    fn as_raw(&self) -> *mut rusb::ffi::libusb_context {
+        self.context.as_raw()
+    }
+}
+
+impl UsbContext for CustomContext {
+    fn device_identity(&self) -> DeviceIdentity {
+        (*self.identity).clone()
+    }
+}
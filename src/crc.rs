@@ -0,0 +1,161 @@
+//! Contains the checksum algorithms calculated by punt bootloaders.
+
+/// The CRC-32 polynomial used by the STM32's CRC unit.
+const POLYNOMIAL: u32 = 0x04c1_1db7;
+
+/// Incremental calculation of a CRC32 checksum in the way the punt target does it.
+///
+/// The target computes checksums with the STM32's hardware CRC unit, which processes memory as
+/// 32-bit little-endian words with the CRC-32/MPEG-2 polynomial. The result is therefore equal to
+/// the CRC-32/MPEG-2 of the data with the bytes of every word reversed. If the data does not end
+/// on a word boundary, the last word is padded with zeros.
+///
+/// This allows computing the checksum a target will report for a firmware image without a device,
+/// e.g. in a build pipeline.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::{crc32, Crc32};
+///
+/// let mut crc = Crc32::new();
+/// crc.update(b"pu");
+/// crc.update(b"nt-rs");
+/// assert_eq!(crc.finalize(), crc32(b"punt-rs"));
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Crc32 {
+    crc: u32,
+
+    /// Bytes of an incomplete word not yet fed into the CRC.
+    pending: [u8; 4],
+    pending_length: usize,
+}
+
+impl Crc32 {
+    /// Starts a new checksum calculation.
+    pub fn new() -> Self {
+        Self {
+            crc: 0xffff_ffff,
+            pending: [0; 4],
+            pending_length: 0,
+        }
+    }
+
+    /// Adds data to the checksum. The data does not need to be word-aligned.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let length = data.len().min(4 - self.pending_length);
+            self.pending[self.pending_length..self.pending_length + length]
+                .copy_from_slice(&data[..length]);
+            self.pending_length += length;
+            data = &data[length..];
+
+            if self.pending_length == 4 {
+                self.crc = digest_word(self.crc, self.pending);
+                self.pending_length = 0;
+            }
+        }
+    }
+
+    /// Returns the checksum of all data added so far. Further data can be added afterwards.
+    pub fn finalize(&self) -> u32 {
+        if self.pending_length == 0 {
+            return self.crc;
+        }
+
+        let mut word = [0u8; 4];
+        word[..self.pending_length].copy_from_slice(&self.pending[..self.pending_length]);
+        digest_word(self.crc, word)
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Calculates a CRC32 checksum of a byte buffer in the way the punt target does it. See
+/// [`Crc32`] for details.
+///
+/// [`Crc32`]: struct.Crc32.html
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+/// Feeds a little-endian word into the CRC the way the STM32's CRC unit does.
+fn digest_word(mut crc: u32, word: [u8; 4]) -> u32 {
+    crc ^= u32::from_le_bytes(word);
+    for _ in 0..32 {
+        crc = if crc & 0x8000_0000 != 0 {
+            (crc << 1) ^ POLYNOMIAL
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+/// Checksum algorithm a bootloader uses to answer CRC requests, as returned by
+/// [`TargetHandle::checksum`].
+///
+/// All punt bootloaders use the STM32's hardware CRC unit, so they calculate [`Crc32Mpeg2`].
+/// Variants will only be added once a bootloader version calculates a different checksum, so
+/// matches on it need a wildcard arm.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::{crc32, Checksum};
+///
+/// assert_eq!(Checksum::Crc32Mpeg2.compute(b"punt"), crc32(b"punt"));
+/// ```
+///
+/// [`TargetHandle::checksum`]: struct.TargetHandle.html#method.checksum
+/// [`Crc32Mpeg2`]: #variant.Crc32Mpeg2
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Checksum {
+    /// CRC-32/MPEG-2 over little-endian 32-bit words, as calculated by the STM32's CRC unit. See
+    /// [`Crc32`] for details.
+    ///
+    /// [`Crc32`]: struct.Crc32.html
+    Crc32Mpeg2,
+}
+
+impl Checksum {
+    /// Calculates the checksum of a byte buffer.
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        match self {
+            Checksum::Crc32Mpeg2 => crc32(data),
+        }
+    }
+
+    /// Combines the checksums of two consecutive memory areas into the checksum of both areas
+    /// together, given the length of the second area.
+    ///
+    /// The punt CRC32 has neither input/output reflection nor a final XOR, so the CRC of the
+    /// combined area is the CRC of the second area XORed with the first area's contribution, which
+    /// is obtained by advancing the first area's CRC over as many zero bytes as the second area is
+    /// long (rounded up to whole words).
+    pub(crate) fn combine(&self, first: u32, second: u32, second_length: usize) -> u32 {
+        match self {
+            Checksum::Crc32Mpeg2 => {
+                let words = second_length.div_ceil(4);
+                let mut contribution = first ^ 0xffff_ffff;
+                for _ in 0..words * 32 {
+                    contribution = if contribution & 0x8000_0000 != 0 {
+                        (contribution << 1) ^ POLYNOMIAL
+                    } else {
+                        contribution << 1
+                    };
+                }
+                second ^ contribution
+            }
+        }
+    }
+}
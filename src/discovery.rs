@@ -0,0 +1,108 @@
+use crate::context::{serial_matches, CustomContext, UsbContext};
+use crate::error::{Error, Result};
+use crate::target::{DeviceIdentity, Target};
+use std::env;
+
+/// Target selection criteria, typically read from environment variables.
+///
+/// Scripts and CI jobs can steer which target a program connects to without the program passing
+/// options through all of its layers. Reading the environment is opt-in via [`from_env`], which
+/// takes the following variables into account:
+///
+/// * `PUNT_SERIAL`: Serial number or serial number pattern, see
+///   [`UsbContext::find_targets_matching`].
+/// * `PUNT_VID_PID`: USB vendor and product ID in hexadecimal, separated by a colon, e.g.
+///   `16c0:05dc`, for bootloaders with a USB identity of their own.
+/// * `PUNT_PORT`: Physical port path, see [`Target::port_path`].
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::Discovery;
+///
+/// # fn main() -> punt::Result<()> {
+/// let discovery = Discovery::from_env()?;
+/// let context = discovery.context()?;
+/// let mut target_handle = discovery.pick_target(&context)?.open()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`from_env`]: #method.from_env
+/// [`UsbContext::find_targets_matching`]: trait.UsbContext.html#method.find_targets_matching
+/// [`Target::port_path`]: struct.Target.html#method.port_path
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Discovery {
+    /// Serial number pattern the target has to match.
+    pub serial: Option<String>,
+
+    /// USB vendor and product ID to recognise targets by instead of the default ones.
+    pub vid_pid: Option<(u16, u16)>,
+
+    /// Port path the target has to be plugged into.
+    pub port: Option<String>,
+}
+
+impl Discovery {
+    /// Reads the selection criteria from the environment variables `PUNT_SERIAL`, `PUNT_VID_PID`
+    /// and `PUNT_PORT`. Unset or empty variables do not restrict the selection. Returns
+    /// [`Error::InvalidRequest`] if `PUNT_VID_PID` is malformed.
+    ///
+    /// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
+    pub fn from_env() -> Result<Self> {
+        let variable = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+        let vid_pid = match variable("PUNT_VID_PID") {
+            Some(value) => Some(parse_vid_pid(&value).ok_or(Error::InvalidRequest)?),
+            None => None,
+        };
+
+        Ok(Self {
+            serial: variable("PUNT_SERIAL"),
+            vid_pid,
+            port: variable("PUNT_PORT"),
+        })
+    }
+
+    /// Creates a USB context recognising targets by the configured vendor and product ID, if any.
+    /// The manufacturer and product strings of the upstream punt bootloader are kept.
+    pub fn context(&self) -> Result<CustomContext> {
+        let mut identity = DeviceIdentity::default();
+        if let Some((vendor_id, product_id)) = self.vid_pid {
+            identity.vendor_id = vendor_id;
+            identity.product_id = product_id;
+        }
+        CustomContext::new(identity)
+    }
+
+    /// Picks the target matching the criteria. Without a port, this behaves like
+    /// [`UsbContext::pick_target`]. With a port, the target plugged into it is returned if its
+    /// serial number matches as well.
+    ///
+    /// [`UsbContext::pick_target`]: trait.UsbContext.html#method.pick_target
+    pub fn pick_target<T: UsbContext>(&self, context: &T) -> Result<Target<T>> {
+        match &self.port {
+            Some(port) => {
+                let target = context.pick_target_by_port(port)?;
+                match &self.serial {
+                    Some(serial) if !serial_matches(serial, &target.serial()?) => {
+                        Err(Error::TargetNotFound)
+                    }
+                    _ => Ok(target),
+                }
+            }
+            None => context.pick_target(self.serial.as_deref()),
+        }
+    }
+}
+
+/// Parses a vendor and product ID pair like `16c0:05dc`.
+fn parse_vid_pid(value: &str) -> Option<(u16, u16)> {
+    let (vendor_id, product_id) = value.split_once(':')?;
+    Some((
+        u16::from_str_radix(vendor_id.trim(), 16).ok()?,
+        u16::from_str_radix(product_id.trim(), 16).ok()?,
+    ))
+}
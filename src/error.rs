@@ -1,9 +1,40 @@
+use crate::bootloader_info::Version;
+use crate::target_handle::Command;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
 use std::result::Result as StdResult;
+use std::sync::Arc;
+
+/// Short descriptions of the codes returned by [`Error::code`], indexed by code, with 0 meaning
+/// success. They are null-terminated so that the C API can hand them out as they are.
+///
+/// [`Error::code`]: enum.Error.html#method.code
+pub(crate) const CODE_DESCRIPTIONS: [&str; 12] = [
+    "Success\0",
+    "Invalid request\0",
+    "Target not found\0",
+    "Target is unsupported\0",
+    "Too many matches\0",
+    "Flash erase error\0",
+    "Verification error\0",
+    "USB error\0",
+    "Malformed response\0",
+    "Data looks like an Intel HEX or S-record file, not a raw binary\0",
+    "Bootloader version is not supported\0",
+    "I/O error\0",
+];
 
 /// Errors which can occur during target setup and communication.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+///
+/// Further variants may be added in future versions, so matches on it need a wildcard arm. The
+/// underlying error of an [`IoError`] or [`Io`] is available via [`source`] as well.
+///
+/// [`IoError`]: #variant.IoError
+/// [`Io`]: #variant.Io
+/// [`source`]: https://doc.rust-lang.org/std/error/trait.Error.html#method.source
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum Error {
     /// An operation could not be performed because it was prohibited by safety checks (e.g.
     /// programming at an odd address)
@@ -23,33 +54,361 @@ pub enum Error {
     /// An error was reported during the erase from the target.
     EraseError(EraseError),
 
-    /// Verifying memory contents via CRC failed.
-    VerificationError,
+    /// Verifying memory contents failed. The mismatch describes the differing area and both
+    /// checksums.
+    VerificationError(CrcMismatch),
 
     /// An error occurred during the raw USB communication.
     IoError(rusb::Error),
 
     /// The target responded with unexpected data.
     MalformedResponse,
+
+    /// The data to be programmed looks like the text of an Intel HEX or Motorola S-record file
+    /// rather than a raw binary. Such files have to be converted before programming.
+    TextualImage,
+
+    /// The bootloader's version indicates a protocol this crate does not support.
+    IncompatibleBootloader {
+        /// Version reported by the bootloader.
+        device: Version,
+
+        /// Major versions supported by this crate.
+        supported: std::ops::RangeInclusive<u8>,
+    },
+
+    /// A step of an operation failed with the contained error. The context describes where in the
+    /// operation the failure occurred.
+    Context(ErrorContext, Box<Error>),
+
+    /// An I/O error occurred in a transport not using libusb, e.g. the network connection of a
+    /// [`TcpTransport`]. Two such errors compare equal if their kinds and messages match.
+    ///
+    /// [`TcpTransport`]: remote/struct.TcpTransport.html
+    Io(Arc<std::io::Error>),
+}
+
+/// Location of a failure within an operation, e.g. the chunk a program operation was writing.
+///
+/// All errors returned by the steps of erase, program, read and verify operations carry a context,
+/// except for [`Error::VerificationError`], which describes a mismatch rather than a failed
+/// transfer.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::{Command, Error, Operation};
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// let base = target_handle.bootloader_info()?.application_base;
+/// target_handle.transport_mut().inject_failures(&[rusb::Error::Timeout]);
+///
+/// let error = target_handle.program_at(&[0x55; 256], base)?.execute().unwrap_err();
+/// let context = error.context().unwrap();
+/// assert_eq!(context.command, Command::Program);
+/// assert_eq!(context.address, base);
+/// assert_eq!(error.root(), &Error::IoError(rusb::Error::Timeout));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::VerificationError`]: enum.Error.html#variant.VerificationError
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorContext {
+    /// The bootloader command which failed.
+    pub command: Command,
+
+    /// Target address the failed step started at.
+    pub address: u32,
+
+    /// Index of the failed step within the operation, e.g. the chunk or page number.
+    pub chunk: usize,
+
+    /// Progress of the operation before the failure, in the operation's units, i.e. pages for
+    /// erase operations and bytes otherwise.
+    pub completed: usize,
 }
 
-impl StdError for Error {}
+impl Error {
+    /// Returns a numeric code identifying the kind of error. Codes are stable across crate versions
+    /// and never reused, so they can be relied upon by other programs processing errors.
+    ///
+    /// | Code | Variant                    |
+    /// |------|----------------------------|
+    /// | 1    | [`InvalidRequest`]         |
+    /// | 2    | [`TargetNotFound`]         |
+    /// | 3    | [`UnsupportedTarget`]      |
+    /// | 4    | [`TooManyMatches`]         |
+    /// | 5    | [`EraseError`]             |
+    /// | 6    | [`VerificationError`]      |
+    /// | 7    | [`IoError`]                |
+    /// | 8    | [`MalformedResponse`]      |
+    /// | 9    | [`TextualImage`]           |
+    /// | 10   | [`IncompatibleBootloader`] |
+    /// | 11   | [`Io`]                     |
+    ///
+    /// A [`Context`] error returns the code of the error it contains.
+    ///
+    /// [`InvalidRequest`]: #variant.InvalidRequest
+    /// [`TargetNotFound`]: #variant.TargetNotFound
+    /// [`UnsupportedTarget`]: #variant.UnsupportedTarget
+    /// [`TooManyMatches`]: #variant.TooManyMatches
+    /// [`EraseError`]: #variant.EraseError
+    /// [`VerificationError`]: #variant.VerificationError
+    /// [`IoError`]: #variant.IoError
+    /// [`MalformedResponse`]: #variant.MalformedResponse
+    /// [`TextualImage`]: #variant.TextualImage
+    /// [`IncompatibleBootloader`]: #variant.IncompatibleBootloader
+    /// [`Io`]: #variant.Io
+    /// [`Context`]: #variant.Context
+    pub fn code(&self) -> u16 {
+        match self {
+            Error::InvalidRequest => 1,
+            Error::TargetNotFound => 2,
+            Error::UnsupportedTarget => 3,
+            Error::TooManyMatches => 4,
+            Error::EraseError(_) => 5,
+            Error::VerificationError(_) => 6,
+            Error::IoError(_) => 7,
+            Error::MalformedResponse => 8,
+            Error::TextualImage => 9,
+            Error::IncompatibleBootloader { .. } => 10,
+            Error::Io(_) => 11,
+            Error::Context(_, error) => error.code(),
+        }
+    }
+
+    /// Returns how serious the error is, i.e. whether repeating the request may help and whether
+    /// the target's flash may have been left in an inconsistent state.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::{Error, Severity};
+    ///
+    /// assert_eq!(Error::IoError(rusb::Error::Timeout).severity(), Severity::Transient);
+    /// assert_eq!(Error::InvalidRequest.severity(), Severity::Error);
+    /// ```
+    pub fn severity(&self) -> Severity {
+        if self.is_transient() {
+            return Severity::Transient;
+        }
+        match self.root() {
+            Error::EraseError(_) | Error::VerificationError(_) => Severity::Critical,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Returns what can be done to recover from the error, if anything.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::{Error, RecoveryHint};
+    ///
+    /// let error = Error::IoError(rusb::Error::NoDevice);
+    /// assert_eq!(error.recovery_hint(), Some(RecoveryHint::Reconnect));
+    /// println!("{}", error.recovery_hint().unwrap());
+    /// ```
+    pub fn recovery_hint(&self) -> Option<RecoveryHint> {
+        if self.is_transient() {
+            return Some(RecoveryHint::Retry);
+        }
+        Some(match self.root() {
+            Error::InvalidRequest | Error::TextualImage => RecoveryHint::FixRequest,
+            Error::TargetNotFound | Error::TooManyMatches => RecoveryHint::SelectTarget,
+            Error::VerificationError(_) => RecoveryHint::Reflash,
+            Error::IoError(rusb::Error::NoDevice) => RecoveryHint::Reconnect,
+            Error::IoError(rusb::Error::Access) => RecoveryHint::CheckPermissions,
+            Error::IoError(rusb::Error::Busy) => RecoveryHint::CloseOtherPrograms,
+            Error::Io(error) => match error.kind() {
+                ErrorKind::NotFound
+                | ErrorKind::NotConnected
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof => RecoveryHint::Reconnect,
+                ErrorKind::PermissionDenied => RecoveryHint::CheckPermissions,
+                _ => return None,
+            },
+            Error::IncompatibleBootloader { .. } => RecoveryHint::Update,
+            _ => return None,
+        })
+    }
+
+    /// Returns the name of the variant, used as a human-readable counterpart to [`code`].
+    ///
+    /// [`code`]: #method.code
+    #[cfg(feature = "serde")]
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::InvalidRequest => "InvalidRequest",
+            Error::TargetNotFound => "TargetNotFound",
+            Error::UnsupportedTarget => "UnsupportedTarget",
+            Error::TooManyMatches => "TooManyMatches",
+            Error::EraseError(_) => "EraseError",
+            Error::VerificationError(_) => "VerificationError",
+            Error::IoError(_) => "IoError",
+            Error::MalformedResponse => "MalformedResponse",
+            Error::TextualImage => "TextualImage",
+            Error::IncompatibleBootloader { .. } => "IncompatibleBootloader",
+            Error::Io(_) => "Io",
+            Error::Context(_, error) => error.kind(),
+        }
+    }
+
+    /// Returns where in an operation the error occurred, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Context(context, _) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error, with any context removed.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context(_, error) => error.root(),
+            error => error,
+        }
+    }
+
+    /// Attaches the location of a failed step to the error, replacing any context it already
+    /// carries. Verification errors are returned unchanged, since they describe a mismatch rather
+    /// than a failed step.
+    pub(crate) fn in_step(
+        self,
+        command: Command,
+        address: u32,
+        chunk: usize,
+        completed: usize,
+    ) -> Error {
+        let error = match self {
+            Error::Context(_, error) => *error,
+            error => error,
+        };
+        if let Error::VerificationError(_) = error {
+            return error;
+        }
+        Error::Context(
+            ErrorContext {
+                command,
+                address,
+                chunk,
+                completed,
+            },
+            Box::new(error),
+        )
+    }
+}
+
+impl Error {
+    /// Returns `true` for errors which may not occur again when the failed transfer is repeated,
+    /// e.g. timeouts on a flaky connection.
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            Error::Context(_, error) => return error.is_transient(),
+            Error::Io(error) => {
+                return matches!(
+                    error.kind(),
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                )
+            }
+            _ => (),
+        }
+        matches!(
+            self,
+            Error::IoError(
+                rusb::Error::Io
+                    | rusb::Error::Timeout
+                    | rusb::Error::Pipe
+                    | rusb::Error::Overflow
+                    | rusb::Error::Interrupted
+            )
+        )
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::IoError(error) => Some(error),
+            Error::Io(error) => Some(error.as_ref()),
+            Error::Context(_, error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::InvalidRequest, Error::InvalidRequest)
+            | (Error::TargetNotFound, Error::TargetNotFound)
+            | (Error::UnsupportedTarget, Error::UnsupportedTarget)
+            | (Error::TooManyMatches, Error::TooManyMatches)
+            | (Error::MalformedResponse, Error::MalformedResponse)
+            | (Error::TextualImage, Error::TextualImage) => true,
+            (Error::EraseError(a), Error::EraseError(b)) => a == b,
+            (Error::VerificationError(a), Error::VerificationError(b)) => a == b,
+            (Error::IoError(a), Error::IoError(b)) => a == b,
+            (
+                Error::IncompatibleBootloader {
+                    device: a_device,
+                    supported: a_supported,
+                },
+                Error::IncompatibleBootloader {
+                    device: b_device,
+                    supported: b_supported,
+                },
+            ) => a_device == b_device && a_supported == b_supported,
+            (Error::Context(a_context, a), Error::Context(b_context, b)) => {
+                a_context == b_context && a == b
+            }
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind() && a.to_string() == b.to_string(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Error {}
 
 impl Display for Error {
     fn fmt(&self, fmt: &mut Formatter) -> StdResult<(), std::fmt::Error> {
         if let Error::IoError(err) = self {
             write!(fmt, "IO Error: {}", err)
+        } else if let Error::Io(err) = self {
+            write!(fmt, "I/O error: {}", err)
+        } else if let Error::VerificationError(mismatch) = self {
+            write!(
+                fmt,
+                "Verification error at 0x{:08x}..0x{:08x}: expected CRC 0x{:08x}, target reported \
+                 0x{:08x}",
+                mismatch.address,
+                mismatch.end(),
+                mismatch.expected,
+                mismatch.actual
+            )
+        } else if let Error::IncompatibleBootloader { device, supported } = self {
+            write!(
+                fmt,
+                "Bootloader version {} is incompatible, supported major versions are {} to {}",
+                device,
+                supported.start(),
+                supported.end()
+            )
+        } else if let Error::Context(context, error) = self {
+            write!(
+                fmt,
+                "{} ({:?} at 0x{:08x}, step {}, {} completed)",
+                error, context.command, context.address, context.chunk, context.completed
+            )
         } else {
-            fmt.write_str(match self {
-                Error::InvalidRequest => "Invalid request.",
-                Error::TargetNotFound => "Target not found",
-                Error::UnsupportedTarget => "Target is unsupported",
-                Error::TooManyMatches => "Too many matches",
-                Error::EraseError(_) => "Flash erase error",
-                Error::VerificationError => "Verification error",
-                Error::MalformedResponse => "Malformed response",
-                Error::IoError(_) => unreachable!(),
-            })
+            fmt.write_str(CODE_DESCRIPTIONS[usize::from(self.code())].trim_end_matches('\0'))
         }
     }
 }
@@ -60,8 +419,136 @@ impl From<rusb::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    /// Wraps an I/O error, e.g. from a network connection, keeping it as the source.
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(Arc::new(error))
+    }
+}
+
+/// Area which failed verification, with the checksum calculated on the host and the one
+/// reported by the target.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::Error;
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// target_handle.flash_bytes(&[0x12, 0x34], 0x0800_1000)?;
+///
+/// match target_handle.verify(&[0x56, 0x78], 0x0800_1000) {
+///     Err(Error::VerificationError(mismatch)) => {
+///         assert_eq!(mismatch.address, 0x0800_1000);
+///         assert_eq!(mismatch.length, 2);
+///         assert_ne!(mismatch.expected, mismatch.actual);
+///     }
+///     result => panic!("unexpected result {:?}", result),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrcMismatch {
+    /// Start address of the compared area.
+    pub address: u32,
+
+    /// Length of the compared area in bytes.
+    pub length: usize,
+
+    /// Checksum of the data on the host.
+    pub expected: u32,
+
+    /// Checksum of the memory contents reported by the target.
+    pub actual: u32,
+}
+
+impl CrcMismatch {
+    /// Returns the address following the compared area.
+    pub fn end(&self) -> u32 {
+        self.address + self.length as u32
+    }
+}
+
+/// How serious an [`Error`] is, as returned by [`Error::severity`].
+///
+/// [`Error`]: enum.Error.html
+/// [`Error::severity`]: enum.Error.html#method.severity
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// The error may not occur again when the request is repeated, e.g. a USB timeout.
+    Transient,
+
+    /// The request failed, but the target's flash is either unchanged or in the state the request
+    /// left it in.
+    Error,
+
+    /// The target's flash does not contain what it should, e.g. after a failed erase or
+    /// verification. The application should not be started before the flash has been programmed
+    /// successfully.
+    Critical,
+}
+
+/// Suggested action to recover from an [`Error`], as returned by [`Error::recovery_hint`]. Its
+/// `Display` implementation gives a short instruction suitable for users.
+///
+/// [`Error`]: enum.Error.html
+/// [`Error::recovery_hint`]: enum.Error.html#method.recovery_hint
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum RecoveryHint {
+    /// Repeat the failed request.
+    Retry,
+
+    /// Reconnect to the target, which has been disconnected or reset.
+    Reconnect,
+
+    /// Erase and program the affected area again.
+    Reflash,
+
+    /// Correct the request, e.g. the address or the image format.
+    FixRequest,
+
+    /// Check the target selection, e.g. the serial number, and that the target is in bootloader
+    /// mode.
+    SelectTarget,
+
+    /// Grant the user access to the USB device, e.g. via a udev rule.
+    CheckPermissions,
+
+    /// Close other programs which have claimed the USB device.
+    CloseOtherPrograms,
+
+    /// Update the bootloader or this software to versions compatible with each other.
+    Update,
+}
+
+impl Display for RecoveryHint {
+    fn fmt(&self, fmt: &mut Formatter) -> StdResult<(), std::fmt::Error> {
+        fmt.write_str(match self {
+            RecoveryHint::Retry => "Try again",
+            RecoveryHint::Reconnect => "Reconnect the target",
+            RecoveryHint::Reflash => "Erase and program the target again",
+            RecoveryHint::FixRequest => "Check the address and the image",
+            RecoveryHint::SelectTarget => {
+                "Check that the target is connected and in bootloader mode"
+            }
+            RecoveryHint::CheckPermissions => "Check the permissions for the USB device",
+            RecoveryHint::CloseOtherPrograms => "Close other programs using the target",
+            RecoveryHint::Update => "Update the bootloader or this program",
+        })
+    }
+}
+
 /// Error during flash erasing.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum EraseError {
     /// Erasing an area which should or could not be erased was attempted.
     Prohibited = 1,
@@ -83,5 +570,205 @@ impl From<u8> for EraseError {
     }
 }
 
+/// Serializes an error as a structure with its stable numeric `code`, its `kind`, a `message` and
+/// its `severity`. Errors carrying additional information add it as extra fields: `erase_error` for
+/// [`Error::EraseError`], `mismatch` for [`Error::VerificationError`], `usb_error` for
+/// [`Error::IoError`], `io_error` for [`Error::Io`] and `device` and `supported` for
+/// [`Error::IncompatibleBootloader`]. Errors with an [`Error::Context`] are serialized like the
+/// error they contain, with an additional `context` field.
+///
+/// [`Error::EraseError`]: enum.Error.html#variant.EraseError
+/// [`Error::VerificationError`]: enum.Error.html#variant.VerificationError
+/// [`Error::IoError`]: enum.Error.html#variant.IoError
+/// [`Error::Io`]: enum.Error.html#variant.Io
+/// [`Error::IncompatibleBootloader`]: enum.Error.html#variant.IncompatibleBootloader
+/// [`Error::Context`]: enum.Error.html#variant.Context
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut fields = match self.root() {
+            Error::EraseError(_)
+            | Error::VerificationError(_)
+            | Error::IoError(_)
+            | Error::Io(_) => 5,
+            Error::IncompatibleBootloader { .. } => 6,
+            _ => 4,
+        };
+        if self.context().is_some() {
+            fields += 1;
+        }
+        let mut state = serializer.serialize_struct("Error", fields)?;
+        state.serialize_field("code", &self.code())?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("severity", &self.severity())?;
+        match self.root() {
+            Error::EraseError(error) => state.serialize_field("erase_error", error)?,
+            Error::VerificationError(mismatch) => state.serialize_field("mismatch", mismatch)?,
+            Error::IoError(error) => state.serialize_field("usb_error", usb_error_name(error))?,
+            Error::Io(error) => state.serialize_field("io_error", &error.to_string())?,
+            Error::IncompatibleBootloader { device, supported } => {
+                state.serialize_field("device", device)?;
+                state.serialize_field("supported", supported)?;
+            }
+            _ => (),
+        }
+        if let Some(context) = self.context() {
+            state.serialize_field("context", context)?;
+        }
+        state.end()
+    }
+}
+
+/// Deserializes an error from the structure written by its `Serialize` implementation. The
+/// variant is identified by its `code`; `kind`, `message` and `severity` are ignored. An
+/// [`Error::Io`] is restored with its message only, as the kind of the I/O error is not recorded.
+///
+/// [`Error::Io`]: enum.Error.html#variant.Io
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Error {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> StdResult<Self, D::Error> {
+        use serde::de::Error as _;
+
+        #[derive(serde::Deserialize)]
+        struct Fields {
+            code: u16,
+            erase_error: Option<EraseError>,
+            mismatch: Option<CrcMismatch>,
+            usb_error: Option<String>,
+            io_error: Option<String>,
+            device: Option<Version>,
+            supported: Option<std::ops::RangeInclusive<u8>>,
+            context: Option<ErrorContext>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let error = match fields.code {
+            1 => Error::InvalidRequest,
+            2 => Error::TargetNotFound,
+            3 => Error::UnsupportedTarget,
+            4 => Error::TooManyMatches,
+            5 => Error::EraseError(
+                fields
+                    .erase_error
+                    .ok_or_else(|| D::Error::missing_field("erase_error"))?,
+            ),
+            6 => Error::VerificationError(
+                fields
+                    .mismatch
+                    .ok_or_else(|| D::Error::missing_field("mismatch"))?,
+            ),
+            7 => {
+                let name = fields
+                    .usb_error
+                    .ok_or_else(|| D::Error::missing_field("usb_error"))?;
+                Error::IoError(usb_error_from_name(&name).ok_or_else(|| {
+                    D::Error::invalid_value(
+                        serde::de::Unexpected::Str(&name),
+                        &"a libusb error name",
+                    )
+                })?)
+            }
+            8 => Error::MalformedResponse,
+            9 => Error::TextualImage,
+            10 => Error::IncompatibleBootloader {
+                device: fields
+                    .device
+                    .ok_or_else(|| D::Error::missing_field("device"))?,
+                supported: fields
+                    .supported
+                    .ok_or_else(|| D::Error::missing_field("supported"))?,
+            },
+            11 => Error::Io(Arc::new(std::io::Error::other(
+                fields
+                    .io_error
+                    .ok_or_else(|| D::Error::missing_field("io_error"))?,
+            ))),
+            code => {
+                return Err(D::Error::invalid_value(
+                    serde::de::Unexpected::Unsigned(code.into()),
+                    &"a punt error code",
+                ))
+            }
+        };
+        Ok(match fields.context {
+            Some(context) => Error::Context(context, Box::new(error)),
+            None => error,
+        })
+    }
+}
+
+/// Returns a stable name for a libusb error, independent of rusb's `Debug` output.
+pub(crate) fn usb_error_name(error: &rusb::Error) -> &'static str {
+    match error {
+        rusb::Error::Io => "Io",
+        rusb::Error::InvalidParam => "InvalidParam",
+        rusb::Error::Access => "Access",
+        rusb::Error::NoDevice => "NoDevice",
+        rusb::Error::NotFound => "NotFound",
+        rusb::Error::Busy => "Busy",
+        rusb::Error::Timeout => "Timeout",
+        rusb::Error::Overflow => "Overflow",
+        rusb::Error::Pipe => "Pipe",
+        rusb::Error::Interrupted => "Interrupted",
+        rusb::Error::NoMem => "NoMem",
+        rusb::Error::NotSupported => "NotSupported",
+        rusb::Error::BadDescriptor => "BadDescriptor",
+        rusb::Error::Other => "Other",
+    }
+}
+
+/// Parses a libusb error name as returned by [`usb_error_name`].
+///
+/// [`usb_error_name`]: fn.usb_error_name.html
+pub(crate) fn usb_error_from_name(name: &str) -> Option<rusb::Error> {
+    Some(match name {
+        "Io" => rusb::Error::Io,
+        "InvalidParam" => rusb::Error::InvalidParam,
+        "Access" => rusb::Error::Access,
+        "NoDevice" => rusb::Error::NoDevice,
+        "NotFound" => rusb::Error::NotFound,
+        "Busy" => rusb::Error::Busy,
+        "Timeout" => rusb::Error::Timeout,
+        "Overflow" => rusb::Error::Overflow,
+        "Pipe" => rusb::Error::Pipe,
+        "Interrupted" => rusb::Error::Interrupted,
+        "NoMem" => rusb::Error::NoMem,
+        "NotSupported" => rusb::Error::NotSupported,
+        "BadDescriptor" => rusb::Error::BadDescriptor,
+        "Other" => rusb::Error::Other,
+        _ => return None,
+    })
+}
+
 /// Shorthand for a Result with the crate's own Error type.
 pub type Result<T> = StdResult<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn io_errors_keep_their_source() {
+        let error = Error::from(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "refused by peer",
+        ));
+        let source = error
+            .source()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(source.to_string(), "refused by peer");
+        assert_eq!(error.code(), 11);
+        assert_eq!(error.recovery_hint(), Some(RecoveryHint::Reconnect));
+        assert_eq!(error, error.clone());
+
+        let timeout = Error::from(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(timeout.is_transient());
+        assert_ne!(timeout, error);
+    }
+}
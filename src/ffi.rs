@@ -0,0 +1,325 @@
+//! Contains a flat C API for using the crate from C and C++ programs.
+//!
+//! The declarations are in `include/punt.h`, which is generated from this module with
+//! [cbindgen] and checked to be up to date by CI. After changing the API, regenerate it with:
+//!
+//! ```text
+//! cbindgen --config cbindgen.toml --output include/punt.h
+//! ```
+//!
+//! The crate is built as a shared and a static library as well, so the functions are exported by
+//! `libpunt.so` (or `punt.dll`) and `libpunt.a` after running:
+//!
+//! ```text
+//! cargo build --release --features ffi
+//! ```
+//!
+//! All functions returning `int` return `PUNT_OK` (0) on success and the stable error code of
+//! [`Error::code`] otherwise. [`punt_error_message`] describes a code.
+//!
+//! [`Error::code`]: ../enum.Error.html#method.code
+//! [`punt_error_message`]: fn.punt_error_message.html
+//! [cbindgen]: https://github.com/mozilla/cbindgen
+
+use crate::context::{Context, UsbContext};
+use crate::error::{Error, Result, CODE_DESCRIPTIONS};
+use crate::firmware::FirmwareImage;
+use crate::operation::Operation;
+use crate::target_handle::TargetHandle;
+use rusb::DeviceHandle;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::slice;
+
+/// Return value of successful calls.
+pub const PUNT_OK: c_int = 0;
+
+/// Return code of `Error::InvalidRequest`.
+pub const PUNT_INVALID_REQUEST: c_int = 1;
+
+/// Return code of `Error::TargetNotFound`.
+pub const PUNT_TARGET_NOT_FOUND: c_int = 2;
+
+/// Return code of `Error::UnsupportedTarget`.
+pub const PUNT_UNSUPPORTED_TARGET: c_int = 3;
+
+/// Return code of `Error::TooManyMatches`.
+pub const PUNT_TOO_MANY_MATCHES: c_int = 4;
+
+/// Return code of `Error::EraseError`.
+pub const PUNT_ERASE_ERROR: c_int = 5;
+
+/// Return code of `Error::VerificationError`.
+pub const PUNT_VERIFICATION_ERROR: c_int = 6;
+
+/// Return code of `Error::IoError`.
+pub const PUNT_IO_ERROR: c_int = 7;
+
+/// Return code of `Error::MalformedResponse`.
+pub const PUNT_MALFORMED_RESPONSE: c_int = 8;
+
+/// Return code of `Error::TextualImage`.
+pub const PUNT_TEXTUAL_IMAGE: c_int = 9;
+
+/// Return code of `Error::IncompatibleBootloader`.
+pub const PUNT_INCOMPATIBLE_BOOTLOADER: c_int = 10;
+
+/// Return code of `Error::Io`.
+pub const PUNT_IO: c_int = 11;
+
+/// Opaque handle to a connected target, created by [`punt_open`] and freed by [`punt_close`].
+///
+/// [`punt_open`]: fn.punt_open.html
+/// [`punt_close`]: fn.punt_close.html
+pub struct PuntTarget {
+    handle: TargetHandle<DeviceHandle<Context>>,
+}
+
+/// Progress callback receiving the user data pointer, the progress so far and the total, both in
+/// bytes.
+pub type PuntProgressCallback = Option<extern "C" fn(*mut c_void, usize, usize)>;
+
+/// Converts a result into a return code.
+fn return_code(result: Result<()>) -> c_int {
+    match result {
+        Ok(()) => PUNT_OK,
+        Err(error) => error.code().into(),
+    }
+}
+
+/// Runs an operation to completion, calling the progress callback after every step.
+fn run(
+    mut operation: impl Operation,
+    callback: PuntProgressCallback,
+    user_data: *mut c_void,
+) -> Result<()> {
+    let total = operation.total();
+    for done in &mut operation {
+        let done = done?;
+        if let Some(callback) = callback {
+            callback(user_data, done, total);
+        }
+    }
+    Ok(())
+}
+
+/// Opens the target with the given serial number, or the only connected target if `serial` is
+/// null, and stores its handle in `target`.
+///
+/// # Safety
+///
+/// `serial` has to be null or point to a null-terminated string. `target` has to point to writable
+/// memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn punt_open(serial: *const c_char, target: *mut *mut PuntTarget) -> c_int {
+    if target.is_null() {
+        return Error::InvalidRequest.code().into();
+    }
+    let serial = if serial.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(serial).to_str() {
+            Ok(serial) => Some(serial),
+            Err(_) => return Error::InvalidRequest.code().into(),
+        }
+    };
+
+    return_code(
+        Context::new()
+            .map_err(Error::from)
+            .and_then(|context| context.open_target(serial))
+            .map(|handle| *target = Box::into_raw(Box::new(PuntTarget { handle }))),
+    )
+}
+
+/// Closes a target opened with [`punt_open`]. Does nothing if `target` is null.
+///
+/// # Safety
+///
+/// `target` has to be null or a handle returned by [`punt_open`] which has not been closed yet.
+///
+/// [`punt_open`]: fn.punt_open.html
+#[no_mangle]
+pub unsafe extern "C" fn punt_close(target: *mut PuntTarget) {
+    if !target.is_null() {
+        drop(Box::from_raw(target));
+    }
+}
+
+/// Queries the start address and size of the application flash.
+///
+/// # Safety
+///
+/// `target` has to be a valid handle and `base` and `size` have to point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn punt_application_area(
+    target: *mut PuntTarget,
+    base: *mut u32,
+    size: *mut usize,
+) -> c_int {
+    if target.is_null() || base.is_null() || size.is_null() {
+        return Error::InvalidRequest.code().into();
+    }
+    return_code((*target).handle.bootloader_info().map(|info| {
+        *base = info.application_base;
+        *size = info.application_size;
+    }))
+}
+
+/// Erases the pages needed for the data, programs and verifies it. The progress callback, if not
+/// null, is called with `user_data` after every step.
+///
+/// # Safety
+///
+/// `target` has to be a valid handle and `data` has to point to `length` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn punt_flash(
+    target: *mut PuntTarget,
+    data: *const u8,
+    length: usize,
+    address: u32,
+    callback: PuntProgressCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let end = u32::try_from(length)
+        .ok()
+        .and_then(|length| address.checked_add(length));
+    if target.is_null() || (data.is_null() && length > 0) || end.is_none() {
+        return Error::InvalidRequest.code().into();
+    }
+    let data = match length {
+        0 => Vec::new(),
+        _ => slice::from_raw_parts(data, length).to_vec(),
+    };
+    let image = FirmwareImage::from_binary(address, data);
+    return_code(
+        (*target)
+            .handle
+            .update_image(&image)
+            .and_then(|update| run(update, callback, user_data)),
+    )
+}
+
+/// Reads `length` bytes of memory starting at `address` into `buffer`. The progress callback, if
+/// not null, is called with `user_data` after every step.
+///
+/// # Safety
+///
+/// `target` has to be a valid handle and `buffer` has to point to `length` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn punt_read(
+    target: *mut PuntTarget,
+    buffer: *mut u8,
+    length: usize,
+    address: u32,
+    callback: PuntProgressCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    if target.is_null() || (buffer.is_null() && length > 0) {
+        return Error::InvalidRequest.code().into();
+    }
+    let buffer = match length {
+        0 => &mut [],
+        _ => slice::from_raw_parts_mut(buffer, length),
+    };
+    return_code(
+        (*target)
+            .handle
+            .read_at(buffer, address)
+            .and_then(|read| run(read, callback, user_data)),
+    )
+}
+
+/// Lets the target exit from the bootloader and start its application. The handle has to be
+/// closed afterwards.
+///
+/// # Safety
+///
+/// `target` has to be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn punt_exit(target: *mut PuntTarget) -> c_int {
+    if target.is_null() {
+        return Error::InvalidRequest.code().into();
+    }
+    return_code((*target).handle.exit_bootloader())
+}
+
+/// Returns a static, null-terminated description of a return code.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::ffi::punt_error_message;
+/// use std::ffi::CStr;
+///
+/// let message = unsafe { CStr::from_ptr(punt_error_message(2)) };
+/// assert_eq!(message.to_str(), Ok("Target not found"));
+/// ```
+#[no_mangle]
+pub extern "C" fn punt_error_message(code: c_int) -> *const c_char {
+    let message = usize::try_from(code)
+        .ok()
+        .and_then(|code| CODE_DESCRIPTIONS.get(code))
+        .copied()
+        .unwrap_or("Unknown error\0");
+    message.as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bootloader_info::Version;
+    use crate::error::{CrcMismatch, EraseError};
+
+    #[test]
+    fn constants_match_error_codes() {
+        let mismatch = CrcMismatch {
+            address: 0,
+            length: 0,
+            expected: 0,
+            actual: 0,
+        };
+        let errors = [
+            (PUNT_INVALID_REQUEST, Error::InvalidRequest),
+            (PUNT_TARGET_NOT_FOUND, Error::TargetNotFound),
+            (PUNT_UNSUPPORTED_TARGET, Error::UnsupportedTarget),
+            (PUNT_TOO_MANY_MATCHES, Error::TooManyMatches),
+            (PUNT_ERASE_ERROR, Error::EraseError(EraseError::Prohibited)),
+            (PUNT_VERIFICATION_ERROR, Error::VerificationError(mismatch)),
+            (PUNT_IO_ERROR, Error::IoError(rusb::Error::Io)),
+            (
+                PUNT_IO,
+                Error::from(std::io::Error::from(std::io::ErrorKind::Other)),
+            ),
+            (PUNT_MALFORMED_RESPONSE, Error::MalformedResponse),
+            (PUNT_TEXTUAL_IMAGE, Error::TextualImage),
+            (
+                PUNT_INCOMPATIBLE_BOOTLOADER,
+                Error::IncompatibleBootloader {
+                    device: Version {
+                        major: 0,
+                        minor: 0,
+                        patch: 0,
+                    },
+                    supported: Version::SUPPORTED_MAJOR_VERSIONS,
+                },
+            ),
+        ];
+        for (code, error) in errors.iter() {
+            assert_eq!(*code, c_int::from(error.code()));
+        }
+        assert_eq!(CODE_DESCRIPTIONS.len(), PUNT_IO as usize + 1);
+    }
+
+    #[test]
+    fn messages_are_null_terminated() {
+        for code in -1..=CODE_DESCRIPTIONS.len() as c_int {
+            let message = unsafe { CStr::from_ptr(punt_error_message(code)) };
+            assert!(!message.to_bytes().is_empty());
+        }
+        let message = unsafe { CStr::from_ptr(punt_error_message(PUNT_TOO_MANY_MATCHES)) };
+        assert_eq!(message.to_str(), Ok("Too many matches"));
+        assert_eq!(Error::TooManyMatches.to_string(), "Too many matches");
+    }
+}
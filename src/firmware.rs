@@ -0,0 +1,777 @@
+//! Contains firmware images and parsers for common firmware file formats.
+
+use crate::crc::crc32;
+use crate::flash::Page;
+use std::convert::{TryFrom, TryInto};
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+/// A contiguous block of data to be placed at a specific address.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Segment {
+    /// Address of the first byte.
+    pub address: u32,
+
+    /// Contents of the segment.
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    /// Returns the address directly after the last byte of the segment. This is 64 bits wide so that
+    /// a segment ending at the top of the address space does not overflow.
+    fn end(&self) -> u64 {
+        u64::from(self.address) + self.data.len() as u64
+    }
+}
+
+/// A firmware image consisting of one or more non-overlapping segments, plus metadata from the file
+/// it was loaded from.
+///
+/// All file formats and raw binaries are loaded into this type, which can be erased, programmed
+/// and verified as a whole with [`TargetHandle::erase_image`], [`TargetHandle::program_image`] and
+/// [`TargetHandle::verify_image`].
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{Context, FirmwareImage, Operation, UsbContext};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let image = FirmwareImage::from_ihex(&std::fs::read_to_string("firmware.hex")?)?;
+///
+/// let mut context = Context::new()?;
+/// let mut target_handle = context.open_target(None)?;
+/// target_handle.erase_image(&image)?.execute()?;
+/// target_handle.program_image(&image)?.execute()?;
+/// target_handle.verify_image(&image)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`TargetHandle::erase_image`]: ../struct.TargetHandle.html#method.erase_image
+/// [`TargetHandle::program_image`]: ../struct.TargetHandle.html#method.program_image
+/// [`TargetHandle::verify_image`]: ../struct.TargetHandle.html#method.verify_image
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FirmwareImage {
+    segments: Vec<Segment>,
+    entry_point: Option<u32>,
+    header: Option<String>,
+}
+
+impl FirmwareImage {
+    /// Parses the contents of an Intel HEX file.
+    ///
+    /// Data records are merged into as few segments as possible. Extended segment and extended
+    /// linear address records are supported. A start linear address record sets the image's entry
+    /// point, start segment address records are ignored. Parsing stops at the end-of-file record.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::FirmwareImage;
+    ///
+    /// let image = FirmwareImage::from_ihex(
+    ///     ":020000040800F2\n\
+    ///      :04000000005000208C\n\
+    ///      :04000400F1010008FE\n\
+    ///      :00000001FF\n",
+    /// )
+    /// .unwrap();
+    ///
+    /// let segments = image.segments();
+    /// assert_eq!(segments.len(), 1);
+    /// assert_eq!(segments[0].address, 0x0800_0000);
+    /// assert_eq!(segments[0].data, [0x00, 0x50, 0x00, 0x20, 0xf1, 0x01, 0x00, 0x08]);
+    /// ```
+    pub fn from_ihex(text: &str) -> Result<Self, ParseError> {
+        let mut chunks = Vec::new();
+        let mut base = 0u32;
+        let mut entry_point = None;
+        let mut end_of_file = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |kind| ParseError {
+                line: line_number,
+                kind,
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bytes = line
+                .strip_prefix(':')
+                .and_then(decode_hex)
+                .ok_or_else(|| error(ParseErrorKind::InvalidRecord))?;
+            if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+                return Err(error(ParseErrorKind::InvalidRecord));
+            }
+            if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+                return Err(error(ParseErrorKind::ChecksumMismatch));
+            }
+
+            let offset = u16::from_be_bytes([bytes[1], bytes[2]]);
+            let data = &bytes[4..bytes.len() - 1];
+            match bytes[3] {
+                0x00 => {
+                    let address = base
+                        .checked_add(offset.into())
+                        .ok_or_else(|| error(ParseErrorKind::InvalidRecord))?;
+                    chunks.push((line_number, address, data.to_vec()));
+                }
+                0x01 => {
+                    end_of_file = true;
+                    break;
+                }
+                0x02 if data.len() == 2 => {
+                    base = u32::from(u16::from_be_bytes([data[0], data[1]])) << 4;
+                }
+                0x04 if data.len() == 2 => {
+                    base = u32::from(u16::from_be_bytes([data[0], data[1]])) << 16;
+                }
+                0x05 if data.len() == 4 => {
+                    entry_point = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+                }
+                0x03 => (),
+                0x02 | 0x04 | 0x05 => return Err(error(ParseErrorKind::InvalidRecord)),
+                _ => return Err(error(ParseErrorKind::UnsupportedRecordType)),
+            }
+        }
+
+        if !end_of_file {
+            return Err(ParseError {
+                line: text.lines().count(),
+                kind: ParseErrorKind::MissingEndOfFile,
+            });
+        }
+
+        Ok(Self {
+            entry_point,
+            ..Self::from_chunks(chunks)?
+        })
+    }
+
+    /// Parses the contents of a Motorola S-record file.
+    ///
+    /// S1, S2 and S3 data records are merged into as few segments as possible. The contents of an S0
+    /// record are kept as the image's header and the address in the termination record (S7, S8 or
+    /// S9) as its entry point, if non-zero. Record count records are ignored. Parsing stops at the
+    /// termination record.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::FirmwareImage;
+    ///
+    /// let image = FirmwareImage::from_srec(
+    ///     "S00600004844521B\n\
+    ///      S30D0800000000500020F101000880\n\
+    ///      S70508000000F2\n",
+    /// )
+    /// .unwrap();
+    ///
+    /// let segments = image.segments();
+    /// assert_eq!(segments.len(), 1);
+    /// assert_eq!(segments[0].address, 0x0800_0000);
+    /// assert_eq!(segments[0].data, [0x00, 0x50, 0x00, 0x20, 0xf1, 0x01, 0x00, 0x08]);
+    /// ```
+    pub fn from_srec(text: &str) -> Result<Self, ParseError> {
+        let mut chunks = Vec::new();
+        let mut header = None;
+        let mut entry_point = None;
+        let mut end_of_file = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |kind| ParseError {
+                line: line_number,
+                kind,
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (record_type, bytes) = match line.as_bytes() {
+                [b'S', record_type, ..] => (
+                    *record_type,
+                    line.get(2..)
+                        .and_then(decode_hex)
+                        .ok_or_else(|| error(ParseErrorKind::InvalidRecord))?,
+                ),
+                _ => return Err(error(ParseErrorKind::InvalidRecord)),
+            };
+            if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+                return Err(error(ParseErrorKind::InvalidRecord));
+            }
+            if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0xff {
+                return Err(error(ParseErrorKind::ChecksumMismatch));
+            }
+
+            let address_length = match record_type {
+                b'0' | b'1' | b'5' | b'9' => 2,
+                b'2' | b'6' | b'8' => 3,
+                b'3' | b'7' => 4,
+                _ => return Err(error(ParseErrorKind::UnsupportedRecordType)),
+            };
+            if bytes.len() < address_length + 2 {
+                return Err(error(ParseErrorKind::InvalidRecord));
+            }
+            let address = bytes[1..=address_length]
+                .iter()
+                .fold(0u32, |address, b| address << 8 | u32::from(*b));
+            let data = &bytes[address_length + 1..bytes.len() - 1];
+
+            match record_type {
+                b'0' if !data.is_empty() => {
+                    header = Some(String::from_utf8_lossy(data).into_owned());
+                }
+                b'1' | b'2' | b'3' => chunks.push((line_number, address, data.to_vec())),
+                b'7' | b'8' | b'9' => {
+                    entry_point = Some(address).filter(|&address| address != 0);
+                    end_of_file = true;
+                    break;
+                }
+                _ => (),
+            }
+        }
+
+        if !end_of_file {
+            return Err(ParseError {
+                line: text.lines().count(),
+                kind: ParseErrorKind::MissingEndOfFile,
+            });
+        }
+
+        Ok(Self {
+            header,
+            entry_point,
+            ..Self::from_chunks(chunks)?
+        })
+    }
+
+    /// Parses a 32-bit little-endian ELF file as produced by the linker, e.g. by `cargo build`.
+    ///
+    /// The file contents of all loadable segments are placed at their physical addresses, which is
+    /// where initial values of variables are stored in flash. The ELF entry point becomes the
+    /// image's entry point. Sections are not taken into account. In a [`ParseError`] for an ELF
+    /// file, `line` is the number of the offending program header, starting at 1, or 0 for the file
+    /// header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::FirmwareImage;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let elf = std::fs::read("target/thumbv7m-none-eabi/release/firmware")?;
+    /// let image = FirmwareImage::from_elf(&elf)?;
+    /// println!("Entry point: {:08x?}", image.entry_point());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ParseError`]: struct.ParseError.html
+    pub fn from_elf(data: &[u8]) -> Result<Self, ParseError> {
+        const PT_LOAD: u32 = 1;
+
+        let error = |line, kind| ParseError { line, kind };
+        let word = |offset: usize| -> Option<u32> {
+            Some(u32::from_le_bytes(
+                data.get(offset..offset + 4)?.try_into().ok()?,
+            ))
+        };
+        let halfword = |offset: usize| -> Option<u16> {
+            Some(u16::from_le_bytes(
+                data.get(offset..offset + 2)?.try_into().ok()?,
+            ))
+        };
+
+        // Magic number, 32-bit class and little-endian data encoding
+        if data.get(0..6) != Some(&[0x7f, b'E', b'L', b'F', 1, 1]) {
+            return Err(error(0, ParseErrorKind::UnsupportedRecordType));
+        }
+        let header = || -> Option<(u32, usize, usize, usize)> {
+            Some((
+                word(24)?,
+                word(28)? as usize,
+                halfword(42)?.into(),
+                halfword(44)?.into(),
+            ))
+        };
+        let (entry, program_headers, header_size, header_count) =
+            header().ok_or_else(|| error(0, ParseErrorKind::InvalidRecord))?;
+
+        let mut chunks = Vec::new();
+        for index in 0..header_count {
+            let base = program_headers + index * header_size;
+            let segment = || -> Option<(u32, u32, usize, usize)> {
+                Some((
+                    word(base)?,
+                    word(base + 12)?,
+                    word(base + 4)? as usize,
+                    word(base + 16)? as usize,
+                ))
+            };
+            let (segment_type, address, offset, size) =
+                segment().ok_or_else(|| error(index + 1, ParseErrorKind::InvalidRecord))?;
+            if segment_type != PT_LOAD || size == 0 {
+                continue;
+            }
+            let contents = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| error(index + 1, ParseErrorKind::InvalidRecord))?;
+            chunks.push((index + 1, address, contents.to_vec()));
+        }
+
+        Ok(Self {
+            entry_point: Some(entry).filter(|&entry| entry != 0),
+            ..Self::from_chunks(chunks)?
+        })
+    }
+
+    /// Creates a firmware image from a single block of binary data located at the given address.
+    pub fn from_binary(address: u32, data: Vec<u8>) -> Self {
+        let segments = if data.is_empty() {
+            Vec::new()
+        } else {
+            vec![Segment { address, data }]
+        };
+        Self {
+            segments,
+            ..Self::default()
+        }
+    }
+
+    /// Formats the image as a Motorola S-record file.
+    ///
+    /// Depending on the highest address in the image, S1, S2 or S3 records are used, together with
+    /// the matching termination record. The header and entry point are included if set. A header
+    /// has to fit into a single S0 record, so only its first 252 bytes are written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::FirmwareImage;
+    ///
+    /// let dump = FirmwareImage::from_binary(0x0800_0000, vec![0x00, 0x50, 0x00, 0x20]);
+    /// let srec = dump.to_srec();
+    /// assert_eq!(srec, "S0030000FC\nS30908000000005000207E\nS70500000000FA\n");
+    /// assert_eq!(FirmwareImage::from_srec(&srec).unwrap(), dump);
+    /// ```
+    pub fn to_srec(&self) -> String {
+        const RECORD_LENGTH: usize = 32;
+
+        let end = self
+            .segments
+            .last()
+            .map_or(0, |s| u64::from(s.address) + s.data.len() as u64);
+        let (data_type, termination_type, address_length) = if end <= 0x1_0000 {
+            ('1', '9', 2)
+        } else if end <= 0x100_0000 {
+            ('2', '8', 3)
+        } else {
+            ('3', '7', 4)
+        };
+
+        let mut text = String::new();
+        let header = self
+            .header
+            .as_ref()
+            .map_or(&[][..], |header| header.as_bytes());
+        let header = &header[..header.len().min(MAX_SREC_DATA_LENGTH)];
+        write_srec(&mut text, '0', 2, 0, header);
+        for segment in &self.segments {
+            for (i, chunk) in segment.data.chunks(RECORD_LENGTH).enumerate() {
+                let address = segment.address + (i * RECORD_LENGTH) as u32;
+                write_srec(&mut text, data_type, address_length, address, chunk);
+            }
+        }
+        let entry_point = self.entry_point.unwrap_or(0);
+        write_srec(
+            &mut text,
+            termination_type,
+            address_length,
+            entry_point,
+            &[],
+        );
+        text
+    }
+
+    /// Returns all segments, ordered by address.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns the address execution starts at, if specified by the file the image was loaded
+    /// from.
+    pub fn entry_point(&self) -> Option<u32> {
+        self.entry_point
+    }
+
+    /// Sets the address execution starts at.
+    pub fn set_entry_point(&mut self, entry_point: Option<u32>) {
+        self.entry_point = entry_point;
+    }
+
+    /// Returns the header text, if the file the image was loaded from contained one.
+    pub fn header(&self) -> Option<&str> {
+        self.header.as_deref()
+    }
+
+    /// Sets the header text, which is written to S0 records.
+    pub fn set_header(&mut self, header: Option<String>) {
+        self.header = header;
+    }
+
+    /// Adds a [`Metadata`] block describing the image, so that an application checking its own
+    /// integrity finds the correct length and CRC32.
+    ///
+    /// The block covers all bytes from the image's first address up to the block itself, with
+    /// gaps between segments counted as erased flash (`0xff`). Its CRC32 is calculated like
+    /// [`crc32`], i.e. the way the target does it. A block placed within the image replaces the
+    /// data there, e.g. space reserved by the linker; otherwise the image is extended. Returns the
+    /// block's contents, or `None` if the image is empty or the block would not be word-aligned,
+    /// would lie before the image or would extend past the end of the address space.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::firmware::{MetadataPlacement, METADATA_SIZE};
+    /// use punt::{crc32, FirmwareImage};
+    ///
+    /// let mut image = FirmwareImage::from_binary(0x0800_1000, vec![0x12; 6]);
+    /// let metadata = image.inject_metadata(MetadataPlacement::Append, 3).unwrap();
+    /// assert_eq!(metadata.length, 8);
+    /// assert_eq!(metadata.crc, crc32(&[0x12, 0x12, 0x12, 0x12, 0x12, 0x12, 0xff, 0xff]));
+    ///
+    /// let segments = image.segments();
+    /// assert_eq!(segments.len(), 2);
+    /// assert_eq!(segments[1].address, 0x0800_1008);
+    /// assert_eq!(segments[1].data, metadata.to_bytes());
+    /// assert_eq!(segments[1].data.len(), METADATA_SIZE);
+    /// ```
+    ///
+    /// [`Metadata`]: struct.Metadata.html
+    /// [`crc32`]: ../fn.crc32.html
+    pub fn inject_metadata(
+        &mut self,
+        placement: MetadataPlacement,
+        version: u32,
+    ) -> Option<Metadata> {
+        let start = self.segments.first()?.address;
+        let address = match placement {
+            MetadataPlacement::Append => {
+                u32::try_from(self.segments.last()?.end().next_multiple_of(4)).ok()?
+            }
+            MetadataPlacement::Offset(offset) => start.checked_add(offset)?,
+        };
+        if !address.is_multiple_of(4)
+            || address < start
+            || address.checked_add(METADATA_SIZE as u32).is_none()
+        {
+            return None;
+        }
+
+        let mut covered = vec![0xff; (address - start) as usize];
+        for segment in &self.segments {
+            if segment.address >= address {
+                break;
+            }
+            let offset = (segment.address - start) as usize;
+            let length = segment.data.len().min(covered.len() - offset);
+            covered[offset..offset + length].copy_from_slice(&segment.data[..length]);
+        }
+
+        let metadata = Metadata {
+            length: covered.len() as u32,
+            crc: crc32(&covered),
+            version,
+        };
+        self.patch(address, &metadata.to_bytes());
+        Some(metadata)
+    }
+
+    /// Places data at the given address, replacing any image data already there.
+    fn patch(&mut self, address: u32, data: &[u8]) {
+        let end = u64::from(address) + data.len() as u64;
+        let mut chunks = vec![(0, address, data.to_vec())];
+        for segment in self.segments.drain(..) {
+            if segment.address < address {
+                let length = segment.data.len().min((address - segment.address) as usize);
+                chunks.push((0, segment.address, segment.data[..length].to_vec()));
+            }
+            if segment.end() > end {
+                let from = segment.address.max(end as u32);
+                let offset = (from - segment.address) as usize;
+                chunks.push((0, from, segment.data[offset..].to_vec()));
+            }
+        }
+
+        let patched = Self::from_chunks(chunks).expect("patched chunks do not overlap");
+        self.segments = patched.segments;
+    }
+
+    /// Returns a copy of the image with its segments replaced, keeping the metadata. Returns `None`
+    /// if the segments overlap.
+    pub(crate) fn with_segments(&self, segments: Vec<Segment>) -> Option<Self> {
+        let chunks = segments
+            .into_iter()
+            .map(|segment| (0, segment.address, segment.data))
+            .collect();
+        Some(Self {
+            entry_point: self.entry_point,
+            header: self.header.clone(),
+            ..Self::from_chunks(chunks).ok()?
+        })
+    }
+
+    /// Builds a firmware image from blocks of data, each tagged with the line it was read from.
+    /// Adjacent blocks are merged into a single segment.
+    fn from_chunks(mut chunks: Vec<(usize, u32, Vec<u8>)>) -> Result<Self, ParseError> {
+        chunks.sort_by_key(|(_, address, _)| *address);
+
+        let mut segments: Vec<Segment> = Vec::new();
+        for (line, address, data) in chunks {
+            if data.is_empty() {
+                continue;
+            }
+            if u64::from(address) + data.len() as u64 > 1 << 32 {
+                return Err(ParseError {
+                    line,
+                    kind: ParseErrorKind::InvalidRecord,
+                });
+            }
+            match segments.last_mut() {
+                Some(segment) if segment.end() > u64::from(address) => {
+                    return Err(ParseError {
+                        line,
+                        kind: ParseErrorKind::Overlap,
+                    });
+                }
+                Some(segment) if segment.end() == u64::from(address) => segment.data.extend(data),
+                _ => segments.push(Segment { address, data }),
+            }
+        }
+        Ok(Self {
+            segments,
+            ..Self::default()
+        })
+    }
+}
+
+/// Largest number of data bytes in an S-record with a 2-byte address. The record's byte count
+/// covers the address, the data and the checksum and has to fit into a single byte.
+const MAX_SREC_DATA_LENGTH: usize = 0xff - 2 - 1;
+
+/// Appends a single S-record line to a string. The data has to fit into the record's byte count.
+fn write_srec(
+    text: &mut String,
+    record_type: char,
+    address_length: usize,
+    address: u32,
+    data: &[u8],
+) {
+    use std::fmt::Write;
+
+    debug_assert!(address_length + data.len() < 0xff);
+    let mut bytes = vec![(address_length + data.len() + 1) as u8];
+    bytes.extend(&address.to_be_bytes()[4 - address_length..]);
+    bytes.extend(data);
+    let checksum = !bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    bytes.push(checksum);
+
+    text.push('S');
+    text.push(record_type);
+    for byte in bytes {
+        // Writing to a String cannot fail
+        let _ = write!(text, "{:02X}", byte);
+    }
+    text.push('\n');
+}
+
+/// Decodes a string of hexadecimal digit pairs.
+pub(crate) fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Size in bytes of a [`Metadata`] block.
+///
+/// [`Metadata`]: struct.Metadata.html
+pub const METADATA_SIZE: usize = 12;
+
+/// Where [`FirmwareImage::inject_metadata`] places the metadata block.
+///
+/// [`FirmwareImage::inject_metadata`]: struct.FirmwareImage.html#method.inject_metadata
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MetadataPlacement {
+    /// Directly after the image's last byte, rounded up to the next word boundary.
+    Append,
+
+    /// At the given offset from the image's first address.
+    Offset(u32),
+}
+
+/// Metadata block describing a firmware image, as added by [`FirmwareImage::inject_metadata`].
+///
+/// In flash, the block consists of the three fields as 32-bit little-endian words, in order.
+///
+/// [`FirmwareImage::inject_metadata`]: struct.FirmwareImage.html#method.inject_metadata
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    /// Number of bytes from the image's first address up to the block.
+    pub length: u32,
+
+    /// CRC32 of these bytes in the target's format.
+    pub crc: u32,
+
+    /// Version number supplied by the caller.
+    pub version: u32,
+}
+
+impl Metadata {
+    /// Returns the block as stored in flash.
+    pub fn to_bytes(&self) -> [u8; METADATA_SIZE] {
+        let mut bytes = [0; METADATA_SIZE];
+        bytes[0..4].copy_from_slice(&self.length.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.crc.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a block as stored in flash.
+    pub fn from_bytes(bytes: &[u8; METADATA_SIZE]) -> Self {
+        let word = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        Self {
+            length: word(0),
+            crc: word(4),
+            version: word(8),
+        }
+    }
+}
+
+/// Summary of a completed flashing process.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlashSummary {
+    /// Number of flash pages erased.
+    pub pages_erased: usize,
+
+    /// Number of bytes programmed.
+    pub bytes_programmed: usize,
+
+    /// Time it took to erase, program and verify.
+    pub duration: Duration,
+}
+
+/// Result of checking a firmware image against a target before flashing it, as returned by
+/// [`TargetHandle::preflight`].
+///
+/// [`TargetHandle::preflight`]: ../struct.TargetHandle.html#method.preflight
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Preflight {
+    /// Whether all segments lie within the application flash.
+    pub fits: bool,
+
+    /// Whether all segments start at halfword-aligned addresses.
+    pub aligned: bool,
+
+    /// Whether the segment at the start of the application flash looks like the text of an Intel
+    /// HEX or S-record file rather than binary data.
+    pub textual: bool,
+
+    /// Pages containing data of the image. Only pages within the flash are listed.
+    pub pages: Vec<Page>,
+
+    /// Pages containing data of the image which are not blank and thus have to be erased. Only
+    /// pages within the application flash are checked.
+    pub pages_to_erase: Vec<Page>,
+
+    /// Number of bytes to program.
+    pub bytes: usize,
+
+    /// Rough estimate of the time erasing, programming and verifying the image takes.
+    pub estimated_duration: Duration,
+}
+
+impl Preflight {
+    /// Returns whether the image can be flashed, i.e. it fits, is aligned and is binary data.
+    pub fn is_ok(&self) -> bool {
+        self.fits && self.aligned && !self.textual
+    }
+}
+
+/// Error while parsing a firmware file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    /// Line number (starting at 1) the error occurred in.
+    pub line: usize,
+
+    /// The kind of problem encountered.
+    pub kind: ParseErrorKind,
+}
+
+/// Kinds of problems encountered while parsing a firmware file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// A record is not formatted correctly.
+    InvalidRecord,
+
+    /// The checksum of a record does not match its contents.
+    ChecksumMismatch,
+
+    /// A record has a type which is not supported.
+    UnsupportedRecordType,
+
+    /// The file ended without an end-of-file record, so it may be truncated.
+    MissingEndOfFile,
+
+    /// A record contains data for addresses already covered by another record.
+    Overlap,
+}
+
+impl StdError for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::InvalidRecord => "Invalid record",
+            ParseErrorKind::ChecksumMismatch => "Checksum mismatch",
+            ParseErrorKind::UnsupportedRecordType => "Unsupported record type",
+            ParseErrorKind::MissingEndOfFile => "Missing end-of-file record",
+            ParseErrorKind::Overlap => "Overlapping data",
+        };
+        write!(fmt, "{} in line {}", reason, self.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_headers_are_truncated_to_one_record() {
+        let mut image = FirmwareImage::from_binary(0x0800_0000, vec![0x55; 4]);
+        image.set_header(Some("x".repeat(300)));
+
+        let srec = image.to_srec();
+        let header = srec.lines().next().unwrap();
+        assert_eq!(&header[..4], "S0FF");
+        assert_eq!(header.len(), 4 + 2 * 0xff);
+
+        let parsed = FirmwareImage::from_srec(&srec).unwrap();
+        assert_eq!(parsed.header(), Some(&*"x".repeat(MAX_SREC_DATA_LENGTH)));
+        assert_eq!(parsed.segments(), image.segments());
+    }
+}
@@ -1,11 +1,46 @@
+use std::ops::RangeInclusive;
+
 /// Address of the first byte in the target microcontroller's flash.
 pub const FLASH_BASE: u32 = 0x0800_0000;
 
 /// Flash page size of the target microcontroller.
 pub const PAGE_SIZE: u32 = 1024;
 
+/// Returns whether the `length` bytes starting at `address` lie within the flash addresses which
+/// page indices can refer to, i.e. the 256 pages starting at [`FLASH_BASE`].
+///
+/// [`FLASH_BASE`]: constant.FLASH_BASE.html
+pub(crate) fn is_page_addressable(address: u32, length: usize) -> bool {
+    let end = u64::from(address) + length as u64;
+    address >= FLASH_BASE && end <= u64::from(FLASH_BASE) + 256 * u64::from(PAGE_SIZE)
+}
+
+/// Alignment program requests are padded to. See [`TargetHandle::set_padding`].
+///
+/// [`TargetHandle::set_padding`]: struct.TargetHandle.html#method.set_padding
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Padding {
+    /// Pad to two bytes, the unit the flash is programmed in.
+    Halfword,
+
+    /// Pad to four bytes, e.g. for firmware expecting whole words.
+    Word,
+}
+
+impl Padding {
+    /// Returns the alignment in bytes.
+    pub fn bytes(self) -> u32 {
+        match self {
+            Padding::Halfword => 2,
+            Padding::Word => 4,
+        }
+    }
+}
+
 /// A page in the punt microcontroller's flash memory.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Page(u8);
 
 impl Page {
@@ -68,3 +103,175 @@ impl From<&Page> for u8 {
         val.0
     }
 }
+
+/// A page-aligned region of flash memory reserved for a specific purpose, e.g. a filesystem image.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Region {
+    start: u32,
+    length: usize,
+}
+
+impl Region {
+    /// Declares a region starting at `start` and spanning `length` bytes. Returns `None` if the
+    /// region is empty, does not start and end on page boundaries, or does not lie within the 256
+    /// pages starting at [`FLASH_BASE`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::{Region, PAGE_SIZE};
+    ///
+    /// let region = Region::new(0x0801_0000, 16 * PAGE_SIZE as usize).unwrap();
+    /// assert_eq!(region.end(), 0x0801_3fff);
+    /// assert!(Region::new(0x0801_0010, PAGE_SIZE as usize).is_none());
+    /// assert!(Region::new(0x0804_0000, PAGE_SIZE as usize).is_none());
+    /// ```
+    ///
+    /// [`FLASH_BASE`]: constant.FLASH_BASE.html
+    pub fn new(start: u32, length: usize) -> Option<Self> {
+        if !is_page_addressable(start, length)
+            || length == 0
+            || !start.is_multiple_of(PAGE_SIZE)
+            || !length.is_multiple_of(PAGE_SIZE as usize)
+        {
+            None
+        } else {
+            Some(Self { start, length })
+        }
+    }
+
+    /// Address of the first byte in the region.
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Size of the region in bytes.
+    pub fn size(&self) -> usize {
+        self.length
+    }
+
+    /// Address of the last byte in the region.
+    pub fn end(&self) -> u32 {
+        self.start + self.length as u32 - 1
+    }
+
+    /// Returns a range containing all pages within the region.
+    pub fn pages(&self) -> RangeInclusive<Page> {
+        Page::from_address(self.start)..=Page::from_address(self.end())
+    }
+}
+
+/// Layout of a target's flash memory, derived from the application area reported by its
+/// bootloader.
+///
+/// The flash starts at [`FLASH_BASE`] with the bootloader, followed by the application area,
+/// which extends to the end of the flash. All pages are [`PAGE_SIZE`] bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// let memory_map = target_handle.memory_map()?;
+///
+/// let application = memory_map.application();
+/// println!("{} pages, application at 0x{:08x}", memory_map.page_count(), application.start());
+/// assert!(memory_map.contains(application.start(), application.size()));
+/// assert!(!memory_map.contains(memory_map.flash_base(), 4));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FLASH_BASE`]: constant.FLASH_BASE.html
+/// [`PAGE_SIZE`]: constant.PAGE_SIZE.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryMap {
+    application: Region,
+}
+
+impl MemoryMap {
+    /// Describes the flash of a target whose application area starts at `application_base` and
+    /// spans `application_size` bytes.
+    pub fn new(application_base: u32, application_size: usize) -> Self {
+        Self {
+            application: Region {
+                start: application_base,
+                length: application_size,
+            },
+        }
+    }
+
+    /// Address of the first byte in the flash.
+    pub fn flash_base(&self) -> u32 {
+        FLASH_BASE
+    }
+
+    /// Size of a flash page in bytes.
+    pub fn page_size(&self) -> u32 {
+        PAGE_SIZE
+    }
+
+    /// Total number of pages in the flash, including those occupied by the bootloader.
+    pub fn page_count(&self) -> usize {
+        self.flash_size() / PAGE_SIZE as usize
+    }
+
+    /// Total size of the flash in bytes.
+    pub fn flash_size(&self) -> usize {
+        (self.application.start - FLASH_BASE) as usize + self.application.length
+    }
+
+    /// The flash area available for the application.
+    pub fn application(&self) -> &Region {
+        &self.application
+    }
+
+    /// The flash area occupied by the bootloader. Returns `None` if the application starts at
+    /// the beginning of the flash.
+    pub fn bootloader(&self) -> Option<Region> {
+        if self.application.start > FLASH_BASE {
+            Some(Region {
+                start: FLASH_BASE,
+                length: (self.application.start - FLASH_BASE) as usize,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a range containing all application pages.
+    pub fn application_pages(&self) -> RangeInclusive<Page> {
+        self.application.pages()
+    }
+
+    /// Returns whether the `length` bytes starting at `address` lie fully within the application
+    /// area.
+    pub fn contains(&self, address: u32, length: usize) -> bool {
+        address >= self.application.start
+            && address as usize + length
+                <= self.application.start as usize + self.application.length
+    }
+
+    /// Returns whether the `length` bytes starting at `address` lie fully within the flash,
+    /// including the bootloader area.
+    pub fn contains_flash(&self, address: u32, length: usize) -> bool {
+        address >= FLASH_BASE
+            && address as usize + length <= FLASH_BASE as usize + self.flash_size()
+    }
+
+    /// Returns whether a page belongs to the application area.
+    pub fn contains_page(&self, page: &Page) -> bool {
+        self.application_pages().contains(page)
+    }
+
+    /// Returns whether `length` bytes starting at `address` can be programmed. Besides lying
+    /// within the application area, the address has to be halfword-aligned, since the flash is
+    /// programmed halfword-wise.
+    pub fn can_program(&self, address: u32, length: usize) -> bool {
+        self.contains(address, length) && address.is_multiple_of(2)
+    }
+}
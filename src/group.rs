@@ -0,0 +1,163 @@
+use crate::context::UsbContext;
+use crate::error::Result;
+use crate::firmware::{FirmwareImage, FlashSummary};
+use crate::session::{EraseStrategy, FlashSession, Phase, Verify};
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use rusb::DeviceHandle;
+use std::iter::FromIterator;
+use std::thread;
+
+/// Several targets which are flashed with the same image at the same time.
+///
+/// Each target is flashed by a [`FlashSession`] in its own thread, configured identically for all
+/// targets. A failure on one target does not affect the others; the results are reported per
+/// target.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::{FirmwareImage, TargetGroup};
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut group: TargetGroup<MockTarget> = (0..4).map(|_| MockTarget::new().open()).collect();
+/// let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 2048]);
+///
+/// let results = group.retries(1).run(&image, |serial, phase, done, total| {
+///     println!("{}: {:?} {}/{}", serial, phase, done, total)
+/// });
+/// for (serial, result) in results {
+///     println!("{}: {} bytes", serial, result?.bytes_programmed);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FlashSession`]: struct.FlashSession.html
+pub struct TargetGroup<T: Transport> {
+    handles: Vec<TargetHandle<T>>,
+    erase: EraseStrategy,
+    verify: Verify,
+    retries: u32,
+}
+
+impl<T: UsbContext> TargetGroup<DeviceHandle<T>> {
+    /// Opens all connected targets in bootloader mode. Returns [`Error::IoError`] on USB errors
+    /// during device enumeration or while opening a target.
+    ///
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    pub fn open_all(context: &T) -> Result<Self> {
+        context
+            .find_targets()?
+            .iter()
+            .map(|target| target.open())
+            .collect()
+    }
+}
+
+impl<T: Transport> TargetGroup<T> {
+    /// Creates a group of the given targets. Like for a [`FlashSession`], only the pages needed for
+    /// the image are erased, the result is verified via CRC32 and failures are not retried by
+    /// default.
+    ///
+    /// [`FlashSession`]: struct.FlashSession.html
+    pub fn new(handles: Vec<TargetHandle<T>>) -> Self {
+        Self {
+            handles,
+            erase: EraseStrategy::MinimalPages,
+            verify: Verify::Crc,
+            retries: 0,
+        }
+    }
+
+    /// Adds a target to the group.
+    pub fn push(&mut self, handle: TargetHandle<T>) {
+        self.handles.push(handle);
+    }
+
+    /// Sets which pages are erased before programming.
+    pub fn erase(&mut self, strategy: EraseStrategy) -> &mut Self {
+        self.erase = strategy;
+        self
+    }
+
+    /// Sets how the flash contents are checked after programming.
+    pub fn verify(&mut self, verify: Verify) -> &mut Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Sets how many times the whole process is repeated for a target after a failure.
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Returns the handles of all targets in the group.
+    pub fn handles(&mut self) -> &mut [TargetHandle<T>] {
+        &mut self.handles
+    }
+
+    /// Returns the number of targets in the group.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if the group contains no targets.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Flashes the image to all targets concurrently and waits for all of them to finish.
+    ///
+    /// The callback receives the serial number of the target the progress belongs to, followed by
+    /// the same arguments as [`FlashSession::on_progress`]. It is called from the targets' threads.
+    /// Returns the serial number and result of every target in the order the targets were added.
+    ///
+    /// [`FlashSession::on_progress`]: struct.FlashSession.html#method.on_progress
+    pub fn run(
+        &mut self,
+        image: &FirmwareImage,
+        progress: impl Fn(&str, Phase, usize, usize) + Sync,
+    ) -> Vec<(String, Result<FlashSummary>)>
+    where
+        T: Send,
+    {
+        let (erase, verify, retries) = (self.erase, self.verify, self.retries);
+        let progress = &progress;
+        thread::scope(|scope| {
+            let threads: Vec<_> = self
+                .handles
+                .iter_mut()
+                .map(|handle| {
+                    scope.spawn(move || {
+                        let serial = handle.serial().to_string();
+                        let result = FlashSession::new(handle)
+                            .erase(erase)
+                            .verify(verify)
+                            .retries(retries)
+                            .on_progress(|phase, done, total| progress(&serial, phase, done, total))
+                            .run(image);
+                        (serial, result)
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                // Flashing itself does not panic, so a panic can only originate in the callback
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+}
+
+impl<T: Transport> FromIterator<TargetHandle<T>> for TargetGroup<T> {
+    fn from_iter<I: IntoIterator<Item = TargetHandle<T>>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
@@ -0,0 +1,167 @@
+use crate::context::UsbContext;
+use crate::error::Result;
+use crate::target::Target;
+use rusb::{Device, Hotplug, HotplugBuilder, Registration};
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval in which the connected devices are enumerated if libusb does not support hotplug
+/// notifications on the platform.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Change in the set of connected targets, as reported by a [`TargetWatcher`].
+///
+/// [`TargetWatcher`]: struct.TargetWatcher.html
+pub enum TargetEvent<T: UsbContext> {
+    /// A target in bootloader mode has been connected.
+    Arrived(Target<T>),
+
+    /// The target with the given serial number has been disconnected, e.g. because it left the
+    /// bootloader.
+    Left(String),
+}
+
+/// Reports targets being connected and disconnected. Created by [`UsbContext::watch_targets`].
+///
+/// libusb's hotplug notifications are used where available. On other platforms, the connected
+/// devices are enumerated periodically instead.
+///
+/// [`UsbContext::watch_targets`]: trait.UsbContext.html#method.watch_targets
+pub struct TargetWatcher<T: UsbContext> {
+    context: T,
+    _registration: Option<Registration<T>>,
+    devices: Receiver<DeviceEvent<T>>,
+
+    /// Serial numbers of the known targets by bus number and address.
+    known: BTreeMap<(u8, u8), String>,
+
+    pending: VecDeque<TargetEvent<T>>,
+}
+
+/// Raw notification from the hotplug callback.
+enum DeviceEvent<T: UsbContext> {
+    Arrived(Device<T>),
+    Left(Device<T>),
+}
+
+/// Hotplug callback forwarding notifications to the watcher. libusb does not allow opening devices
+/// from within the callback, so they are examined later by the watcher.
+struct Forwarder<T: UsbContext>(Sender<DeviceEvent<T>>);
+
+impl<T: UsbContext> Hotplug<T> for Forwarder<T> {
+    fn device_arrived(&mut self, device: Device<T>) {
+        let _ = self.0.send(DeviceEvent::Arrived(device));
+    }
+
+    fn device_left(&mut self, device: Device<T>) {
+        let _ = self.0.send(DeviceEvent::Left(device));
+    }
+}
+
+impl<T: UsbContext + 'static> TargetWatcher<T> {
+    pub(crate) fn new(context: T) -> Result<Self> {
+        let (sender, devices) = mpsc::channel();
+        let registration = if rusb::has_hotplug() {
+            let identity = context.device_identity();
+            Some(
+                HotplugBuilder::new()
+                    .vendor_id(identity.vendor_id)
+                    .product_id(identity.product_id)
+                    .enumerate(true)
+                    .register(context.clone(), Box::new(Forwarder(sender)))?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            context,
+            _registration: registration,
+            devices,
+            known: BTreeMap::new(),
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl<T: UsbContext> TargetWatcher<T> {
+    /// Waits for the next change for at most `timeout`. Targets which are connected already when
+    /// the watcher is created are reported as arrivals first. Returns `None` if nothing changed in
+    /// time.
+    pub fn next_event(&mut self, timeout: Duration) -> Result<Option<TargetEvent<T>>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            if self._registration.is_some() {
+                self.context.handle_events(Some(deadline - now))?;
+                while let Ok(event) = self.devices.try_recv() {
+                    match event {
+                        DeviceEvent::Arrived(device) => self.arrived(device),
+                        DeviceEvent::Left(device) => self.left(&device),
+                    }
+                }
+            } else {
+                self.poll()?;
+                if self.pending.is_empty() {
+                    thread::sleep(POLL_INTERVAL.min(deadline - now));
+                }
+            }
+        }
+    }
+
+    /// Compares the connected devices with the known targets.
+    fn poll(&mut self) -> Result<()> {
+        let devices: Vec<Device<T>> = self.context.devices()?.iter().collect();
+        let connected: Vec<(u8, u8)> = devices.iter().map(location).collect();
+
+        let gone: Vec<(u8, u8)> = self
+            .known
+            .keys()
+            .filter(|key| !connected.contains(key))
+            .cloned()
+            .collect();
+        for key in gone {
+            if let Some(serial) = self.known.remove(&key) {
+                self.pending.push_back(TargetEvent::Left(serial));
+            }
+        }
+
+        for device in devices {
+            if !self.known.contains_key(&location(&device)) {
+                self.arrived(device);
+            }
+        }
+        Ok(())
+    }
+
+    fn arrived(&mut self, device: Device<T>) {
+        let key = location(&device);
+        // Devices which are no punt targets or cannot be opened are ignored
+        if let Ok(target) = Target::try_from(device) {
+            self.known.insert(key, target.serial().unwrap_or_default());
+            self.pending.push_back(TargetEvent::Arrived(target));
+        }
+    }
+
+    fn left(&mut self, device: &Device<T>) {
+        if let Some(serial) = self.known.remove(&location(device)) {
+            self.pending.push_back(TargetEvent::Left(serial));
+        }
+    }
+}
+
+/// Returns the bus number and address identifying a connected device.
+fn location<T: UsbContext>(device: &Device<T>) -> (u8, u8) {
+    (device.bus_number(), device.address())
+}
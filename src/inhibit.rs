@@ -0,0 +1,25 @@
+//! Prevents the host from going to sleep while operations are in progress.
+
+/// Keeps the host from idle-sleeping for as long as it exists. Without the `inhibit-sleep`
+/// feature, this does nothing.
+pub(crate) struct SleepInhibitor {
+    #[cfg(feature = "inhibit-sleep")]
+    _guard: Option<keepawake::KeepAwake>,
+}
+
+impl SleepInhibitor {
+    /// Starts inhibiting sleep. This is best-effort: if the operating system refuses, the host may
+    /// still go to sleep, but no error is reported.
+    pub(crate) fn new() -> Self {
+        Self {
+            #[cfg(feature = "inhibit-sleep")]
+            _guard: keepawake::Builder::default()
+                .idle(true)
+                .reason("Programming a microcontroller")
+                .app_name("punt")
+                .app_reverse_domain("io.github.fruchti.punt")
+                .create()
+                .ok(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::error::{Error, Result};
+use crate::target_handle::{Command, TargetHandle};
+use crate::transport::Transport;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Guard keeping the bootloader busy in the background. Created by [`TargetHandle::keep_alive`].
+///
+/// Dropping the guard stops the background thread and makes the handle usable again.
+///
+/// [`TargetHandle::keep_alive`]: struct.TargetHandle.html#method.keep_alive
+pub struct KeepAlive<'a, T: Transport> {
+    _handle: &'a mut TargetHandle<T>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl<'a, T: Transport + Send + 'static> KeepAlive<'a, T> {
+    /// Starts the background thread, which sends a command via the given transport every
+    /// `interval` until stopped.
+    pub(crate) fn start(
+        handle: &'a mut TargetHandle<T>,
+        mut transport: T,
+        interval: Duration,
+    ) -> Self {
+        let timeouts = handle.timeouts;
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut info_packet = [0u8; 64];
+                    transport.send_command(
+                        Command::BootloaderInfo as u8,
+                        &[0; 0],
+                        &mut info_packet,
+                        &timeouts,
+                    )?;
+                }
+                _ => return Ok(()),
+            }
+        });
+
+        Self {
+            _handle: handle,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl<T: Transport> KeepAlive<'_, T> {
+    /// Stops the background thread. Returns the error which made the thread stop early, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        // Dropping the sender wakes up the thread
+        self.stop.take();
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(Error::IoError(rusb::Error::Other)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Transport> Drop for KeepAlive<'_, T> {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
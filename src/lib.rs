@@ -15,7 +15,7 @@
 //!
 //! // Find a bootloader target
 //! let mut context = Context::new()?;
-//! let mut target_handle = context.pick_target(None)?.open()?;
+//! let mut target_handle = context.open_target(None)?;
 //!
 //! // Fetch information about the target's bootloader
 //! let start_address = target_handle.bootloader_info()?.application_base;
@@ -34,31 +34,133 @@
 //! # }
 //! ```
 //!
+//! The erase, program and verify steps can also be carried out in one go:
+//!
+//! ```rust, no_run
+//! use punt::{Context, UsbContext};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let buff = std::fs::read("test.bin")?;
+//!
+//! let mut context = Context::new()?;
+//! let mut target_handle = context.open_target(None)?;
+//! let start_address = target_handle.bootloader_info()?.application_base;
+//!
+//! let summary = target_handle.flash_bytes(&buff, start_address)?;
+//! println!("Programmed {} bytes.", summary.bytes_programmed);
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! In addition to this very basic API, it also provides functionality for progress feedback during
 //! operations like reading, erasing and flashing. See the [`Operation`] trait for details.
 //!
 //! [`Operation`]: trait.Operation.html
+//!
+//! # Features
+//!
+//! * `async`: Adds [`AsyncTargetHandle`], an asynchronous interface for use from async code.
+//! * `cli`: Builds the `punt` command line tool with the subcommands `list`, `info`, `flash`,
+//!   `read`, `erase`, `verify` and `exit`, and `punt-run`, a Cargo runner flashing and starting the
+//!   ELF file built by `cargo run`. Run either with `--help` for details.
+//! * `ffi`: Adds the [`ffi`] module, a flat C API with the header `include/punt.h`, exported by
+//!   the shared and static libraries built from the crate.
+//! * `inhibit-sleep`: Keeps the host from going to sleep while an erase, program, read or verify
+//!   operation is in progress.
+//! * `log` (enabled by default): Emits records via the `log` crate for every command sent to the
+//!   target, including the transfer sizes, the time taken and retries. Individual bulk transfers
+//!   are logged at trace level.
+//! * `nusb`: Adds [`NusbTransport`], which talks to targets via the pure-Rust `nusb` library
+//!   instead of libusb.
+//! * `plan`: Adds [`Plan`], which flashes several images to several targets as described in a
+//!   JSON or TOML manifest. Implies `serde`.
+//! * `serde`: Implements `Serialize` and `Deserialize` for [`Error`], [`BootloaderInfo`], [`Page`],
+//!   [`FlashSummary`] and the other data types describing targets, so they can be written to
+//!   machine-readable reports.
+//!
+//! [`AsyncTargetHandle`]: struct.AsyncTargetHandle.html
+//! [`BootloaderInfo`]: struct.BootloaderInfo.html
+//! [`Error`]: enum.Error.html
+//! [`ffi`]: ffi/index.html
+//! [`FlashSummary`]: struct.FlashSummary.html
+//! [`NusbTransport`]: nusb_transport/struct.NusbTransport.html
+//! [`Page`]: struct.Page.html
+//! [`Plan`]: plan/struct.Plan.html
 
 #![warn(missing_docs)]
 
-extern crate crc_any;
 extern crate rusb;
 
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "async")]
+pub mod async_handle;
 pub mod bootloader_info;
+mod cache;
+mod chunk_size;
 mod context;
+mod crc;
+mod discovery;
 mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod firmware;
 mod flash;
+mod group;
+mod hotplug;
+mod inhibit;
+mod keep_alive;
+mod manifest;
+mod memory;
+pub mod mock;
+#[cfg(feature = "nusb")]
+pub mod nusb_transport;
 pub mod operation;
+#[cfg(feature = "plan")]
+pub mod plan;
+mod pool;
+pub mod provision;
+mod reboot;
+pub mod remote;
+pub mod report;
+mod retry;
+mod session;
+pub mod slots;
+mod stats;
 mod target;
 mod target_handle;
+pub mod trace;
+mod transport;
+mod write_queue;
 
-pub use bootloader_info::BootloaderInfo;
-pub use context::{Context, UsbContext};
-pub use error::{Error, Result};
-pub use flash::{Page, FLASH_BASE, PAGE_SIZE};
+#[cfg(feature = "async")]
+pub use async_handle::AsyncTargetHandle;
+pub use bootloader_info::{BootloaderInfo, OptionBytes, ReadProtection, UniqueId};
+pub use context::{Context, CustomContext, UsbContext};
+pub use crc::{crc32, Checksum, Crc32};
+pub use discovery::Discovery;
+pub use error::{CrcMismatch, Error, ErrorContext, RecoveryHint, Result, Severity};
+pub use firmware::{FirmwareImage, FlashSummary, Preflight};
+pub use flash::{MemoryMap, Padding, Page, Region, FLASH_BASE, PAGE_SIZE};
+pub use group::TargetGroup;
+pub use hotplug::{TargetEvent, TargetWatcher};
+pub use keep_alive::KeepAlive;
+pub use manifest::CrcManifest;
+pub use memory::TargetMemory;
 pub use operation::Operation;
-pub use target::Target;
-pub use target_handle::TargetHandle;
+pub use pool::TargetPool;
+pub use provision::ProvisioningLayout;
+pub use reboot::BootloaderTrigger;
+pub use remote::TcpTransport;
+pub use report::Report;
+pub use retry::RetryPolicy;
+pub use session::{EraseStrategy, FlashSession, Hooks, Phase, PuntSession, Verify};
+pub use stats::TransferStats;
+pub use target::{DeviceIdentity, Target, TargetRecord};
+pub use target_handle::{Command, TargetHandle, MAX_CRC_LENGTH};
+pub use transport::{Timeouts, Transport};
+pub use write_queue::WriteQueue;
 
 /// Timeout for all usb transactions.
 const TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
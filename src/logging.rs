@@ -0,0 +1,21 @@
+//! Forwards log records to the `log` crate if the `log` feature is enabled.
+
+/// Emits a debug-level record. Without the `log` feature, the arguments are only type-checked.
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    };
+}
+
+/// Emits a trace-level record. Without the `log` feature, the arguments are only type-checked.
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::trace!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    };
+}
@@ -0,0 +1,112 @@
+use crate::flash::{Page, FLASH_BASE};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+/// CRC32 checksums of individual flash pages, taken at one point in time.
+///
+/// A manifest can be stored in a simple text format with one line per page, containing the
+/// page's start address and its CRC32 as hexadecimal numbers. Comparing a stored manifest with a
+/// freshly read one reveals which pages have changed in between.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::{CrcManifest, Page};
+///
+/// let mut before = CrcManifest::new();
+/// before.insert(Page::from_index(4), 0x1234_5678);
+/// before.insert(Page::from_index(5), 0xffff_ffff);
+///
+/// let mut file = Vec::new();
+/// before.write_to(&mut file)?;
+/// assert_eq!(file, b"0x08001000 0x12345678\n0x08001400 0xffffffff\n");
+///
+/// let mut after = CrcManifest::read_from(file.as_slice())?;
+/// after.insert(Page::from_index(5), 0xdead_beef);
+/// assert_eq!(before.changed_pages(&after), vec![Page::from_index(5)]);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct CrcManifest {
+    crcs: BTreeMap<Page, u32>,
+}
+
+impl CrcManifest {
+    /// Creates an empty manifest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the CRC32 of a page.
+    pub fn insert(&mut self, page: Page, crc: u32) {
+        self.crcs.insert(page, crc);
+    }
+
+    /// Returns the CRC32 of a page, if the manifest contains it.
+    pub fn get(&self, page: &Page) -> Option<u32> {
+        self.crcs.get(page).copied()
+    }
+
+    /// Iterates over all pages and their CRC32s in ascending page order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Page, u32)> {
+        self.crcs.iter().map(|(page, crc)| (page, *crc))
+    }
+
+    /// Returns all pages whose CRC32 differs between the two manifests, including pages only
+    /// contained in one of them.
+    pub fn changed_pages(&self, other: &CrcManifest) -> Vec<Page> {
+        let mut pages: Vec<Page> = self
+            .crcs
+            .keys()
+            .chain(other.crcs.keys())
+            .filter(|page| self.get(page) != other.get(page))
+            .cloned()
+            .collect();
+        pages.sort();
+        pages.dedup();
+        pages
+    }
+
+    /// Writes the manifest in its text format.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        for (page, crc) in self.iter() {
+            writeln!(writer, "0x{:08x} 0x{:08x}", page.begin(), crc)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a manifest in its text format. Empty lines are ignored.
+    pub fn read_from(reader: impl io::Read) -> io::Result<Self> {
+        let mut manifest = Self::new();
+        for line in io::BufReader::new(reader).lines() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let (address, crc) = match (fields.next(), fields.next(), fields.next()) {
+                (None, _, _) => continue,
+                (Some(address), Some(crc), None) => (parse_hex(address)?, parse_hex(crc)?),
+                _ => return Err(invalid_data(&line)),
+            };
+            // Only page start addresses are valid
+            if address < FLASH_BASE || Page::from_address(address).begin() != address {
+                return Err(invalid_data(&line));
+            }
+            manifest.insert(Page::from_address(address), crc);
+        }
+        Ok(manifest)
+    }
+}
+
+/// Parses a hexadecimal number with a `0x` prefix.
+fn parse_hex(field: &str) -> io::Result<u32> {
+    field
+        .strip_prefix("0x")
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| invalid_data(field))
+}
+
+fn invalid_data(text: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed CRC manifest entry: {}", text),
+    )
+}
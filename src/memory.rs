@@ -0,0 +1,162 @@
+//! Contains an adapter for accessing the application flash via the standard I/O traits.
+
+use crate::error::Error;
+use crate::flash::{Region, PAGE_SIZE};
+use crate::operation::Operation;
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// The application flash of a target as a file-like object implementing [`Read`], [`Write`] and
+/// [`Seek`]. Created by [`TargetHandle::application_memory`].
+///
+/// Positions are offsets from the start of the application flash. Reading past its end returns no
+/// data, as does writing, so `write_all` fails with `WriteZero`. Since this adapter never erases
+/// anything, writes only succeed on erased flash and fail with `InvalidInput` otherwise. Flash is
+/// programmed halfword-wise, so writes have to start at even offsets. A single trailing byte is
+/// padded with `0xff`.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use std::io::{Read, Seek, SeekFrom, Write};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut target_handle = MockTarget::new().open();
+/// let mut memory = target_handle.application_memory()?;
+///
+/// memory.write_all(b"punt")?;
+/// memory.seek(SeekFrom::Start(0))?;
+///
+/// let mut contents = Vec::new();
+/// memory.take(6).read_to_end(&mut contents)?;
+/// assert_eq!(contents, b"punt\xff\xff");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+/// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
+/// [`Seek`]: https://doc.rust-lang.org/std/io/trait.Seek.html
+/// [`TargetHandle::application_memory`]: struct.TargetHandle.html#method.application_memory
+pub struct TargetMemory<'a, T: Transport> {
+    handle: &'a mut TargetHandle<T>,
+    region: Region,
+    position: u64,
+}
+
+impl<'a, T: Transport> TargetMemory<'a, T> {
+    pub(crate) fn new(handle: &'a mut TargetHandle<T>, region: Region) -> Self {
+        Self {
+            handle,
+            region,
+            position: 0,
+        }
+    }
+
+    /// Returns the area of flash accessed.
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// Returns the target address at the current position.
+    pub fn address(&self) -> u32 {
+        self.region.start() + self.position as u32
+    }
+
+    /// Returns the number of bytes between the current position and the end of the region.
+    fn remaining(&self) -> usize {
+        (self.region.size() as u64).saturating_sub(self.position) as usize
+    }
+}
+
+impl<T: Transport> Read for TargetMemory<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = buf.len().min(self.remaining());
+        if length == 0 {
+            return Ok(0);
+        }
+        let address = self.address();
+        self.handle
+            .read_at(&mut buf[..length], address)?
+            .execute()?;
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl<T: Transport> Write for TargetMemory<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let length = buf.len().min(self.remaining()).min(PAGE_SIZE as usize);
+        if length == 0 {
+            return Ok(0);
+        }
+        let address = self.address();
+        if !address.is_multiple_of(2) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Flash can only be written at even offsets",
+            ));
+        }
+
+        let data = match length {
+            1 => vec![buf[0], 0xff],
+            _ => buf[..length & !1].to_vec(),
+        };
+        if !self.handle.crc_matches(address, &vec![0xff; data.len()])? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Flash has to be erased before writing",
+            ));
+        }
+        self.handle.program_at(&data, address)?.execute()?;
+
+        let written = length.min(data.len());
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Transport> Seek for TargetMemory<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (self.region.size() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position.and_then(|position| u32::try_from(position).ok()) {
+            Some(position) => {
+                self.position = position.into();
+                Ok(self.position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    /// Maps an error to the closest I/O error kind, keeping the error as the I/O error's source.
+    fn from(error: Error) -> Self {
+        let kind = match error.root() {
+            Error::InvalidRequest | Error::TextualImage => io::ErrorKind::InvalidInput,
+            Error::VerificationError(_) | Error::MalformedResponse => io::ErrorKind::InvalidData,
+            Error::TargetNotFound => io::ErrorKind::NotFound,
+            Error::IoError(rusb::Error::Timeout) => io::ErrorKind::TimedOut,
+            Error::IoError(rusb::Error::Access) => io::ErrorKind::PermissionDenied,
+            Error::IoError(rusb::Error::Interrupted) => io::ErrorKind::Interrupted,
+            Error::IoError(rusb::Error::NoDevice) => io::ErrorKind::NotConnected,
+            Error::Io(error) => error.kind(),
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, error)
+    }
+}
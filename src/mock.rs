@@ -0,0 +1,287 @@
+//! Contains a simulated target for testing code using this crate without hardware.
+
+use crate::bootloader_info::{OptionBytes, UniqueId};
+use crate::crc::crc32;
+use crate::error::{Error, Result};
+use crate::flash::{FLASH_BASE, PAGE_SIZE};
+use crate::target_handle::{Command, TargetHandle};
+use crate::transport::Transport;
+use std::convert::TryInto;
+use std::time::Duration;
+
+/// Endpoint buffer size of the simulated target, matching the real bootloader.
+const BUFFER_LENGTH: u16 = 64;
+
+/// In-memory model of a target running the punt bootloader.
+///
+/// The model implements the complete command set against a simulated flash memory: pages are
+/// erased to `0xff`, programming only succeeds on erased halfwords, pages outside the application
+/// area refuse to be erased and CRCs are calculated like the real bootloader does. Apart from
+/// flash, only the unique device identifier and the option bytes can be read. As a
+/// [`Transport`], it can be used in place of a real target in a [`TargetHandle`].
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// let base = target_handle.bootloader_info()?.application_base;
+///
+/// target_handle.flash_bytes(&[0x12, 0x34, 0x56, 0x78], base)?;
+/// assert_eq!(target_handle.transport().memory(base, 4), &[0x12, 0x34, 0x56, 0x78]);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Transport`]: ../trait.Transport.html
+/// [`TargetHandle`]: ../struct.TargetHandle.html
+pub struct MockTarget {
+    flash: Vec<u8>,
+    application_base: u32,
+    command: Option<u8>,
+    request: Vec<u8>,
+    unique_id: UniqueId,
+    option_bytes: OptionBytes,
+    failures: Vec<rusb::Error>,
+    exited: bool,
+}
+
+impl MockTarget {
+    /// Creates a target with 64 KiB of erased flash, of which the first 4 KiB are occupied by the
+    /// bootloader.
+    pub fn new() -> Self {
+        Self::with_layout(64 * 1024, 4 * 1024)
+    }
+
+    /// Creates a target with the given flash size, of which the first `bootloader_size` bytes are
+    /// occupied by the bootloader. Both sizes are rounded up to whole pages.
+    pub fn with_layout(flash_size: usize, bootloader_size: usize) -> Self {
+        let page_size = PAGE_SIZE as usize;
+        let bootloader_size = bootloader_size.div_ceil(page_size) * page_size;
+        let flash_size = flash_size.div_ceil(page_size) * page_size;
+        let mut flash = vec![0xff; flash_size];
+        flash[..bootloader_size].fill(0);
+        Self {
+            flash,
+            application_base: FLASH_BASE + bootloader_size as u32,
+            command: None,
+            request: Vec::new(),
+            unique_id: UniqueId([
+                0x4d, 0x4f, 0x43, 0x4b, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+            ]),
+            option_bytes: OptionBytes::default(),
+            failures: Vec::new(),
+            exited: false,
+        }
+    }
+
+    /// Creates a handle for the simulated target.
+    pub fn open(self) -> TargetHandle<Self> {
+        TargetHandle::new(self, BUFFER_LENGTH, BUFFER_LENGTH, "MOCK")
+    }
+
+    /// Returns the simulated memory contents. Panics if the area is not within flash.
+    pub fn memory(&self, address: u32, length: usize) -> &[u8] {
+        let offset = (address - FLASH_BASE) as usize;
+        &self.flash[offset..offset + length]
+    }
+
+    /// Returns whether all bytes in the given area are erased.
+    pub fn is_erased(&self, address: u32, length: usize) -> bool {
+        self.memory(address, length)
+            .iter()
+            .all(|&byte| byte == 0xff)
+    }
+
+    /// Sets the unique device identifier reported by the target.
+    pub fn set_unique_id(&mut self, unique_id: UniqueId) {
+        self.unique_id = unique_id;
+    }
+
+    /// Sets the option bytes reported by the target.
+    pub fn set_option_bytes(&mut self, option_bytes: OptionBytes) {
+        self.option_bytes = option_bytes;
+    }
+
+    /// Makes the next transfers fail with the given errors, one per transfer, e.g. to test error
+    /// handling and retries.
+    pub fn inject_failures(&mut self, errors: &[rusb::Error]) {
+        self.failures.extend(errors.iter().rev());
+    }
+
+    /// Returns whether the bootloader has been exited.
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Fails the current transfer if requested by the test or the bootloader has been exited.
+    fn check_transfer(&mut self) -> Result<()> {
+        if self.exited {
+            return Err(Error::IoError(rusb::Error::NoDevice));
+        }
+        match self.failures.pop() {
+            Some(error) => Err(Error::IoError(error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the flash offsets for an area, if completely within flash.
+    fn area(&self, address: u32, length: usize) -> Option<std::ops::Range<usize>> {
+        let offset = address.checked_sub(FLASH_BASE)? as usize;
+        let end = offset.checked_add(length)?;
+        if end <= self.flash.len() {
+            Some(offset..end)
+        } else {
+            None
+        }
+    }
+
+    /// Splits an address and a length off the request.
+    fn address_and_length(&self) -> Result<(u32, usize)> {
+        if self.request.len() < 8 {
+            return Err(Error::IoError(rusb::Error::Pipe));
+        }
+        let address = u32::from_le_bytes(self.request[0..4].try_into().unwrap());
+        let length = u32::from_le_bytes(self.request[4..8].try_into().unwrap());
+        Ok((address, length as usize))
+    }
+
+    /// Programs the flash halfword-wise. Halfwords which are not erased are left unchanged, which
+    /// is what the hardware does on a programming error.
+    fn program(&mut self) {
+        if self.request.len() < 4 {
+            return;
+        }
+        let address = u32::from_le_bytes(self.request[0..4].try_into().unwrap());
+        let data = self.request[4..].to_vec();
+        let application_area = self.area(self.application_base, self.application_size());
+        let area = match (self.area(address, data.len()), application_area) {
+            (Some(area), Some(application_area))
+                if area.start >= application_area.start && area.end <= application_area.end =>
+            {
+                area
+            }
+            _ => return,
+        };
+        for (target, source) in self.flash[area].chunks_mut(2).zip(data.chunks(2)) {
+            if target.iter().all(|&byte| byte == 0xff) {
+                target.copy_from_slice(source);
+            }
+        }
+    }
+
+    fn application_size(&self) -> usize {
+        self.flash.len() - (self.application_base - FLASH_BASE) as usize
+    }
+
+    /// Builds the response to the current command.
+    fn response(&mut self) -> Result<Vec<u8>> {
+        const BOOTLOADER_INFO: u8 = Command::BootloaderInfo as u8;
+        const READ_CRC: u8 = Command::ReadCrc as u8;
+        const READ_MEMORY: u8 = Command::ReadMemory as u8;
+        const ERASE_PAGE: u8 = Command::ErasePage as u8;
+
+        match self.command {
+            Some(BOOTLOADER_INFO) => {
+                let mut packet = Vec::new();
+                packet.extend_from_slice(&20200101u32.to_ne_bytes());
+                packet.extend_from_slice(&1u32.to_ne_bytes());
+                packet.extend_from_slice(&self.application_base.to_ne_bytes());
+                packet.extend_from_slice(&(self.application_size() as u32).to_ne_bytes());
+                packet.extend_from_slice(&[1, 0, 0]);
+                packet.extend_from_slice(b"MOCK");
+                Ok(packet)
+            }
+            Some(READ_CRC) => {
+                let (address, length) = self.address_and_length()?;
+                let area = self
+                    .area(address, length)
+                    .ok_or(Error::IoError(rusb::Error::Pipe))?;
+                Ok(crc32(&self.flash[area]).to_le_bytes().to_vec())
+            }
+            Some(READ_MEMORY) => {
+                let (address, length) = self.address_and_length()?;
+                if address == UniqueId::ADDRESS && length <= 12 {
+                    return Ok(self.unique_id.0[..length].to_vec());
+                }
+                if address == OptionBytes::ADDRESS && length <= OptionBytes::SIZE {
+                    return Ok(self.option_bytes.to_bytes()[..length].to_vec());
+                }
+                let area = self
+                    .area(address, length)
+                    .ok_or(Error::IoError(rusb::Error::Pipe))?;
+                Ok(self.flash[area].to_vec())
+            }
+            Some(ERASE_PAGE) => {
+                let page = *self
+                    .request
+                    .first()
+                    .ok_or(Error::IoError(rusb::Error::Pipe))?;
+                let begin = FLASH_BASE + u32::from(page) * PAGE_SIZE;
+                match self.area(begin, PAGE_SIZE as usize) {
+                    Some(area) if begin >= self.application_base => {
+                        self.flash[area].fill(0xff);
+                        Ok(vec![0])
+                    }
+                    // Prohibited
+                    _ => Ok(vec![1]),
+                }
+            }
+            _ => Err(Error::IoError(rusb::Error::Pipe)),
+        }
+    }
+}
+
+impl Default for MockTarget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for MockTarget {
+    fn command(&mut self, command: u8, _timeout: Duration) -> Result<()> {
+        self.check_transfer()?;
+        self.command = Some(command);
+        self.request.clear();
+        if command == Command::Exit as u8 {
+            self.exited = true;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8], _timeout: Duration) -> Result<usize> {
+        self.check_transfer()?;
+        self.request = data.to_vec();
+        if self.command == Some(Command::Program as u8) {
+            self.program();
+        }
+        Ok(data.len())
+    }
+
+    fn read(&mut self, buffer: &mut [u8], _timeout: Duration) -> Result<usize> {
+        self.check_transfer()?;
+        let response = self.response()?;
+        let length = response.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&response[..length]);
+        Ok(length)
+    }
+
+    /// Discards a partially received command, like the bootloader does on a USB reset.
+    fn reset_device(&mut self) -> Result<()> {
+        self.command = None;
+        self.request.clear();
+        Ok(())
+    }
+
+    /// Reports whether the bootloader has been exited, without waiting. The application's vendor
+    /// and product ID are not checked.
+    fn wait_for_application(
+        &self,
+        _application: Option<(u16, u16)>,
+        _timeout: Duration,
+    ) -> Result<bool> {
+        Ok(self.exited)
+    }
+}
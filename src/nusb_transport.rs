@@ -0,0 +1,206 @@
+//! Contains a transport based on the pure-Rust [`nusb`] library, available with the `nusb` feature.
+//!
+//! Unlike the default transport, this one does not need libusb to be installed on the host.
+//!
+//! [`nusb`]: https://docs.rs/nusb
+
+use crate::error::{Error, Result};
+use crate::target::DeviceIdentity;
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use crate::TIMEOUT;
+use nusb::transfer::{
+    Completion, Control, ControlType, Direction, Queue, Recipient, RequestBuffer, TransferError,
+};
+use nusb::{DeviceInfo, Interface};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// Transport communicating with a target via [`nusb`].
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::nusb_transport::NusbTransport;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut target_handle = NusbTransport::open_target(None)?;
+/// println!("{}", target_handle.bootloader_info()?.identifier);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`nusb`]: https://docs.rs/nusb
+pub struct NusbTransport {
+    interface: Interface,
+    in_queue: Queue<RequestBuffer>,
+    out_queue: Queue<Vec<u8>>,
+}
+
+impl NusbTransport {
+    /// Returns the serial numbers of all connected targets in bootloader mode.
+    pub fn serials() -> Result<Vec<String>> {
+        Ok(find_targets()?
+            .iter()
+            .filter_map(|info| info.serial_number().map(String::from))
+            .collect())
+    }
+
+    /// Opens a target selected by serial number like [`UsbContext::pick_target`] does.
+    ///
+    /// [`UsbContext::pick_target`]: ../trait.UsbContext.html#method.pick_target
+    pub fn open_target(serial: Option<&str>) -> Result<TargetHandle<Self>> {
+        let targets = find_targets()?;
+        let info = if let Some(serial) = serial {
+            targets
+                .into_iter()
+                .find(|info| info.serial_number() == Some(serial))
+                .ok_or(Error::TargetNotFound)?
+        } else if targets.len() > 1 {
+            return Err(Error::TooManyMatches);
+        } else {
+            targets.into_iter().next().ok_or(Error::TargetNotFound)?
+        };
+
+        let device = info.open()?;
+        device.reset()?;
+        let interface = device.claim_interface(0)?;
+
+        // Fetch endpoint sizes
+        let (mut in_buffer_length, mut out_buffer_length) = (None, None);
+        if let Some(alt_setting) = interface.descriptors().next() {
+            for endpoint in alt_setting.endpoints() {
+                let size = Some(endpoint.max_packet_size() as u16);
+                match endpoint.direction() {
+                    Direction::In => in_buffer_length = in_buffer_length.or(size),
+                    Direction::Out => out_buffer_length = out_buffer_length.or(size),
+                }
+            }
+        }
+
+        let mut target_handle = TargetHandle::new(
+            Self::new(interface),
+            in_buffer_length.ok_or(Error::IoError(rusb::Error::Io))?,
+            out_buffer_length.ok_or(Error::IoError(rusb::Error::Io))?,
+            info.serial_number().unwrap_or_default(),
+        );
+        // Fail early for bootloaders speaking an incompatible protocol
+        target_handle.bootloader_info()?;
+        Ok(target_handle)
+    }
+
+    fn new(interface: Interface) -> Self {
+        Self {
+            in_queue: interface.bulk_in_queue(0x81),
+            out_queue: interface.bulk_out_queue(0x02),
+            interface,
+        }
+    }
+}
+
+impl Transport for NusbTransport {
+    fn command(&mut self, command: u8, timeout: Duration) -> Result<()> {
+        self.interface
+            .control_out_blocking(
+                Control {
+                    control_type: ControlType::Vendor,
+                    recipient: Recipient::Device,
+                    request: command,
+                    value: 0,
+                    index: 0,
+                },
+                &[0u8; 0],
+                timeout,
+            )
+            .map_err(transfer_error)?;
+        Ok(())
+    }
+
+    /// Sends data via bulk endpoint 2.
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize> {
+        self.out_queue.submit(data.to_vec());
+        let completion =
+            wait(&mut |cx| self.out_queue.poll_next(cx), timeout).ok_or_else(|| {
+                self.out_queue.cancel_all();
+                let _ = wait(&mut |cx| self.out_queue.poll_next(cx), TIMEOUT);
+                Error::IoError(rusb::Error::Timeout)
+            })?;
+        completion.status.map_err(transfer_error)?;
+        Ok(completion.data.actual_length())
+    }
+
+    /// Reads data from bulk endpoint 1.
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.in_queue.submit(RequestBuffer::new(buffer.len()));
+        let completion = wait(&mut |cx| self.in_queue.poll_next(cx), timeout).ok_or_else(|| {
+            self.in_queue.cancel_all();
+            let _ = wait(&mut |cx| self.in_queue.poll_next(cx), TIMEOUT);
+            Error::IoError(rusb::Error::Timeout)
+        })?;
+        let data = completion.into_result().map_err(transfer_error)?;
+        let length = data.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&data[..length]);
+        Ok(length)
+    }
+
+    fn try_clone(&self) -> Result<Self> {
+        // Interfaces are reference-counted, so the connection can simply be shared
+        Ok(Self::new(self.interface.clone()))
+    }
+}
+
+/// Returns all connected USB devices running a punt bootloader.
+fn find_targets() -> Result<Vec<DeviceInfo>> {
+    let identity = DeviceIdentity::default();
+    Ok(nusb::list_devices()?
+        .filter(|info| {
+            info.vendor_id() == identity.vendor_id
+                && info.product_id() == identity.product_id
+                && info.manufacturer_string() == Some(identity.manufacturer.as_str())
+                && info.product_string() == Some(identity.product.as_str())
+        })
+        .collect())
+}
+
+/// Wakes up a thread blocked in [`wait`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls a transfer queue on the current thread until a transfer completes. Returns `None` if
+/// that does not happen within `timeout`.
+fn wait<T>(
+    poll: &mut dyn FnMut(&mut Context) -> Poll<Completion<T>>,
+    timeout: Duration,
+) -> Option<Completion<T>> {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Poll::Ready(completion) = poll(&mut cx) {
+            return Some(completion);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        thread::park_timeout(deadline - now);
+    }
+}
+
+/// Maps nusb's transfer errors to the corresponding libusb errors.
+fn transfer_error(error: TransferError) -> Error {
+    Error::IoError(match error {
+        TransferError::Cancelled => rusb::Error::Interrupted,
+        TransferError::Stall => rusb::Error::Pipe,
+        TransferError::Disconnected => rusb::Error::NoDevice,
+        TransferError::Fault => rusb::Error::Io,
+        TransferError::Unknown => rusb::Error::Other,
+    })
+}
@@ -1,12 +1,23 @@
 //! Contains multi-step operations, i.e. erasing an area by erasing multiple pages subsequently abd
 //! reading/writing flash memory blockwise.
 
-use crate::context::UsbContext;
-use crate::error::Result;
-use crate::flash::Page;
-use crate::target_handle::TargetHandle;
-use std::iter::Enumerate;
-use std::slice::{Chunks, ChunksMut};
+use crate::error::{Error, Result};
+use crate::firmware::FirmwareImage;
+use crate::flash::{Page, PAGE_SIZE};
+use crate::inhibit::SleepInhibitor;
+use crate::session::Phase;
+use crate::target_handle::{Command, TargetHandle};
+use crate::transport::Transport;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Number of recent steps the rate of a [`Timed`] operation is averaged over.
+///
+/// [`Timed`]: struct.Timed.html
+const RATE_WINDOW: usize = 8;
 
 /// General-purpose trait for operations which take multiple command transmissions via USB, e.g.
 /// reading or writing a larger section of memory in smaller blocks.
@@ -22,7 +33,7 @@ use std::slice::{Chunks, ChunksMut};
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// // Find a bootloader target
 /// let mut context = Context::new()?;
-/// let mut target_handle = context.pick_target(None)?.open()?;
+/// let mut target_handle = context.open_target(None)?;
 ///
 /// // Create an erase Operation
 /// let mut erase = target_handle.erase_area(0x0800_0c00, 1024)?;
@@ -41,7 +52,7 @@ use std::slice::{Chunks, ChunksMut};
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// // Find a bootloader target
 /// let mut context = Context::new()?;
-/// let mut target_handle = context.pick_target(None)?.open()?;
+/// let mut target_handle = context.open_target(None)?;
 ///
 /// // Create an Erase Operation
 /// let mut erase = target_handle.erase_area(0x0800_0c00, 1024)?;
@@ -70,24 +81,239 @@ pub trait Operation: Iterator<Item = Result<usize>> {
             Ok(())
         }
     }
+
+    /// Consumes the iterator to execute the operation like [`execute`], but also reports how much
+    /// work was completed before a failure, e.g. to decide whether to resume, retry or give up on
+    /// a target.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::Operation;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// let base = target_handle.bootloader_info()?.application_base;
+    ///
+    /// let outcome = target_handle.erase_area(base, 4096)?.execute_report();
+    /// assert!(outcome.is_complete());
+    /// assert_eq!(outcome.completed, 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`execute`]: #method.execute
+    fn execute_report(&mut self) -> Outcome {
+        let total = self.total();
+        let mut completed = 0;
+        for step in &mut *self {
+            match step {
+                Ok(done) => completed = done,
+                Err(error) => {
+                    return Outcome {
+                        completed,
+                        total,
+                        error: Some(error),
+                    }
+                }
+            }
+        }
+        Outcome {
+            completed,
+            total,
+            error: None,
+        }
+    }
+
+    /// Wraps the operation so each step additionally reports the current rate and the estimated
+    /// time remaining.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext, Operation};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    /// let mut buffer = vec![0u8; 0x8000];
+    ///
+    /// for progress in target_handle.read_at(&mut buffer, 0x0800_0000)?.timed() {
+    ///     let progress = progress?;
+    ///     println!(
+    ///         "{}/{} bytes, {:.0} B/s, {:?} remaining",
+    ///         progress.done, progress.total, progress.rate, progress.remaining
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn timed(self) -> Timed<Self>
+    where
+        Self: Sized,
+    {
+        Timed {
+            total: self.total(),
+            operation: self,
+            start: Instant::now(),
+            samples: VecDeque::with_capacity(RATE_WINDOW + 1),
+        }
+    }
+}
+
+/// Result of an operation executed with [`Operation::execute_report`].
+///
+/// [`Operation::execute_report`]: trait.Operation.html#method.execute_report
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// Progress reached before the operation finished or failed, in the operation's units.
+    pub completed: usize,
+
+    /// Progress of the complete operation, as returned by [`Operation::total`].
+    ///
+    /// [`Operation::total`]: trait.Operation.html#tymethod.total
+    pub total: usize,
+
+    /// The error the operation failed with, if any.
+    pub error: Option<Error>,
+}
+
+impl Outcome {
+    /// Returns whether the operation completed without an error.
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Converts the outcome into a result, discarding the progress.
+    pub fn into_result(self) -> Result<()> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Progress of a [`Timed`] operation after a step.
+///
+/// [`Timed`]: struct.Timed.html
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimedProgress {
+    /// Progress so far, in the operation's unit.
+    pub done: usize,
+
+    /// The operation's total, as returned by [`Operation::total`].
+    ///
+    /// [`Operation::total`]: trait.Operation.html#tymethod.total
+    pub total: usize,
+
+    /// Recent rate in units per second, averaged over the last few steps.
+    pub rate: f64,
+
+    /// Time since the operation was started.
+    pub elapsed: Duration,
+
+    /// Estimated time until the operation completes at the current rate, if it can be estimated.
+    pub remaining: Option<Duration>,
+}
+
+/// An operation reporting its rate and estimated time remaining. Created by [`Operation::timed`].
+///
+/// [`Operation::timed`]: trait.Operation.html#method.timed
+pub struct Timed<O: Operation> {
+    operation: O,
+    total: usize,
+    start: Instant,
+
+    /// Progress and completion time of the most recent steps, starting with the oldest.
+    samples: VecDeque<(usize, Instant)>,
+}
+
+impl<O: Operation> Timed<O> {
+    /// Returns the total of the wrapped operation.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the wrapped operation.
+    pub fn into_inner(self) -> O {
+        self.operation
+    }
+}
+
+impl<O: Operation> Iterator for Timed<O> {
+    type Item = Result<TimedProgress>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let done = match self.operation.next()? {
+            Ok(done) => done,
+            Err(error) => return Some(Err(error)),
+        };
+        let now = Instant::now();
+
+        if self.samples.is_empty() {
+            self.samples.push_back((0, self.start));
+        }
+        self.samples.push_back((done, now));
+        if self.samples.len() > RATE_WINDOW + 1 {
+            self.samples.pop_front();
+        }
+
+        let (first_done, first_time) = self.samples[0];
+        let seconds = now.duration_since(first_time).as_secs_f64();
+        let rate = if seconds > 0.0 {
+            done.saturating_sub(first_done) as f64 / seconds
+        } else {
+            0.0
+        };
+        let remaining = if done >= self.total {
+            Some(Duration::from_secs(0))
+        } else if rate > 0.0 {
+            Some(Duration::from_secs_f64((self.total - done) as f64 / rate))
+        } else {
+            None
+        };
+
+        Some(Ok(TimedProgress {
+            done,
+            total: self.total,
+            rate,
+            elapsed: now.duration_since(self.start),
+            remaining,
+        }))
+    }
 }
 
 /// A page-wise flash erase operation.
-pub struct Erase<'a, T: UsbContext> {
+pub struct Erase<'a, T: Transport> {
     handle: &'a mut TargetHandle<T>,
     pages: Vec<Page>,
     count: usize,
     done: bool,
+
+    /// Whether pages are checked for being blank before erasing them.
+    skip_blank: bool,
+
+    skipped: usize,
+
+    /// Pages erased (or found blank) so far, in the order they were processed.
+    erased: Vec<Page>,
+
+    /// The page the operation failed on, together with the error.
+    failed: Option<(Page, Error)>,
+
+    _inhibitor: SleepInhibitor,
 }
 
-impl<T: UsbContext> Operation for Erase<'_, T> {
+impl<T: Transport> Operation for Erase<'_, T> {
     /// Returns the total number of pages.
     fn total(&self) -> usize {
         self.count
     }
 }
 
-impl<T: UsbContext> Iterator for Erase<'_, T> {
+impl<T: Transport> Iterator for Erase<'_, T> {
     type Item = Result<usize>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -101,18 +327,43 @@ impl<T: UsbContext> Iterator for Erase<'_, T> {
         if self.pages.is_empty() {
             self.done = true;
         }
-        Some(match self.handle.erase_page(page) {
-            Ok(()) => Ok(self.count - self.pages.len()),
+        let completed = self.count - self.pages.len() - 1;
+        let address = page.begin();
+        let result = if self.skip_blank {
+            match self
+                .handle
+                .crc_matches(address, &[0xff; PAGE_SIZE as usize])
+            {
+                Ok(true) => {
+                    self.skipped += 1;
+                    Ok(())
+                }
+                Ok(false) => self.handle.erase_page(page.clone()),
+                Err(error) => Err(error.in_step(Command::ReadCrc, address, completed, completed)),
+            }
+        } else {
+            self.handle.erase_page(page.clone())
+        };
+        Some(match result {
+            Ok(()) => {
+                self.erased.push(page);
+                Ok(self.count - self.pages.len())
+            }
             Err(error) => {
                 // Ensure that the iterator is fused after an error occurs
                 self.done = true;
+                let error = match error {
+                    Error::Context(..) => error,
+                    error => error.in_step(Command::ErasePage, address, completed, completed),
+                };
+                self.failed = Some((page, error.clone()));
                 Err(error)
             }
         })
     }
 }
 
-impl<'a, T: UsbContext> Erase<'a, T> {
+impl<'a, T: Transport> Erase<'a, T> {
     /// Erase a set of given pages (not necessarily a continuous range).
     pub(crate) fn pages(handle: &'a mut TargetHandle<T>, pages: &[Page]) -> Self {
         Self {
@@ -120,6 +371,11 @@ impl<'a, T: UsbContext> Erase<'a, T> {
             done: pages.is_empty(),
             pages: Vec::from(pages),
             count: pages.len(),
+            skip_blank: false,
+            skipped: 0,
+            erased: Vec::new(),
+            failed: None,
+            _inhibitor: SleepInhibitor::new(),
         }
     }
 
@@ -143,28 +399,127 @@ impl<'a, T: UsbContext> Erase<'a, T> {
             done: pages.is_empty(),
             count: pages.len(),
             pages,
+            skip_blank: false,
+            skipped: 0,
+            erased: Vec::new(),
+            failed: None,
+            _inhibitor: SleepInhibitor::new(),
         }
     }
+
+    /// Makes the operation check each page before erasing it and skip pages which are blank
+    /// already. The check is a single CRC32 request, which is considerably faster than erasing a
+    /// page and spares the flash an erase cycle. Skipped pages still count towards the progress.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::Operation;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// let mut erase = target_handle.erase_area(0x0800_1000, 4096)?.skip_blank_pages();
+    /// erase.execute()?;
+    /// assert_eq!(erase.skipped(), 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn skip_blank_pages(mut self) -> Self {
+        self.skip_blank = true;
+        self
+    }
+
+    /// Returns the number of pages skipped so far because they were blank already.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Returns which pages have been erased so far, which page the operation failed on and which
+    /// pages are still left to erase. Pages skipped because they were blank count as erased.
+    ///
+    /// # Examples
+    ///
+    /// Retrying only the pages which were not erased:
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::Operation;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// target_handle.set_retry_policy(punt::RetryPolicy::none());
+    /// target_handle.bootloader_info()?;
+    /// target_handle.transport_mut().inject_failures(&[rusb::Error::Busy]);
+    ///
+    /// let mut erase = target_handle.erase_area(0x0800_1000, 4096)?;
+    /// assert!(erase.execute_report().error.is_some());
+    /// let report = erase.report();
+    /// assert_eq!(report.remaining.len(), 4);
+    ///
+    /// target_handle.erase_pages(&report.remaining)?.execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn report(&self) -> EraseReport {
+        let mut remaining = self.pages.clone();
+        if let Some((page, _)) = &self.failed {
+            remaining.push(page.clone());
+        }
+        remaining.sort();
+        EraseReport {
+            erased: self.erased.clone(),
+            failed: self.failed.clone(),
+            remaining,
+        }
+    }
+}
+
+/// Per-page result of an [`Erase`] operation, as returned by [`Erase::report`].
+///
+/// [`Erase`]: struct.Erase.html
+/// [`Erase::report`]: struct.Erase.html#method.report
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EraseReport {
+    /// Pages erased or found blank, in the order they were processed.
+    pub erased: Vec<Page>,
+
+    /// The page the operation failed on and the error it failed with, if any.
+    pub failed: Option<(Page, Error)>,
+
+    /// Pages which still have to be erased, including the failed one, in ascending order.
+    pub remaining: Vec<Page>,
+}
+
+impl EraseReport {
+    /// Returns whether all pages have been erased.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_none() && self.remaining.is_empty()
+    }
 }
 
 /// A flash program operation.
-pub struct Program<'d, 'a, T: UsbContext> {
+pub struct Program<'d, 'a, T: Transport> {
     handle: &'a mut TargetHandle<T>,
-    address: u32,
-    chunks: Enumerate<Chunks<'d, u8>>,
-    length: usize,
-    chunk_size: usize,
+    parts: Vec<(u32, Cow<'d, [u8]>)>,
+    part: usize,
+    offset: usize,
+    written: usize,
+    chunks: usize,
+    total: usize,
     done: bool,
+    _inhibitor: SleepInhibitor,
 }
 
-impl<T: UsbContext> Operation for Program<'_, '_, T> {
+impl<T: Transport> Operation for Program<'_, '_, T> {
     /// Returns the total size in bytes.
     fn total(&self) -> usize {
-        self.length
+        self.total
     }
 }
 
-impl<T: UsbContext> Iterator for Program<'_, '_, T> {
+impl<T: Transport> Iterator for Program<'_, '_, T> {
     type Item = Result<usize>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -172,16 +527,32 @@ impl<T: UsbContext> Iterator for Program<'_, '_, T> {
             return None;
         }
 
-        if let Some((i, chunk)) = self.chunks.next() {
+        // Skip to the next part if the current one is complete
+        while self
+            .parts
+            .get(self.part)
+            .is_some_and(|(_, data)| self.offset == data.len())
+        {
+            self.part += 1;
+            self.offset = 0;
+        }
+
+        if let Some((address, data)) = self.parts.get(self.part) {
+            let address = address + self.offset as u32;
             Some(
                 match self
                     .handle
-                    .program_chunk(self.address + (i * self.chunk_size) as u32, chunk)
+                    .program_next_chunk(address, &data[self.offset..])
                 {
-                    Ok(()) => Ok(i * self.chunk_size + chunk.len()),
+                    Ok(length) => {
+                        self.offset += length;
+                        self.written += length;
+                        self.chunks += 1;
+                        Ok(self.written)
+                    }
                     Err(error) => {
                         self.done = true;
-                        Err(error)
+                        Err(error.in_step(Command::Program, address, self.chunks, self.written))
                     }
                 },
             )
@@ -192,40 +563,50 @@ impl<T: UsbContext> Iterator for Program<'_, '_, T> {
     }
 }
 
-impl<'d, 'a, T: UsbContext> Program<'d, 'a, T> {
+impl<'d, 'a, T: Transport> Program<'d, 'a, T> {
     /// Write to flash, starting at a given memory location. The memory has to be manually erased
     /// before starting a programming operation.
-    pub(crate) fn at(handle: &'a mut TargetHandle<T>, data: &'d [u8], address: u32) -> Self {
-        let chunk_size = handle.max_program_chunk_size();
+    pub(crate) fn at(handle: &'a mut TargetHandle<T>, data: Cow<'d, [u8]>, address: u32) -> Self {
+        Self::parts(handle, vec![(address, data)])
+    }
+
+    /// Write several blocks of data to flash, each starting at its own memory location. The memory
+    /// has to be manually erased before starting a programming operation.
+    pub(crate) fn parts(handle: &'a mut TargetHandle<T>, parts: Vec<(u32, Cow<'d, [u8]>)>) -> Self {
+        let total = parts.iter().map(|(_, data)| data.len()).sum();
         Self {
             handle,
-            address,
-            chunk_size,
-            chunks: data.chunks(chunk_size).enumerate(),
-            length: data.len(),
-            done: data.is_empty(),
+            parts,
+            part: 0,
+            offset: 0,
+            written: 0,
+            chunks: 0,
+            total,
+            done: total == 0,
+            _inhibitor: SleepInhibitor::new(),
         }
     }
 }
 
 /// Memory read operation.
-pub struct Read<'d, 'a, T: UsbContext> {
+pub struct Read<'d, 'a, T: Transport> {
     handle: &'a mut TargetHandle<T>,
     address: u32,
-    chunks: Enumerate<ChunksMut<'d, u8>>,
-    length: usize,
-    chunk_size: usize,
+    buffer: &'d mut [u8],
+    offset: usize,
+    chunks: usize,
     done: bool,
+    _inhibitor: SleepInhibitor,
 }
 
-impl<T: UsbContext> Operation for Read<'_, '_, T> {
+impl<T: Transport> Operation for Read<'_, '_, T> {
     /// Returns the total size in bytes.
     fn total(&self) -> usize {
-        self.length
+        self.buffer.len()
     }
 }
 
-impl<T: UsbContext> Iterator for Read<'_, '_, T> {
+impl<T: Transport> Iterator for Read<'_, '_, T> {
     type Item = Result<usize>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -233,16 +614,21 @@ impl<T: UsbContext> Iterator for Read<'_, '_, T> {
             return None;
         }
 
-        if let Some((i, chunk)) = self.chunks.next() {
+        if self.offset < self.buffer.len() {
+            let address = self.address + self.offset as u32;
             Some(
                 match self
                     .handle
-                    .read_chunk(self.address + (i * self.chunk_size) as u32, chunk)
+                    .read_next_chunk(address, &mut self.buffer[self.offset..])
                 {
-                    Ok(()) => Ok(i * self.chunk_size + chunk.len()),
+                    Ok(length) => {
+                        self.offset += length;
+                        self.chunks += 1;
+                        Ok(self.offset)
+                    }
                     Err(error) => {
                         self.done = true;
-                        Err(error)
+                        Err(error.in_step(Command::ReadMemory, address, self.chunks, self.offset))
                     }
                 },
             )
@@ -253,17 +639,368 @@ impl<T: UsbContext> Iterator for Read<'_, '_, T> {
     }
 }
 
-impl<'d, 'a, T: UsbContext> Read<'d, 'a, T> {
+impl<'d, 'a, T: Transport> Read<'d, 'a, T> {
     /// Read from the microcontroller's memory to a buffer, starting at the supplied address.
     pub(crate) fn at(handle: &'a mut TargetHandle<T>, buffer: &'d mut [u8], address: u32) -> Self {
-        let chunk_size = handle.max_read_chunk_size();
         Self {
             handle,
             address,
-            chunk_size,
-            length: buffer.len(),
             done: buffer.is_empty(),
-            chunks: buffer.chunks_mut(chunk_size).enumerate(),
+            buffer,
+            offset: 0,
+            chunks: 0,
+            _inhibitor: SleepInhibitor::new(),
+        }
+    }
+}
+
+/// Memory read operation streaming the memory contents to a writer instead of a buffer.
+///
+/// The memory is read page by page, so only a single page is held in memory at a time.
+pub struct Dump<'a, T: Transport, W: Write> {
+    handle: &'a mut TargetHandle<T>,
+    writer: W,
+    address: u32,
+    length: usize,
+    offset: usize,
+    done: bool,
+    _inhibitor: SleepInhibitor,
+}
+
+impl<T: Transport, W: Write> Operation for Dump<'_, T, W> {
+    /// Returns the total size in bytes.
+    fn total(&self) -> usize {
+        self.length
+    }
+}
+
+impl<T: Transport, W: Write> Iterator for Dump<'_, T, W> {
+    type Item = Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset >= self.length {
+            self.done = true;
+            return None;
+        }
+
+        let length = (PAGE_SIZE as usize).min(self.length - self.offset);
+        let address = self.address + self.offset as u32;
+        let mut buffer = vec![0u8; length];
+        let result = Read::at(self.handle, &mut buffer, address)
+            .execute()
+            .and_then(|_| Ok(self.writer.write_all(&buffer)?));
+        Some(match result {
+            Ok(()) => {
+                self.offset += length;
+                Ok(self.offset)
+            }
+            Err(error) => {
+                self.done = true;
+                // Steps are counted in pages rather than in the chunks of the page's read
+                let page = self.offset / PAGE_SIZE as usize;
+                Err(error.in_step(Command::ReadMemory, address, page, self.offset))
+            }
+        })
+    }
+}
+
+impl<'a, T: Transport, W: Write> Dump<'a, T, W> {
+    /// Read from the microcontroller's memory to a writer, starting at the supplied address.
+    pub(crate) fn at(
+        handle: &'a mut TargetHandle<T>,
+        writer: W,
+        address: u32,
+        length: usize,
+    ) -> Self {
+        Self {
+            handle,
+            writer,
+            address,
+            length,
+            offset: 0,
+            done: length == 0,
+            _inhibitor: SleepInhibitor::new(),
+        }
+    }
+
+    /// Returns the writer, e.g. to flush it after the operation is complete.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+/// CRC32 verification of a memory area, one page at a time.
+///
+/// Each step compares the CRC32 of the part of the area within one flash page. On a mismatch, the
+/// operation fails with [`Error::VerificationError`] and [`mismatch`] returns the address range of
+/// the differing part.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// target_handle.flash_bytes(&[0x55; 4096], 0x0800_1000)?;
+///
+/// let mut data = vec![0x55; 4096];
+/// data[3000] = 0xaa;
+/// let mut verification = target_handle.verify_at(&data, 0x0800_1000)?;
+/// assert!(verification.by_ref().any(|result| result.is_err()));
+/// assert_eq!(verification.mismatch(), Some(0x0800_1800..0x0800_1c00));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::VerificationError`]: ../enum.Error.html#variant.VerificationError
+/// [`mismatch`]: #method.mismatch
+pub struct Verification<'d, 'a, T: Transport> {
+    handle: &'a mut TargetHandle<T>,
+    address: u32,
+    data: &'d [u8],
+    offset: usize,
+    mismatch: Option<Range<u32>>,
+    done: bool,
+    _inhibitor: SleepInhibitor,
+}
+
+impl<T: Transport> Operation for Verification<'_, '_, T> {
+    /// Returns the total size in bytes.
+    fn total(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl<T: Transport> Iterator for Verification<'_, '_, T> {
+    type Item = Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset >= self.data.len() {
+            self.done = true;
+            return None;
+        }
+
+        // Chunks end at page boundaries, so a mismatch can be attributed to a single page
+        let start = self.address + self.offset as u32;
+        let page_end = Page::from_address(start).end() as usize + 1;
+        let length = (page_end - start as usize).min(self.data.len() - self.offset);
+        let chunk = &self.data[self.offset..self.offset + length];
+
+        Some(match self.handle.check_crc(start, chunk) {
+            Ok(()) => {
+                self.offset += length;
+                Ok(self.offset)
+            }
+            Err(Error::VerificationError(mismatch)) => {
+                self.done = true;
+                self.mismatch = Some(start..mismatch.end());
+                Err(Error::VerificationError(mismatch))
+            }
+            Err(error) => {
+                self.done = true;
+                let page = u8::from(Page::from_address(start))
+                    - u8::from(Page::from_address(self.address));
+                Err(error.in_step(Command::ReadCrc, start, page.into(), self.offset))
+            }
+        })
+    }
+}
+
+impl<'d, 'a, T: Transport> Verification<'d, 'a, T> {
+    /// Verify the target's memory starting at the supplied address against a buffer.
+    pub(crate) fn at(handle: &'a mut TargetHandle<T>, data: &'d [u8], address: u32) -> Self {
+        Self {
+            handle,
+            address,
+            done: data.is_empty(),
+            data,
+            offset: 0,
+            mismatch: None,
+            _inhibitor: SleepInhibitor::new(),
+        }
+    }
+
+    /// Returns the address range of the part of the area which did not match, if a mismatch has
+    /// been found.
+    pub fn mismatch(&self) -> Option<Range<u32>> {
+        self.mismatch.clone()
+    }
+}
+
+/// Erase, program and verify of a firmware image as a single operation.
+///
+/// The progress of all three phases is expressed in bytes, so a single progress bar can cover the
+/// whole update: every erased page counts as [`PAGE_SIZE`] bytes, and programming and verifying
+/// count the image's bytes each. The phase the most recent step belonged to is returned by
+/// [`phase`].
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::{FirmwareImage, Operation};
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 2048]);
+///
+/// let mut update = target_handle.update_image(&image)?;
+/// let total = update.total();
+/// while let Some(done) = update.next() {
+///     println!("{:?}: {}/{}", update.phase(), done?, total);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`PAGE_SIZE`]: ../constant.PAGE_SIZE.html
+/// [`phase`]: #method.phase
+pub struct Update<'d, 'a, T: Transport> {
+    handle: &'a mut TargetHandle<T>,
+    image: Cow<'d, FirmwareImage>,
+    phase: Phase,
+
+    /// Pages still to be erased, the next one last.
+    pages: Vec<Page>,
+
+    /// Index of the current segment when programming or verifying.
+    segment: usize,
+
+    /// Offset into the current segment when programming.
+    offset: usize,
+
+    /// Number of steps carried out so far.
+    steps: usize,
+
+    progress: usize,
+    total: usize,
+    done: bool,
+    _inhibitor: SleepInhibitor,
+}
+
+impl<T: Transport> Operation for Update<'_, '_, T> {
+    /// Returns the total number of bytes erased, programmed and verified.
+    fn total(&self) -> usize {
+        self.total
+    }
+}
+
+impl<T: Transport> Iterator for Update<'_, '_, T> {
+    type Item = Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.step() {
+            Some(result) => result,
+            None => {
+                self.done = true;
+                return None;
+            }
+        };
+        Some(match result {
+            Ok(progress) => {
+                self.progress += progress;
+                self.steps += 1;
+                Ok(self.progress)
+            }
+            Err((command, address, error)) => {
+                self.done = true;
+                Err(error.in_step(command, address, self.steps, self.progress))
+            }
+        })
+    }
+}
+
+impl<'d, 'a, T: Transport> Update<'d, 'a, T> {
+    /// Updates the image, erasing the given pages. The pages and segments have to be checked
+    /// against the application area already.
+    pub(crate) fn new(
+        handle: &'a mut TargetHandle<T>,
+        image: Cow<'d, FirmwareImage>,
+        mut pages: Vec<Page>,
+    ) -> Self {
+        let image_size: usize = image
+            .segments()
+            .iter()
+            .map(|segment| segment.data.len())
+            .sum();
+        let total = pages.len() * PAGE_SIZE as usize + 2 * image_size;
+        pages.reverse();
+        Self {
+            handle,
+            image,
+            phase: Phase::Erase,
+            pages,
+            segment: 0,
+            offset: 0,
+            steps: 0,
+            progress: 0,
+            total,
+            done: total == 0,
+            _inhibitor: SleepInhibitor::new(),
+        }
+    }
+
+    /// Returns the phase the most recent step belonged to.
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Carries out the next step of the current phase, moving on to the next phase if the current
+    /// one is complete. Returns the progress made or `None` if the update is complete. Errors are
+    /// returned with the command and address of the failed step.
+    #[allow(clippy::type_complexity)]
+    fn step(&mut self) -> Option<std::result::Result<usize, (Command, u32, Error)>> {
+        let segments = self.image.segments();
+        loop {
+            match self.phase {
+                Phase::Erase => match self.pages.pop() {
+                    Some(page) => {
+                        let address = page.begin();
+                        return Some(
+                            self.handle
+                                .erase_page(page)
+                                .map(|_| PAGE_SIZE as usize)
+                                .map_err(|error| (Command::ErasePage, address, error)),
+                        );
+                    }
+                    None => self.phase = Phase::Program,
+                },
+                Phase::Program => match segments.get(self.segment) {
+                    Some(segment) if self.offset < segment.data.len() => {
+                        let address = segment.address + self.offset as u32;
+                        let result = self
+                            .handle
+                            .program_next_chunk(address, &segment.data[self.offset..]);
+                        if let Ok(length) = result {
+                            self.offset += length;
+                        }
+                        return Some(result.map_err(|error| (Command::Program, address, error)));
+                    }
+                    Some(_) => {
+                        self.segment += 1;
+                        self.offset = 0;
+                    }
+                    None => {
+                        self.phase = Phase::Verify;
+                        self.segment = 0;
+                    }
+                },
+                Phase::Verify => {
+                    let segment = segments.get(self.segment)?;
+                    self.segment += 1;
+                    return Some(
+                        self.handle
+                            .verify(&segment.data, segment.address)
+                            .map(|_| segment.data.len())
+                            .map_err(|error| (Command::ReadCrc, segment.address, error)),
+                    );
+                }
+            }
         }
     }
 }
@@ -0,0 +1,599 @@
+//! Contains declarative flash plans, read from JSON or TOML manifests.
+
+use crate::context::UsbContext;
+use crate::discovery::Discovery;
+use crate::error::Error;
+use crate::firmware::{FirmwareImage, ParseError};
+use crate::operation::Operation;
+use crate::report::{self, Report};
+use crate::session::{EraseStrategy, Verify};
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Declarative description of flashing one or more images to one or more targets.
+///
+/// A plan is usually read from a manifest file with [`load`]. In TOML, a plan flashing an
+/// application and a configuration block to two fixture slots looks like this:
+///
+/// ```toml
+/// erase = "MinimalPages"
+/// verify = "Crc"
+/// exit = true
+///
+/// [[images]]
+/// file = "application.hex"
+///
+/// [[images]]
+/// file = "config.bin"
+/// address = 0x0800f800
+///
+/// [[targets]]
+/// port = "1-4.1"
+///
+/// [[targets]]
+/// port = "1-4.2"
+/// ```
+///
+/// The same structure can be written in JSON. Images are read by their file extension: `.hex` and
+/// `.ihex` as Intel HEX, `.srec`, `.s19` and `.mot` as Motorola S-records, `.elf` as ELF and all
+/// others as raw binaries, which need an `address`. Targets are selected like with
+/// [`Discovery`]. Without any targets, the only connected target is used. `erase` and `verify`
+/// default to `MinimalPages` and `Crc`, `exit` to `false`.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::plan::Plan;
+/// use punt::Context;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let plan = Plan::load("factory.toml")?;
+/// let report = plan.execute(&Context::new()?)?;
+/// for step in &report.steps {
+///     println!("{} {:?}: {:?}", step.target, step.step, step.result);
+/// }
+/// assert!(report.is_success());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`load`]: #method.load
+/// [`Discovery`]: ../struct.Discovery.html
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Plan {
+    /// Images to flash, in order.
+    pub images: Vec<PlanImage>,
+
+    /// Targets to flash the images to, one after another.
+    #[serde(default)]
+    pub targets: Vec<Discovery>,
+
+    /// Which pages are erased before programming.
+    #[serde(default = "default_erase")]
+    pub erase: EraseStrategy,
+
+    /// How the flash contents are checked after programming.
+    #[serde(default = "default_verify")]
+    pub verify: Verify,
+
+    /// Whether the targets start their application after flashing.
+    #[serde(default)]
+    pub exit: bool,
+}
+
+fn default_erase() -> EraseStrategy {
+    EraseStrategy::MinimalPages
+}
+
+fn default_verify() -> Verify {
+    Verify::Crc
+}
+
+/// An image file to flash as part of a [`Plan`].
+///
+/// [`Plan`]: struct.Plan.html
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PlanImage {
+    /// Path of the image file. Relative paths in a manifest are relative to the manifest's
+    /// directory.
+    pub file: PathBuf,
+
+    /// Address of the image, only used for raw binaries.
+    #[serde(default)]
+    pub address: Option<u32>,
+}
+
+/// A step carried out for each target while executing a [`Plan`].
+///
+/// [`Plan`]: struct.Plan.html
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Step {
+    /// Selecting and opening the target.
+    Open,
+
+    /// Erasing according to the plan's erase strategy.
+    Erase,
+
+    /// Programming the given image.
+    Program(PathBuf),
+
+    /// Verifying the given image.
+    Verify(PathBuf),
+
+    /// Leaving the bootloader.
+    Exit,
+}
+
+/// Result of a single step of a plan.
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StepResult {
+    /// Description of the target, i.e. its serial number once opened.
+    pub target: String,
+
+    /// The step carried out.
+    pub step: Step,
+
+    /// Time it took to carry out the step.
+    pub duration: Duration,
+
+    /// The outcome of the step.
+    pub result: Result<(), Error>,
+}
+
+/// Results of all steps of an executed plan. After a step fails, the remaining steps for the same
+/// target are skipped, but the other targets are still flashed.
+#[derive(Debug, Clone, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PlanReport {
+    /// The steps carried out, in order.
+    pub steps: Vec<StepResult>,
+}
+
+impl PlanReport {
+    /// Returns whether all steps succeeded.
+    pub fn is_success(&self) -> bool {
+        self.steps.iter().all(|step| step.result.is_ok())
+    }
+
+    /// Returns a [`Report`] for each target, in the order the targets were flashed, e.g. to
+    /// export the results as JUnit XML.
+    ///
+    /// [`Report`]: ../struct.Report.html
+    pub fn reports(&self) -> Vec<Report> {
+        let mut reports: Vec<Report> = Vec::new();
+        let mut targets: Vec<&str> = Vec::new();
+        for result in &self.steps {
+            let index = match targets.iter().position(|target| *target == result.target) {
+                Some(index) => index,
+                None => {
+                    targets.push(&result.target);
+                    reports.push(Report::new(&result.target));
+                    reports.len() - 1
+                }
+            };
+            reports[index].push(report::Step {
+                name: match &result.step {
+                    Step::Open => "open".to_string(),
+                    Step::Erase => "erase".to_string(),
+                    Step::Program(file) => format!("program {}", file.display()),
+                    Step::Verify(file) => format!("verify {}", file.display()),
+                    Step::Exit => "exit".to_string(),
+                },
+                duration: result.duration,
+                crc: None,
+                error: result.result.clone().err(),
+            });
+        }
+        reports
+    }
+}
+
+/// Error while reading a plan or its images.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PlanError {
+    /// A file could not be read.
+    Io(PathBuf, io::Error),
+
+    /// The manifest is not valid JSON or TOML or does not describe a plan, or the plan cannot be
+    /// serialised.
+    Syntax(String),
+
+    /// An image file could not be parsed.
+    Image(PathBuf, ParseError),
+
+    /// A raw binary image has no address.
+    MissingAddress(PathBuf),
+
+    /// The target selection of a plan is invalid, e.g. it has several targets without any
+    /// selection criteria.
+    InvalidTargets,
+}
+
+impl StdError for PlanError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            PlanError::Io(_, error) => Some(error),
+            PlanError::Image(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PlanError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            PlanError::Io(path, error) => write!(fmt, "Cannot read {}: {}", path.display(), error),
+            PlanError::Syntax(message) => write!(fmt, "Invalid plan: {}", message),
+            PlanError::Image(path, error) => {
+                write!(fmt, "Cannot parse {}: {}", path.display(), error)
+            }
+            PlanError::MissingAddress(path) => {
+                write!(fmt, "No address given for binary {}", path.display())
+            }
+            PlanError::InvalidTargets => {
+                fmt.write_str("Several targets are given, but not all of them select a target")
+            }
+        }
+    }
+}
+
+impl Plan {
+    /// Parses a plan from JSON.
+    pub fn from_json(text: &str) -> Result<Self, PlanError> {
+        serde_json::from_str(text).map_err(|error| PlanError::Syntax(error.to_string()))
+    }
+
+    /// Parses a plan from TOML.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::plan::Plan;
+    /// use punt::EraseStrategy;
+    ///
+    /// let plan = Plan::from_toml(
+    ///     r#"
+    ///     erase = "FullApplication"
+    ///
+    ///     [[images]]
+    ///     file = "firmware.bin"
+    ///     address = 0x08001000
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(plan.erase, EraseStrategy::FullApplication);
+    /// assert_eq!(plan.images[0].address, Some(0x0800_1000));
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Self, PlanError> {
+        toml::from_str(text).map_err(|error| PlanError::Syntax(error.to_string()))
+    }
+
+    /// Reads a plan from a manifest file, as TOML if its name ends in `.toml` and as JSON
+    /// otherwise. Relative image paths are resolved against the manifest's directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PlanError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|error| PlanError::Io(path.to_path_buf(), error))?;
+        let mut plan = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&text)?,
+            _ => Self::from_json(&text)?,
+        };
+        if let Some(directory) = path.parent() {
+            for image in &mut plan.images {
+                image.file = directory.join(&image.file);
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Serialises the plan to JSON.
+    pub fn to_json(&self) -> Result<String, PlanError> {
+        serde_json::to_string_pretty(self).map_err(|error| PlanError::Syntax(error.to_string()))
+    }
+
+    /// Serialises the plan to TOML.
+    pub fn to_toml(&self) -> Result<String, PlanError> {
+        toml::to_string(self).map_err(|error| PlanError::Syntax(error.to_string()))
+    }
+
+    /// Writes the plan to a manifest file, as TOML if its name ends in `.toml` and as JSON
+    /// otherwise. Image paths within the manifest's directory are written relative to it, so
+    /// [`load`] reads back the same plan.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::mock::MockTarget;
+    /// use punt::plan::{Plan, PlanImage};
+    /// use punt::{EraseStrategy, FirmwareImage, FlashSession};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let image = FirmwareImage::from_ihex(&std::fs::read_to_string("firmware.hex")?)?;
+    /// let mut target_handle = MockTarget::new().open();
+    ///
+    /// let session = FlashSession::new(&mut target_handle)
+    ///     .erase(EraseStrategy::FullApplication)
+    ///     .exit(true);
+    /// let plan = session.to_plan(vec![PlanImage {
+    ///     file: "firmware.hex".into(),
+    ///     address: None,
+    /// }]);
+    /// session.run(&image)?;
+    ///
+    /// // Repeat the same session later with `Plan::load("bench.toml")?.execute(...)`
+    /// plan.save("bench.toml")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`load`]: #method.load
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PlanError> {
+        let path = path.as_ref();
+        let mut plan = self.clone();
+        if let Some(directory) = path.parent() {
+            for image in &mut plan.images {
+                if let Ok(relative) = image.file.strip_prefix(directory) {
+                    image.file = relative.to_path_buf();
+                }
+            }
+        }
+        let text = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => plan.to_toml()?,
+            _ => plan.to_json()?,
+        };
+        fs::write(path, text).map_err(|error| PlanError::Io(path.to_path_buf(), error))
+    }
+
+    /// Reads all image files of the plan.
+    pub fn load_images(&self) -> Result<Vec<FirmwareImage>, PlanError> {
+        self.images.iter().map(PlanImage::load).collect()
+    }
+
+    /// Reads all images, then flashes them to every target in turn. Fails before any target is
+    /// touched if an image cannot be read; errors while flashing are reported per step.
+    pub fn execute<C: UsbContext>(&self, context: &C) -> Result<PlanReport, PlanError> {
+        let images = self.load_images()?;
+        let selections = match self.targets.as_slice() {
+            [] => vec![Discovery::default()],
+            targets
+                if targets.len() > 1
+                    && targets
+                        .iter()
+                        .any(|target| target.serial.is_none() && target.port.is_none()) =>
+            {
+                return Err(PlanError::InvalidTargets)
+            }
+            targets => targets.to_vec(),
+        };
+
+        let mut report = PlanReport::default();
+        for selection in &selections {
+            let start = Instant::now();
+            let mut target = describe_selection(selection);
+            let handle = selection
+                .pick_target(context)
+                .and_then(|target| target.open());
+            let mut handle = match handle {
+                Ok(handle) => handle,
+                Err(error) => {
+                    report.push(&target, Step::Open, start, Err(error));
+                    continue;
+                }
+            };
+            target = handle.serial().to_string();
+            report.push(&target, Step::Open, start, Ok(()));
+            self.flash(&mut handle, &images, &target, &mut report);
+        }
+        Ok(report)
+    }
+
+    /// Carries out all steps after opening for a single target, stopping at the first failure.
+    fn flash<T: Transport>(
+        &self,
+        handle: &mut TargetHandle<T>,
+        images: &[FirmwareImage],
+        target: &str,
+        report: &mut PlanReport,
+    ) {
+        let start = Instant::now();
+        let erase = match self.erase {
+            EraseStrategy::MinimalPages => Some(
+                images
+                    .iter()
+                    .try_for_each(|image| handle.erase_image(image)?.execute()),
+            ),
+            EraseStrategy::FullApplication => Some(handle.memory_map().and_then(|map| {
+                let application = map.application();
+                handle
+                    .erase_area(application.start(), application.size())?
+                    .execute()
+            })),
+            EraseStrategy::Skip => None,
+        };
+        if let Some(result) = erase {
+            if !report.push(target, Step::Erase, start, result) {
+                return;
+            }
+        }
+
+        for (image, plan_image) in images.iter().zip(&self.images) {
+            let file = plan_image.file.clone();
+            let start = Instant::now();
+            let result = handle
+                .program_image(image)
+                .and_then(|mut program| program.execute());
+            if !report.push(target, Step::Program(file.clone()), start, result) {
+                return;
+            }
+
+            let start = Instant::now();
+            let result = match self.verify {
+                Verify::Crc => handle.verify_image(image),
+                Verify::Readback => image
+                    .segments()
+                    .iter()
+                    .try_for_each(|segment| handle.verify_readback(&segment.data, segment.address)),
+                Verify::Skip => continue,
+            };
+            if !report.push(target, Step::Verify(file), start, result) {
+                return;
+            }
+        }
+
+        if self.exit {
+            let start = Instant::now();
+            let result = handle.exit_bootloader();
+            report.push(target, Step::Exit, start, result);
+        }
+    }
+}
+
+impl PlanReport {
+    /// Adds a step's result, timed from the given start. Returns whether the step succeeded.
+    fn push(
+        &mut self,
+        target: &str,
+        step: Step,
+        start: Instant,
+        result: Result<(), Error>,
+    ) -> bool {
+        let success = result.is_ok();
+        self.steps.push(StepResult {
+            target: target.to_string(),
+            step,
+            duration: start.elapsed(),
+            result,
+        });
+        success
+    }
+}
+
+impl PlanImage {
+    /// Reads the image file, choosing the format by its extension.
+    fn load(&self) -> Result<FirmwareImage, PlanError> {
+        let path = &self.file;
+        let io_error = |error| PlanError::Io(path.clone(), error);
+        let parse_error = |error| PlanError::Image(path.clone(), error);
+        let text = || fs::read_to_string(path).map_err(io_error);
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("hex" | "ihex") => FirmwareImage::from_ihex(&text()?).map_err(parse_error),
+            Some("srec" | "s19" | "mot") => FirmwareImage::from_srec(&text()?).map_err(parse_error),
+            Some("elf") => {
+                FirmwareImage::from_elf(&fs::read(path).map_err(io_error)?).map_err(parse_error)
+            }
+            _ => {
+                let address = self
+                    .address
+                    .ok_or_else(|| PlanError::MissingAddress(path.clone()))?;
+                Ok(FirmwareImage::from_binary(
+                    address,
+                    fs::read(path).map_err(io_error)?,
+                ))
+            }
+        }
+    }
+}
+
+/// Describes a target selection for steps before the target's serial number is known.
+fn describe_selection(selection: &Discovery) -> String {
+    match (&selection.serial, &selection.port) {
+        (Some(serial), Some(port)) => format!("{} at {}", serial, port),
+        (Some(serial), None) => serial.clone(),
+        (None, Some(port)) => format!("port {}", port),
+        (None, None) => "target".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EraseError;
+    use crate::mock::MockTarget;
+    use crate::session::FlashSession;
+
+    #[test]
+    fn session_exports_a_replayable_plan() {
+        let mut target_handle = MockTarget::new().open();
+        let session = FlashSession::new(&mut target_handle)
+            .erase(EraseStrategy::FullApplication)
+            .verify(Verify::Readback)
+            .exit(true);
+        let plan = session.to_plan(vec![PlanImage {
+            file: "config.bin".into(),
+            address: Some(0x0800_f800),
+        }]);
+
+        assert_eq!(plan.targets.len(), 1);
+        assert!(plan.targets[0].serial.is_some());
+        assert_eq!(Plan::from_toml(&plan.to_toml().unwrap()).unwrap(), plan);
+        assert_eq!(Plan::from_json(&plan.to_json().unwrap()).unwrap(), plan);
+    }
+
+    #[test]
+    fn save_writes_paths_relative_to_the_manifest() {
+        let directory = std::env::temp_dir().join(format!("punt-plan-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let manifest = directory.join("bench.toml");
+        let plan = Plan {
+            images: vec![PlanImage {
+                file: directory.join("firmware.hex"),
+                address: None,
+            }],
+            targets: Vec::new(),
+            erase: EraseStrategy::MinimalPages,
+            verify: Verify::Crc,
+            exit: false,
+        };
+
+        plan.save(&manifest).unwrap();
+        let text = fs::read_to_string(&manifest).unwrap();
+        let loaded = Plan::load(&manifest);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(text.contains("file = \"firmware.hex\""));
+        assert_eq!(loaded.unwrap(), plan);
+    }
+
+    #[test]
+    fn reports_group_steps_by_target() {
+        let step = |target: &str, step, result| StepResult {
+            target: target.to_string(),
+            step,
+            duration: Duration::from_millis(5),
+            result,
+        };
+        let plan_report = PlanReport {
+            steps: vec![
+                step("A", Step::Open, Ok(())),
+                step("B", Step::Open, Ok(())),
+                step("A", Step::Program("app.hex".into()), Ok(())),
+                step(
+                    "B",
+                    Step::Erase,
+                    Err(Error::EraseError(EraseError::VerifyFailed)),
+                ),
+            ],
+        };
+
+        let reports = plan_report.reports();
+        assert_eq!(reports.len(), 2);
+        let names: Vec<_> = reports[0]
+            .steps()
+            .iter()
+            .map(|step| step.name.as_str())
+            .collect();
+        assert_eq!(names, ["open", "program app.hex"]);
+        assert_eq!(
+            reports[1].steps()[1].error,
+            Some(Error::EraseError(EraseError::VerifyFailed))
+        );
+        assert!(reports[1].to_junit_xml().contains(r#"failures="1""#));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::context::UsbContext;
+use crate::error::Result;
+use crate::target_handle::TargetHandle;
+use rusb::DeviceHandle;
+use std::collections::BTreeMap;
+
+/// Keeps open handles to all connected targets, identified by their serial numbers.
+///
+/// The pool is kept up to date by calling [`refresh`], which drops handles to targets which have
+/// been disconnected and opens handles to newly connected ones. A target which is unplugged and
+/// plugged back in is thus reconnected on the next refresh. Serial numbers are expected to be
+/// unique; of several targets reporting the same serial number, only one is added to the pool.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{Context, TargetPool};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut pool = TargetPool::new(Context::new()?);
+/// pool.refresh()?;
+///
+/// let serials: Vec<String> = pool.serials().map(String::from).collect();
+/// for serial in serials {
+///     if let Some(handle) = pool.get(&serial) {
+///         println!("{}: {}", serial, handle.bootloader_info()?.identifier);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`refresh`]: #method.refresh
+pub struct TargetPool<T: UsbContext> {
+    context: T,
+    handles: BTreeMap<String, TargetHandle<DeviceHandle<T>>>,
+}
+
+impl<T: UsbContext> TargetPool<T> {
+    /// Creates an empty pool using the given USB context.
+    pub fn new(context: T) -> Self {
+        Self {
+            context,
+            handles: BTreeMap::new(),
+        }
+    }
+
+    /// Checks all handles in the pool and looks for new targets.
+    ///
+    /// Handles to targets which no longer respond are dropped. All connected targets not yet in
+    /// the pool are opened and added. Targets which cannot be opened are skipped and tried again on
+    /// the next refresh. Returns [`Error::IoError`] on USB errors during device enumeration.
+    ///
+    /// [`Error::IoError`]: enum.Error.html#variant.IoError
+    pub fn refresh(&mut self) -> Result<()> {
+        self.handles
+            .retain(|_, handle| handle.refresh_info().is_ok());
+
+        for target in self.context.find_targets()? {
+            let serial = match target.serial() {
+                Ok(serial) => serial,
+                Err(_) => continue,
+            };
+            if self.handles.contains_key(&serial) {
+                continue;
+            }
+            if let Ok(handle) = target.open() {
+                self.handles.insert(serial, handle);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the serial numbers of all targets in the pool in ascending order.
+    pub fn serials(&self) -> impl Iterator<Item = &str> {
+        self.handles.keys().map(String::as_str)
+    }
+
+    /// Returns the handle to the target with the given serial number, if it is in the pool.
+    pub fn get(&mut self, serial: &str) -> Option<&mut TargetHandle<DeviceHandle<T>>> {
+        self.handles.get_mut(serial)
+    }
+
+    /// Removes the handle to the target with the given serial number from the pool and hands it to
+    /// the caller. Note that the target is opened and added to the pool again on the next
+    /// [`refresh`] if it is still connected.
+    ///
+    /// [`refresh`]: #method.refresh
+    pub fn take(&mut self, serial: &str) -> Option<TargetHandle<DeviceHandle<T>>> {
+        self.handles.remove(serial)
+    }
+
+    /// Returns the number of targets in the pool.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if the pool contains no targets.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+}
@@ -0,0 +1,291 @@
+//! Contains per-device provisioning data and its templating from CSV rows.
+
+use crate::firmware::decode_hex;
+use crate::flash::Region;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+
+/// Values for a single device, keyed by field name. Usually a row of a CSV file read with
+/// [`parse_csv`].
+///
+/// [`parse_csv`]: fn.parse_csv.html
+pub type Row = HashMap<String, String>;
+
+/// How a field's value is encoded in flash.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FieldKind {
+    /// UTF-8 text of at most the given number of bytes, padded with zeroes.
+    Text(usize),
+
+    /// Unsigned 32-bit integer in little-endian byte order. Written in decimal or, prefixed with
+    /// `0x`, in hexadecimal.
+    U32,
+
+    /// Six-byte MAC address, written as hexadecimal digit pairs separated by `:` or `-`.
+    Mac,
+
+    /// Raw bytes of exactly the given length, written as hexadecimal digit pairs, e.g. for
+    /// calibration data.
+    Bytes(usize),
+}
+
+impl FieldKind {
+    /// Returns the number of bytes the field occupies in flash.
+    pub fn size(self) -> usize {
+        match self {
+            FieldKind::Text(length) | FieldKind::Bytes(length) => length,
+            FieldKind::U32 => 4,
+            FieldKind::Mac => 6,
+        }
+    }
+
+    /// Encodes a value, returning `None` if it is malformed or too long.
+    fn encode(self, value: &str) -> Option<Vec<u8>> {
+        match self {
+            FieldKind::Text(length) => {
+                let mut data = value.as_bytes().to_vec();
+                if data.len() > length {
+                    return None;
+                }
+                data.resize(length, 0);
+                Some(data)
+            }
+            FieldKind::U32 => {
+                let number = match value.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => value.parse().ok()?,
+                };
+                Some(number.to_le_bytes().to_vec())
+            }
+            FieldKind::Mac => {
+                let data = value
+                    .split([':', '-'])
+                    .map(|part| {
+                        if part.len() == 2 {
+                            u8::from_str_radix(part, 16).ok()
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Option<Vec<u8>>>()?;
+                Some(data).filter(|data| data.len() == 6)
+            }
+            FieldKind::Bytes(length) => decode_hex(value).filter(|data| data.len() == length),
+        }
+    }
+}
+
+/// A named value at a fixed offset within the provisioning region.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Field {
+    /// Name of the field, as used in the rows' keys.
+    pub name: String,
+
+    /// Offset of the field from the start of the region.
+    pub offset: usize,
+
+    /// Encoding of the field's value.
+    pub kind: FieldKind,
+}
+
+/// Layout of per-device data such as serial numbers, MAC addresses and calibration data within
+/// a designated flash region.
+///
+/// A layout turns a [`Row`] of values into the region's contents with [`render`]. Bytes not
+/// covered by a field are left erased (`0xff`). The result is written as part of a
+/// [`FlashSession`] with [`FlashSession::provision`].
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::provision::{parse_csv, FieldKind, ProvisioningLayout};
+/// use punt::{Region, PAGE_SIZE};
+///
+/// let layout = ProvisioningLayout::new(Region::new(0x0800_fc00, PAGE_SIZE as usize).unwrap())
+///     .field("serial", 0, FieldKind::Text(12))
+///     .field("mac", 12, FieldKind::Mac)
+///     .field("gain", 20, FieldKind::U32);
+///
+/// let rows = parse_csv(
+///     "serial,mac,gain\n\
+///      SN0001,02:00:00:00:00:01,1000\n\
+///      SN0002,02:00:00:00:00:02,0x3e8\n",
+/// )
+/// .unwrap();
+///
+/// let data = layout.render(&rows[1]).unwrap();
+/// assert_eq!(data.len(), PAGE_SIZE as usize);
+/// assert_eq!(&data[..8], b"SN0002\0\0");
+/// assert_eq!(data[12..18], [0x02, 0x00, 0x00, 0x00, 0x00, 0x02]);
+/// assert_eq!(data[20..24], 1000u32.to_le_bytes());
+/// assert_eq!(data[24], 0xff);
+/// ```
+///
+/// [`Row`]: type.Row.html
+/// [`render`]: #method.render
+/// [`FlashSession`]: ../struct.FlashSession.html
+/// [`FlashSession::provision`]: ../struct.FlashSession.html#method.provision
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProvisioningLayout {
+    region: Region,
+    fields: Vec<Field>,
+}
+
+impl ProvisioningLayout {
+    /// Creates a layout without any fields for the given region.
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field at the given offset from the start of the region.
+    pub fn field(mut self, name: impl Into<String>, offset: usize, kind: FieldKind) -> Self {
+        self.fields.push(Field {
+            name: name.into(),
+            offset,
+            kind,
+        });
+        self
+    }
+
+    /// Returns the region the data is written to.
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    /// Returns all fields of the layout.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Encodes the values of a row into the contents of the whole region. Values in the row
+    /// without a matching field are ignored.
+    pub fn render(&self, row: &Row) -> Result<Vec<u8>, ProvisioningError> {
+        let mut data = vec![0xff; self.region.size()];
+        for field in &self.fields {
+            let end = field.offset + field.kind.size();
+            if end > data.len() {
+                return Err(ProvisioningError::OutOfRegion(field.name.clone()));
+            }
+            let value = row
+                .get(&field.name)
+                .ok_or_else(|| ProvisioningError::MissingValue(field.name.clone()))?;
+            let encoded =
+                field
+                    .kind
+                    .encode(value.trim())
+                    .ok_or_else(|| ProvisioningError::InvalidValue {
+                        field: field.name.clone(),
+                        value: value.clone(),
+                    })?;
+            data[field.offset..end].copy_from_slice(&encoded);
+        }
+        Ok(data)
+    }
+}
+
+/// Parses CSV text into rows keyed by the column names in its first line.
+///
+/// Fields are separated by commas and may be enclosed in double quotes to contain commas, with
+/// doubled quotes standing for a literal one. Whitespace around unquoted fields and empty lines
+/// are ignored.
+pub fn parse_csv(text: &str) -> Result<Vec<Row>, ProvisioningError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let header = match lines.next() {
+        Some((number, line)) => split_csv_line(line).ok_or(ProvisioningError::Csv(number))?,
+        None => return Ok(Vec::new()),
+    };
+
+    lines
+        .map(|(number, line)| {
+            let values = split_csv_line(line)
+                .filter(|values| values.len() == header.len())
+                .ok_or(ProvisioningError::Csv(number))?;
+            Ok(header.iter().cloned().zip(values).collect())
+        })
+        .collect()
+}
+
+/// Splits a single CSV line into its fields. Returns `None` if quotes are unbalanced.
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next()? {
+                    '"' if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    '"' => break,
+                    c => field.push(c),
+                }
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        } else {
+            while let Some(c) = chars.next_if(|&c| c != ',') {
+                field.push(c);
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+        match chars.next() {
+            Some(',') => continue,
+            None => return Some(fields),
+            Some(_) => return None,
+        }
+    }
+}
+
+/// Error while preparing provisioning data.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ProvisioningError {
+    /// The row has no value for the named field.
+    MissingValue(String),
+
+    /// The value for a field cannot be encoded, e.g. because it is malformed or too long.
+    InvalidValue {
+        /// Name of the field.
+        field: String,
+
+        /// The offending value.
+        value: String,
+    },
+
+    /// The named field extends beyond the end of the region.
+    OutOfRegion(String),
+
+    /// The CSV line with the given number (starting at 1) is malformed or has the wrong number
+    /// of fields.
+    Csv(usize),
+}
+
+impl StdError for ProvisioningError {}
+
+impl Display for ProvisioningError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            ProvisioningError::MissingValue(field) => write!(fmt, "No value for field {}", field),
+            ProvisioningError::InvalidValue { field, value } => {
+                write!(fmt, "Invalid value {:?} for field {}", value, field)
+            }
+            ProvisioningError::OutOfRegion(field) => {
+                write!(
+                    fmt,
+                    "Field {} extends beyond the provisioning region",
+                    field
+                )
+            }
+            ProvisioningError::Csv(line) => write!(fmt, "Malformed CSV in line {}", line),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Contains the means of asking a running application to start the bootloader.
+
+use crate::context::UsbContext;
+use crate::error::{Error, Result};
+use crate::TIMEOUT;
+use rusb::{Device, DeviceHandle};
+
+/// Request telling a running application to restart into the punt bootloader.
+///
+/// The protocol for entering the bootloader is up to the application, so the caller describes the
+/// request the application reacts to. Applications usually reset while the request is still being
+/// processed, so transfer errors caused by the device disappearing are not treated as failures.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{BootloaderTrigger, Context, UsbContext};
+/// use std::time::Duration;
+///
+/// # fn main() -> punt::Result<()> {
+/// let context = Context::new()?;
+/// let trigger = BootloaderTrigger::Control {
+///     request_type: 0x41,
+///     request: 0xb0,
+///     value: 0,
+///     index: 0,
+///     data: Vec::new(),
+/// };
+/// let target = context.reboot_into_bootloader((0x1209, 0x0001), &trigger, Duration::from_secs(5))?;
+/// let mut target_handle = target.open()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BootloaderTrigger {
+    /// An OUT control transfer, e.g. a vendor request or a CDC `SET_LINE_CODING` request
+    /// selecting a magic baud rate.
+    Control {
+        /// `bmRequestType` field, including the direction, type and recipient.
+        request_type: u8,
+
+        /// `bRequest` field.
+        request: u8,
+
+        /// `wValue` field.
+        value: u16,
+
+        /// `wIndex` field.
+        index: u16,
+
+        /// Data stage, if any.
+        data: Vec<u8>,
+    },
+
+    /// Magic data written to a bulk or interrupt OUT endpoint, e.g. of a CDC or HID interface.
+    /// Kernel drivers bound to the interface are detached while writing, where supported.
+    Write {
+        /// Number of the interface the endpoint belongs to.
+        interface: u8,
+
+        /// Address of the endpoint, e.g. `0x02`.
+        endpoint: u8,
+
+        /// Data to write.
+        data: Vec<u8>,
+
+        /// Whether the endpoint is an interrupt endpoint rather than a bulk endpoint.
+        interrupt: bool,
+    },
+}
+
+impl BootloaderTrigger {
+    /// Sends the request to an application.
+    pub(crate) fn send<T: UsbContext>(&self, device: &Device<T>) -> Result<()> {
+        let mut handle = device.open()?;
+        let result = match self {
+            BootloaderTrigger::Control {
+                request_type,
+                request,
+                value,
+                index,
+                data,
+            } => handle
+                .write_control(*request_type, *request, *value, *index, data, TIMEOUT)
+                .map(|_| ()),
+            BootloaderTrigger::Write {
+                interface,
+                endpoint,
+                data,
+                interrupt,
+            } => write_endpoint(&mut handle, *interface, *endpoint, data, *interrupt),
+        };
+
+        match result {
+            // The application may reset before completing the transfer
+            Ok(())
+            | Err(rusb::Error::NoDevice)
+            | Err(rusb::Error::Io)
+            | Err(rusb::Error::Pipe)
+            | Err(rusb::Error::Timeout) => Ok(()),
+            Err(error) => Err(Error::IoError(error)),
+        }
+    }
+}
+
+/// Claims an interface and writes data to one of its OUT endpoints.
+fn write_endpoint<T: UsbContext>(
+    handle: &mut DeviceHandle<T>,
+    interface: u8,
+    endpoint: u8,
+    data: &[u8],
+    interrupt: bool,
+) -> rusb::Result<()> {
+    match handle.set_auto_detach_kernel_driver(true) {
+        Ok(()) | Err(rusb::Error::NotSupported) => (),
+        Err(error) => return Err(error),
+    }
+    handle.claim_interface(interface)?;
+    if interrupt {
+        handle.write_interrupt(endpoint, data, TIMEOUT)?;
+    } else {
+        handle.write_bulk(endpoint, data, TIMEOUT)?;
+    }
+    // Releasing fails if the application has already reset, which is what was asked for
+    let _ = handle.release_interface(interface);
+    Ok(())
+}
@@ -0,0 +1,271 @@
+//! Contains a transport for targets attached to another machine and the matching server.
+//!
+//! The server side runs on the machine the target is connected to and forwards every request of
+//! a [`TcpTransport`] to the target. Each message corresponds to one [`Transport`] primitive, so
+//! all bootloader logic stays on the client.
+//!
+//! # Security
+//!
+//! The protocol has no authentication, access control or encryption. Anyone able to connect to
+//! the server can erase, program and read the target's flash. Bind the listener to the loopback
+//! interface and reach it through an authenticated tunnel such as SSH port forwarding, or only
+//! expose it on a network where every host is trusted.
+//!
+//! # Examples
+//!
+//! On the machine with the target, listening on the loopback interface only:
+//!
+//! ```rust, no_run
+//! use punt::{Context, UsbContext};
+//! use std::net::TcpListener;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let context = Context::new()?;
+//! let mut target_handle = context.open_target(None)?;
+//! punt::remote::serve(&TcpListener::bind("127.0.0.1:5120")?, &mut target_handle)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! On the build host, after forwarding the port with `ssh -N -L 5120:localhost:5120
+//! raspberrypi.local`:
+//!
+//! ```rust, no_run
+//! use punt::TcpTransport;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut target_handle = TcpTransport::connect("localhost:5120")?;
+//! println!("{}", target_handle.bootloader_info()?.identifier);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`TcpTransport`]: struct.TcpTransport.html
+//! [`Transport`]: ../trait.Transport.html
+
+use crate::error::{Error, Result};
+use crate::target_handle::TargetHandle;
+use crate::transport::{Timeouts, Transport};
+use crate::TIMEOUT;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// Message types sent by the client
+const COMMAND: u8 = b'C';
+const WRITE: u8 = b'W';
+const READ: u8 = b'R';
+
+/// USB errors in the order they are numbered in on the wire. Index 0 means success.
+const USB_ERRORS: [rusb::Error; 14] = [
+    rusb::Error::Io,
+    rusb::Error::InvalidParam,
+    rusb::Error::Access,
+    rusb::Error::NoDevice,
+    rusb::Error::NotFound,
+    rusb::Error::Busy,
+    rusb::Error::Timeout,
+    rusb::Error::Overflow,
+    rusb::Error::Pipe,
+    rusb::Error::Interrupted,
+    rusb::Error::NoMem,
+    rusb::Error::NotSupported,
+    rusb::Error::BadDescriptor,
+    rusb::Error::Other,
+];
+
+/// Largest transfer accepted by the server. Real targets use far smaller buffers, so anything
+/// larger indicates a corrupt stream.
+const MAX_TRANSFER_LENGTH: usize = 0x10000;
+
+/// Transport to a target attached to a remote machine running [`serve`].
+///
+/// The timeouts passed to the transfers are not forwarded. The server uses the default
+/// [`Timeouts`] for the transfers with the target.
+///
+/// [`serve`]: fn.serve.html
+/// [`Timeouts`]: ../struct.Timeouts.html
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// Connects to a server and returns a handle for the target it serves.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<TargetHandle<Self>> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        // The server may wait for a USB timeout itself before answering
+        stream.set_read_timeout(Some(TIMEOUT * 4))?;
+
+        let in_buffer_length = read_u16(&mut stream)?;
+        let out_buffer_length = read_u16(&mut stream)?;
+        let mut serial = vec![0u8; read_u16(&mut stream)? as usize];
+        stream.read_exact(&mut serial)?;
+        let serial = String::from_utf8(serial).map_err(|_| Error::MalformedResponse)?;
+
+        Ok(TargetHandle::new(
+            Self { stream },
+            in_buffer_length,
+            out_buffer_length,
+            serial,
+        ))
+    }
+
+    /// Reads the status sent by the server in response to a request.
+    fn read_status(&mut self) -> Result<()> {
+        let mut status = [0u8];
+        self.stream.read_exact(&mut status)?;
+        match status[0] {
+            0 => Ok(()),
+            code => Err(Error::IoError(
+                *USB_ERRORS
+                    .get(code as usize - 1)
+                    .ok_or(Error::MalformedResponse)?,
+            )),
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn command(&mut self, command: u8, _timeout: Duration) -> Result<()> {
+        self.stream.write_all(&[COMMAND, command])?;
+        self.read_status()
+    }
+
+    fn write(&mut self, data: &[u8], _timeout: Duration) -> Result<usize> {
+        let mut request = vec![WRITE];
+        request.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        request.extend_from_slice(data);
+        self.stream.write_all(&request)?;
+        self.read_status()?;
+        Ok(read_u32(&mut self.stream)? as usize)
+    }
+
+    fn read(&mut self, buffer: &mut [u8], _timeout: Duration) -> Result<usize> {
+        let mut request = vec![READ];
+        request.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+        self.stream.write_all(&request)?;
+        self.read_status()?;
+        let length = read_u32(&mut self.stream)? as usize;
+        if length > buffer.len() {
+            return Err(Error::MalformedResponse);
+        }
+        self.stream.read_exact(&mut buffer[..length])?;
+        Ok(length)
+    }
+}
+
+/// Serves a target to [`TcpTransport`] clients connecting to the listener.
+///
+/// Clients are served one after another until they disconnect. Errors on a connection only end
+/// that connection; this function only returns if accepting a connection fails.
+///
+/// Every client gets full access to the target, as the protocol has no access control. Bind the
+/// listener to a loopback address unless the network is trusted, see the [module
+/// documentation](index.html#security).
+///
+/// [`TcpTransport`]: struct.TcpTransport.html
+pub fn serve<T: Transport>(listener: &TcpListener, handle: &mut TargetHandle<T>) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept()?;
+        let _ = serve_connection(stream, handle);
+    }
+}
+
+/// Handles requests from a single client until it disconnects.
+fn serve_connection<T: Transport>(
+    mut stream: TcpStream,
+    handle: &mut TargetHandle<T>,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+
+    let mut hello = Vec::new();
+    hello.extend_from_slice(&handle.in_buffer_length.to_le_bytes());
+    hello.extend_from_slice(&handle.out_buffer_length.to_le_bytes());
+    hello.extend_from_slice(&(handle.serial.len() as u16).to_le_bytes());
+    hello.extend_from_slice(handle.serial.as_bytes());
+    stream.write_all(&hello)?;
+
+    let timeouts = Timeouts::default();
+    let transport = &mut handle.transport;
+    loop {
+        let mut message_type = [0u8];
+        if stream.read(&mut message_type)? == 0 {
+            // Client disconnected
+            return Ok(());
+        }
+
+        let mut response = Vec::new();
+        match message_type[0] {
+            COMMAND => {
+                let mut command = [0u8];
+                stream.read_exact(&mut command)?;
+                push_status(
+                    &mut response,
+                    transport.command(command[0], timeouts.control),
+                );
+            }
+            WRITE => {
+                let mut data = vec![0u8; read_length(&mut stream)?];
+                stream.read_exact(&mut data)?;
+                if let Some(written) = push_status(
+                    &mut response,
+                    transport.write(&data, timeouts.write_timeout(data.len())),
+                ) {
+                    response.extend_from_slice(&(written as u32).to_le_bytes());
+                }
+            }
+            READ => {
+                let mut buffer = vec![0u8; read_length(&mut stream)?];
+                let timeout = timeouts.read_timeout(buffer.len());
+                if let Some(read) = push_status(&mut response, transport.read(&mut buffer, timeout))
+                {
+                    response.extend_from_slice(&(read as u32).to_le_bytes());
+                    response.extend_from_slice(&buffer[..read]);
+                }
+            }
+            _ => return Err(Error::MalformedResponse),
+        }
+        stream.write_all(&response)?;
+    }
+}
+
+/// Appends the status byte for a result to a response. Returns the result's value if successful.
+fn push_status<R>(response: &mut Vec<u8>, result: Result<R>) -> Option<R> {
+    match result {
+        Ok(value) => {
+            response.push(0);
+            Some(value)
+        }
+        Err(error) => {
+            let usb_error = match error {
+                Error::IoError(usb_error) => usb_error,
+                _ => rusb::Error::Other,
+            };
+            let index = USB_ERRORS.iter().position(|e| *e == usb_error).unwrap();
+            response.push(index as u8 + 1);
+            None
+        }
+    }
+}
+
+/// Reads a transfer length from a request and checks it for plausibility.
+fn read_length(stream: &mut TcpStream) -> Result<usize> {
+    let length = read_u32(stream)? as usize;
+    if length > MAX_TRANSFER_LENGTH {
+        return Err(Error::MalformedResponse);
+    }
+    Ok(length)
+}
+
+fn read_u16(stream: &mut TcpStream) -> Result<u16> {
+    let mut bytes = [0u8; 2];
+    stream.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(stream: &mut TcpStream) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
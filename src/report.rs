@@ -0,0 +1,220 @@
+//! Contains a record of the steps carried out during a flashing session, which can be exported for
+//! consumption by other tools.
+
+use crate::bootloader_info::BootloaderInfo;
+use crate::error::{Error, Result};
+use std::fmt::Write as _;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// A single step of a session, e.g. an erase or a verification.
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// Name of the step as it appears in reports.
+    pub name: String,
+
+    /// Time it took to carry out the step.
+    pub duration: Duration,
+
+    /// CRC32 associated with the step, e.g. the checksum reported by the target during a
+    /// verification.
+    pub crc: Option<u32>,
+
+    /// The error the step failed with, if any.
+    pub error: Option<Error>,
+}
+
+/// A record of all steps carried out with one target.
+///
+/// A report can be filled by a [`FlashSession`] via [`FlashSession::report`], built from the
+/// results of a plan with [`PlanReport::reports`], or assembled step by step with [`record`].
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::Report;
+///
+/// let mut report = Report::new("3976A3653232");
+/// report.record("erase", || Ok(())).unwrap();
+/// report.record_crc("verify", || Ok(0x1234_5678)).unwrap();
+///
+/// let xml = report.to_junit_xml();
+/// assert!(xml.contains(r#"<testsuite name="punt 3976A3653232" tests="2" failures="0""#));
+/// assert!(xml.contains(r#"<property name="crc" value="0x12345678"/>"#));
+/// ```
+///
+/// [`FlashSession`]: struct.FlashSession.html
+/// [`FlashSession::report`]: struct.FlashSession.html#method.report
+/// [`PlanReport::reports`]: plan/struct.PlanReport.html#method.reports
+/// [`record`]: #method.record
+#[derive(Debug, Clone)]
+pub struct Report {
+    serial: String,
+    bootloader: Option<(String, String)>,
+    steps: Vec<Step>,
+}
+
+impl Report {
+    /// Creates an empty report for the target with the given serial number.
+    pub fn new(serial: &str) -> Self {
+        Self {
+            serial: serial.to_string(),
+            bootloader: None,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Adds the target's bootloader identifier and version to the report.
+    pub fn set_bootloader_info(&mut self, info: &BootloaderInfo) {
+        self.bootloader = Some((info.identifier.clone(), info.version.to_string()));
+    }
+
+    /// Returns all steps recorded so far.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Adds a step which has already been carried out.
+    pub fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    /// Carries out a step, recording its duration and outcome. The step's result is passed through
+    /// to the caller.
+    pub fn record<R>(&mut self, name: &str, step: impl FnOnce() -> Result<R>) -> Result<R> {
+        let start = Instant::now();
+        let result = step();
+        self.steps.push(Step {
+            name: name.to_string(),
+            duration: start.elapsed(),
+            crc: None,
+            error: result.as_ref().err().cloned(),
+        });
+        result
+    }
+
+    /// Like [`record`], but for steps producing a CRC32 which is added to the report.
+    ///
+    /// [`record`]: #method.record
+    pub fn record_crc(&mut self, name: &str, step: impl FnOnce() -> Result<u32>) -> Result<u32> {
+        let result = self.record(name, step);
+        if let (Ok(crc), Some(step)) = (&result, self.steps.last_mut()) {
+            step.crc = Some(*crc);
+        }
+        result
+    }
+
+    /// Formats the report as a JUnit-style XML document with one test case per step.
+    pub fn to_junit_xml(&self) -> String {
+        let failures = self.steps.iter().filter(|s| s.error.is_some()).count();
+        let total: Duration = self.steps.iter().map(|s| s.duration).sum();
+        let serial = escape(&self.serial);
+
+        // Writing to a String cannot fail
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"punt {}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">",
+            serial,
+            self.steps.len(),
+            failures,
+            total.as_secs_f64()
+        );
+
+        xml.push_str("    <properties>\n");
+        let _ = writeln!(
+            xml,
+            "      <property name=\"serial\" value=\"{}\"/>",
+            serial
+        );
+        if let Some((identifier, version)) = &self.bootloader {
+            let _ = writeln!(
+                xml,
+                "      <property name=\"bootloader.identifier\" value=\"{}\"/>",
+                escape(identifier)
+            );
+            let _ = writeln!(
+                xml,
+                "      <property name=\"bootloader.version\" value=\"{}\"/>",
+                escape(version)
+            );
+        }
+        xml.push_str("    </properties>\n");
+
+        for step in &self.steps {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"punt.{}\" time=\"{:.3}\"",
+                escape(&step.name),
+                serial,
+                step.duration.as_secs_f64()
+            );
+            if step.crc.is_none() && step.error.is_none() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+            if let Some(crc) = step.crc {
+                let _ = writeln!(
+                    xml,
+                    "      <properties>\n        <property name=\"crc\" value=\"0x{:08x}\"/>\n      </properties>",
+                    crc
+                );
+            }
+            if let Some(error) = &step.error {
+                let _ = writeln!(
+                    xml,
+                    "      <failure message=\"{}\"/>",
+                    escape(&error.to_string())
+                );
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+
+    /// Writes the report as a JUnit-style XML document. See [`to_junit_xml`] for details.
+    ///
+    /// [`to_junit_xml`]: #method.to_junit_xml
+    pub fn write_junit_xml(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(self.to_junit_xml().as_bytes())
+    }
+}
+
+/// Escapes a string for use in XML text and attribute values. Tabs and line breaks are encoded so
+/// that they survive attribute value normalisation. Other control characters cannot be represented
+/// in XML 1.0 at all and are removed.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' => escaped.push_str("&#9;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_encodes_line_breaks_and_drops_control_characters() {
+        assert_eq!(
+            escape("a\tb\r\nc\u{0}d\u{1b}[0m<&>"),
+            "a&#9;b&#13;&#10;cd[0m&lt;&amp;&gt;"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::error::Error;
+use std::time::Duration;
+
+/// Determines how a [`TargetHandle`] repeats commands which failed with a transient USB error.
+///
+/// Each bootloader command is retried on its own, so a single failed transfer on a flaky
+/// connection does not abort a long-running operation. Reads, CRC requests and page erases can be
+/// repeated without side effects. Program commands are never repeated: the bootloader may already
+/// have received part of the data, and flash halfwords which are not erased any more cannot be
+/// programmed again. Exiting the bootloader is never repeated either.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::RetryPolicy;
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target = MockTarget::new();
+/// target.inject_failures(&[rusb::Error::Timeout, rusb::Error::Pipe]);
+///
+/// let mut target_handle = target.open();
+/// target_handle.set_retry_policy(RetryPolicy::new(3));
+/// target_handle.bootloader_info()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`TargetHandle`]: struct.TargetHandle.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of times a failed command is repeated before giving up.
+    pub attempts: u32,
+
+    /// Delay before the first repetition.
+    pub backoff: Duration,
+
+    /// Factor by which the delay grows with every further repetition.
+    pub backoff_factor: u32,
+
+    /// USB errors after which a command is repeated. Other errors are returned immediately.
+    pub retryable: Vec<rusb::Error>,
+}
+
+impl RetryPolicy {
+    /// Repeats a command up to `attempts` times after timeouts, stalls, overflows, interrupted
+    /// transfers and general I/O errors. The delay starts at 10 ms and doubles with every
+    /// repetition.
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts,
+            backoff: Duration::from_millis(10),
+            backoff_factor: 2,
+            retryable: vec![
+                rusb::Error::Io,
+                rusb::Error::Timeout,
+                rusb::Error::Pipe,
+                rusb::Error::Overflow,
+                rusb::Error::Interrupted,
+            ],
+        }
+    }
+
+    /// Never repeats a command. This is the default.
+    pub fn none() -> Self {
+        Self::new(0)
+    }
+
+    /// Returns whether a command failing with the given error may be repeated.
+    pub(crate) fn is_retryable(&self, error: &Error) -> bool {
+        match error {
+            Error::IoError(error) => self.retryable.contains(error),
+            _ => false,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
@@ -0,0 +1,775 @@
+use crate::bootloader_info::BootloaderInfo;
+#[cfg(feature = "plan")]
+use crate::discovery::Discovery;
+use crate::error::{Error, Result};
+use crate::firmware::{FirmwareImage, FlashSummary};
+use crate::flash::{Region, PAGE_SIZE};
+use crate::operation::Operation;
+#[cfg(feature = "plan")]
+use crate::plan::{Plan, PlanImage};
+use crate::report::{Report, Step};
+use crate::target_handle::{check_binary_image, TargetHandle};
+use crate::transport::Transport;
+use std::time::Instant;
+
+/// High-level bootloader operations, independent of how the target is connected.
+///
+/// Unlike the corresponding methods of [`TargetHandle`], which return [`Operation`]s for progress
+/// feedback, these methods carry out the complete operation before returning. Code written
+/// against this trait runs unchanged on a real target and on any other implementation.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{Context, PuntSession, UsbContext};
+///
+/// fn update(session: &mut impl PuntSession, image: &[u8]) -> punt::Result<()> {
+///     let base = session.bootloader_info()?.application_base;
+///     session.erase_area(base, image.len())?;
+///     session.program_at(image, base)?;
+///     session.verify(image, base)?;
+///     session.exit_bootloader()
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let image = [0u8; 1024];
+/// let mut context = Context::new()?;
+/// update(&mut context.open_target(None)?, &image)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`TargetHandle`]: struct.TargetHandle.html
+/// [`Operation`]: trait.Operation.html
+pub trait PuntSession {
+    /// Queries bootloader information from the target.
+    fn bootloader_info(&mut self) -> Result<BootloaderInfo>;
+
+    /// Queries a CRC32 from the target for a given memory area.
+    fn read_crc(&mut self, start: u32, length: usize) -> Result<u32>;
+
+    /// Erases all pages overlapping the given flash area.
+    fn erase_area(&mut self, start: u32, length: usize) -> Result<()>;
+
+    /// Programs a buffer's contents into already erased flash at the given address.
+    fn program_at(&mut self, data: &[u8], address: u32) -> Result<()>;
+
+    /// Reads from the target's memory into a buffer.
+    fn read_at(&mut self, buffer: &mut [u8], address: u32) -> Result<()>;
+
+    /// Verifies the supplied buffer against the target's memory with a CRC32 check.
+    fn verify(&mut self, data: &[u8], address: u32) -> Result<()>;
+
+    /// Lets the target exit from the bootloader and start its application.
+    fn exit_bootloader(&mut self) -> Result<()>;
+}
+
+impl<T: Transport> PuntSession for TargetHandle<T> {
+    fn bootloader_info(&mut self) -> Result<BootloaderInfo> {
+        TargetHandle::bootloader_info(self)
+    }
+
+    fn read_crc(&mut self, start: u32, length: usize) -> Result<u32> {
+        TargetHandle::read_crc(self, start, length)
+    }
+
+    fn erase_area(&mut self, start: u32, length: usize) -> Result<()> {
+        TargetHandle::erase_area(self, start, length)?.execute()
+    }
+
+    fn program_at(&mut self, data: &[u8], address: u32) -> Result<()> {
+        TargetHandle::program_at(self, data, address)?.execute()
+    }
+
+    fn read_at(&mut self, buffer: &mut [u8], address: u32) -> Result<()> {
+        TargetHandle::read_at(self, buffer, address)?.execute()
+    }
+
+    fn verify(&mut self, data: &[u8], address: u32) -> Result<()> {
+        TargetHandle::verify(self, data, address)
+    }
+
+    fn exit_bootloader(&mut self) -> Result<()> {
+        TargetHandle::exit_bootloader(self)
+    }
+}
+
+/// Selects which flash pages a [`FlashSession`] erases before programming.
+///
+/// [`FlashSession`]: struct.FlashSession.html
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EraseStrategy {
+    /// Erase only the pages containing data of the image.
+    MinimalPages,
+
+    /// Erase the complete application flash, so no remains of a previous firmware are left.
+    FullApplication,
+
+    /// Do not erase at all. The flash has to be erased already.
+    Skip,
+}
+
+/// Selects how a [`FlashSession`] checks the flash contents after programming.
+///
+/// [`FlashSession`]: struct.FlashSession.html
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Verify {
+    /// Compare CRC32 checksums of every segment.
+    Crc,
+
+    /// Read back every segment and compare it byte by byte. This takes considerably longer than a
+    /// CRC check, but cannot miss a difference.
+    Readback,
+
+    /// Skip verification.
+    Skip,
+}
+
+/// The step a [`FlashSession`] is carrying out, as reported to its progress callback.
+///
+/// [`FlashSession`]: struct.FlashSession.html
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Phase {
+    /// Erasing pages. Progress is counted in pages.
+    Erase,
+
+    /// Programming flash. Progress is counted in bytes.
+    Program,
+
+    /// Verifying flash contents. Progress is counted in segments.
+    Verify,
+}
+
+/// Callback receiving the current phase, the progress within it and the phase's total.
+type ProgressCallback<'a> = Box<dyn FnMut(Phase, usize, usize) + 'a>;
+
+/// Times the steps of a session and adds them to a [`Report`], if one is set.
+///
+/// [`Report`]: struct.Report.html
+#[derive(Default)]
+struct StepRecorder<'a> {
+    report: Option<&'a mut Report>,
+    current: Option<(&'static str, Instant)>,
+    retry: u32,
+}
+
+impl StepRecorder<'_> {
+    /// Finishes the step in progress successfully and starts timing the next one.
+    fn begin(&mut self, name: &'static str) {
+        self.finish(None);
+        self.current = Some((name, Instant::now()));
+    }
+
+    /// Finishes the step in progress, if any, with the given error or successfully.
+    fn finish(&mut self, error: Option<&Error>) {
+        if let (Some(report), Some((name, start))) = (&mut self.report, self.current.take()) {
+            report.push(Step {
+                name: match self.retry {
+                    0 => name.to_string(),
+                    retry => format!("{} (retry {})", name, retry),
+                },
+                duration: start.elapsed(),
+                crc: None,
+                error: error.cloned(),
+            });
+        }
+    }
+}
+
+/// Extension points around the steps of a [`FlashSession`], set with [`FlashSession::hooks`].
+///
+/// Each method is called right before or after the corresponding step and has access to the
+/// target, so it can carry out additional commands, e.g. to write a provisioning record after
+/// verification. Returning an error stops the session and is passed on by [`FlashSession::run`].
+/// Hooks of steps which are skipped, e.g. with [`EraseStrategy::Skip`], are not called. All
+/// methods do nothing by default.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::{Error, FirmwareImage, FlashSession, Hooks, TargetHandle, Transport};
+///
+/// /// Refuses to flash targets with an unexpected identifier and stamps a build number.
+/// struct Production;
+///
+/// impl<T: Transport> Hooks<T> for Production {
+///     fn before_erase(&mut self, handle: &mut TargetHandle<T>) -> punt::Result<()> {
+///         if handle.bootloader_info()?.identifier != "MOCK" {
+///             return Err(Error::UnsupportedTarget);
+///         }
+///         Ok(())
+///     }
+///
+///     fn after_verify(&mut self, handle: &mut TargetHandle<T>) -> punt::Result<()> {
+///         handle.flash_bytes(&42u32.to_le_bytes(), 0x0800_fc00)?;
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 256]);
+/// FlashSession::new(&mut target_handle)
+///     .hooks(Production)
+///     .run(&image)?;
+/// assert_eq!(target_handle.transport().memory(0x0800_fc00, 4), &[42, 0, 0, 0]);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FlashSession`]: struct.FlashSession.html
+/// [`FlashSession::hooks`]: struct.FlashSession.html#method.hooks
+/// [`FlashSession::run`]: struct.FlashSession.html#method.run
+/// [`EraseStrategy::Skip`]: enum.EraseStrategy.html#variant.Skip
+pub trait Hooks<T: Transport> {
+    /// Called before erasing.
+    fn before_erase(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after erasing.
+    fn after_erase(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before programming.
+    fn before_program(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after programming.
+    fn after_program(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before verifying.
+    fn before_verify(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after verifying.
+    fn after_verify(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before leaving the bootloader.
+    fn before_exit(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after leaving the bootloader. The target has started its application by then, so
+    /// bootloader commands will fail.
+    fn after_exit(&mut self, _handle: &mut TargetHandle<T>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Declaratively configured process of flashing a firmware image.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{Context, EraseStrategy, FirmwareImage, FlashSession, UsbContext, Verify};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let image = FirmwareImage::from_ihex(&std::fs::read_to_string("firmware.hex")?)?;
+///
+/// let mut context = Context::new()?;
+/// let mut target_handle = context.open_target(None)?;
+///
+/// FlashSession::new(&mut target_handle)
+///     .erase(EraseStrategy::FullApplication)
+///     .verify(Verify::Crc)
+///     .retries(2)
+///     .on_progress(|phase, done, total| println!("{:?}: {}/{}", phase, done, total))
+///     .run(&image)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct FlashSession<'a, T: Transport> {
+    handle: &'a mut TargetHandle<T>,
+    erase: EraseStrategy,
+    verify: Verify,
+    retries: u32,
+    progress: Option<ProgressCallback<'a>>,
+    provisioning: Option<(Region, Vec<u8>)>,
+    hooks: Option<Box<dyn Hooks<T> + 'a>>,
+    exit: bool,
+    steps: StepRecorder<'a>,
+}
+
+impl<'a, T: Transport> FlashSession<'a, T> {
+    /// Creates a session for the given target. By default, only the pages needed for the image are
+    /// erased, the result is verified via CRC32, failures are not retried and the target stays in
+    /// the bootloader.
+    pub fn new(handle: &'a mut TargetHandle<T>) -> Self {
+        Self {
+            handle,
+            erase: EraseStrategy::MinimalPages,
+            verify: Verify::Crc,
+            retries: 0,
+            progress: None,
+            provisioning: None,
+            hooks: None,
+            exit: false,
+            steps: StepRecorder::default(),
+        }
+    }
+
+    /// Sets which pages are erased before programming.
+    pub fn erase(mut self, strategy: EraseStrategy) -> Self {
+        self.erase = strategy;
+        self
+    }
+
+    /// Sets how the flash contents are checked after programming.
+    pub fn verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Sets how many times the whole process is repeated after a failure. Only transient USB errors
+    /// and verification failures are retried; invalid requests fail immediately. Sessions using
+    /// [`EraseStrategy::Skip`] are never retried, as programming the partly written flash again
+    /// without erasing it cannot succeed.
+    ///
+    /// [`EraseStrategy::Skip`]: enum.EraseStrategy.html#variant.Skip
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets a callback which is called with the current phase, the progress within that phase and
+    /// the phase's total after every step.
+    pub fn on_progress(mut self, callback: impl FnMut(Phase, usize, usize) + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets per-device data to be written to a designated region after the image, usually
+    /// rendered from a [`ProvisioningLayout`]. The region is erased and programmed together with
+    /// the image and verified the same way, and is included in the progress of each phase. The
+    /// image must not overlap the region.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::provision::{parse_csv, FieldKind};
+    /// use punt::{Context, FirmwareImage, FlashSession, ProvisioningLayout, Region, UsbContext};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let image = FirmwareImage::from_ihex(&std::fs::read_to_string("firmware.hex")?)?;
+    /// let layout = ProvisioningLayout::new(Region::new(0x0800_fc00, 1024).unwrap())
+    ///     .field("serial", 0, FieldKind::Text(16))
+    ///     .field("calibration", 16, FieldKind::Bytes(32));
+    ///
+    /// let mut context = Context::new()?;
+    /// for row in parse_csv(&std::fs::read_to_string("devices.csv")?)? {
+    ///     println!("Connect device {} and press enter.", row["serial"]);
+    ///     std::io::stdin().read_line(&mut String::new())?;
+    ///
+    ///     let mut target_handle = context.open_target(None)?;
+    ///     FlashSession::new(&mut target_handle)
+    ///         .provision(layout.region().clone(), layout.render(&row)?)
+    ///         .run(&image)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ProvisioningLayout`]: provision/struct.ProvisioningLayout.html
+    pub fn provision(mut self, region: Region, data: Vec<u8>) -> Self {
+        self.provisioning = Some((region, data));
+        self
+    }
+
+    /// Sets callbacks to be run before and after each step. See [`Hooks`] for details.
+    ///
+    /// [`Hooks`]: trait.Hooks.html
+    pub fn hooks(mut self, hooks: impl Hooks<T> + 'a) -> Self {
+        self.hooks = Some(Box::new(hooks));
+        self
+    }
+
+    /// Sets whether the target leaves the bootloader and starts the application after the image
+    /// has been flashed successfully.
+    pub fn exit(mut self, exit: bool) -> Self {
+        self.exit = exit;
+        self
+    }
+
+    /// Records the steps of the session in a report, together with the target's bootloader
+    /// information. The steps are `check`, `erase`, `program`, `verify` and `exit`, each with its
+    /// duration and the error it failed with, if any. Skipped steps are left out and the steps of
+    /// repeated attempts are marked as retries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::{FirmwareImage, FlashSession, Report};
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 256]);
+    ///
+    /// let mut report = Report::new(target_handle.serial());
+    /// FlashSession::new(&mut target_handle)
+    ///     .report(&mut report)
+    ///     .run(&image)?;
+    /// let steps: Vec<_> = report.steps().iter().map(|step| step.name.as_str()).collect();
+    /// assert_eq!(steps, ["check", "erase", "program", "verify"]);
+    /// println!("{}", report.to_junit_xml());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn report(mut self, report: &'a mut Report) -> Self {
+        self.steps.report = Some(report);
+        self
+    }
+
+    /// Describes the session as a [`Plan`] flashing the given image files to this session's
+    /// target with the same erase, verification and exit settings. Saved as a manifest, it repeats
+    /// the session. Provisioning data, retries and hooks have no equivalent in a plan and are left
+    /// out.
+    ///
+    /// [`Plan`]: plan/struct.Plan.html
+    #[cfg(feature = "plan")]
+    pub fn to_plan(&self, images: Vec<PlanImage>) -> Plan {
+        Plan {
+            images,
+            targets: vec![Discovery {
+                serial: Some(self.handle.serial().to_string()),
+                ..Discovery::default()
+            }],
+            erase: self.erase,
+            verify: self.verify,
+            exit: self.exit,
+        }
+    }
+
+    /// Carries out the session for the given image.
+    pub fn run(mut self, image: &FirmwareImage) -> Result<FlashSummary> {
+        if let Some(report) = &mut self.steps.report {
+            if let Ok(info) = self.handle.bootloader_info() {
+                report.set_bootloader_info(&info);
+            }
+        }
+
+        let mut attempts_left = match self.erase {
+            EraseStrategy::Skip => 0,
+            _ => self.retries,
+        };
+        let summary = loop {
+            let result = self.attempt(image);
+            self.steps.finish(result.as_ref().err());
+            match result {
+                Err(error)
+                    if attempts_left > 0
+                        && (error.is_transient()
+                            || matches!(error, Error::VerificationError(_))) =>
+                {
+                    attempts_left -= 1;
+                    self.steps.retry += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        if self.exit {
+            self.steps.begin("exit");
+            let result = self.exit_bootloader();
+            self.steps.finish(result.as_ref().err());
+            result?;
+        }
+        Ok(summary)
+    }
+
+    /// Lets the target leave the bootloader, running the hooks around it.
+    fn exit_bootloader(&mut self) -> Result<()> {
+        if let Some(hooks) = &mut self.hooks {
+            hooks.before_exit(self.handle)?;
+        }
+        self.handle.exit_bootloader()?;
+        if let Some(hooks) = &mut self.hooks {
+            hooks.after_exit(self.handle)?;
+        }
+        Ok(())
+    }
+
+    /// Erases, programs and verifies the image once.
+    fn attempt(&mut self, image: &FirmwareImage) -> Result<FlashSummary> {
+        let start = Instant::now();
+        let progress = &mut self.progress;
+        let mut report = |phase, done, total| {
+            if let Some(callback) = progress {
+                callback(phase, done, total);
+            }
+        };
+
+        self.steps.begin("check");
+        check_binary_image(&self.handle.memory_map()?, image)?;
+
+        let provisioning = self.provisioning.as_ref();
+        if let Some((region, data)) = provisioning {
+            let overlaps = image.segments().iter().any(|segment| {
+                segment.address <= region.end()
+                    && segment.address as usize + segment.data.len() > region.start() as usize
+            });
+            if overlaps || data.len() > region.size() {
+                return Err(Error::InvalidRequest);
+            }
+        }
+
+        let hooks = &mut self.hooks;
+        if self.erase != EraseStrategy::Skip {
+            self.steps.begin("erase");
+            if let Some(hooks) = hooks.as_mut() {
+                hooks.before_erase(self.handle)?;
+            }
+        }
+        let pages_erased = match self.erase {
+            EraseStrategy::MinimalPages => {
+                let erase = self.handle.erase_image(image)?;
+                let image_pages = erase.total();
+                let provisioning_pages =
+                    provisioning.map_or(0, |(region, _)| region.size() / PAGE_SIZE as usize);
+                let total = image_pages + provisioning_pages;
+                run_operation(erase, |done, _| report(Phase::Erase, done, total))?;
+                if let Some((region, _)) = provisioning {
+                    let erase = self.handle.erase_area(region.start(), region.size())?;
+                    run_operation(erase, |done, _| {
+                        report(Phase::Erase, image_pages + done, total)
+                    })?;
+                }
+                total
+            }
+            EraseStrategy::FullApplication => {
+                let application = self.handle.memory_map()?.application().clone();
+                let erase = self
+                    .handle
+                    .erase_area(application.start(), application.size())?;
+                run_operation(erase, |done, total| report(Phase::Erase, done, total))?
+            }
+            EraseStrategy::Skip => 0,
+        };
+        if self.erase != EraseStrategy::Skip {
+            if let Some(hooks) = hooks.as_mut() {
+                hooks.after_erase(self.handle)?;
+            }
+        }
+
+        self.steps.begin("program");
+        if let Some(hooks) = hooks.as_mut() {
+            hooks.before_program(self.handle)?;
+        }
+        let program = self.handle.program_image(image)?;
+        let image_bytes = program.total();
+        let total = image_bytes + provisioning.map_or(0, |(_, data)| data.len());
+        run_operation(program, |done, _| report(Phase::Program, done, total))?;
+        if let Some((region, data)) = provisioning {
+            let program = self.handle.program_at(data, region.start())?;
+            run_operation(program, |done, _| {
+                report(Phase::Program, image_bytes + done, total)
+            })?;
+        }
+        let bytes_programmed = total;
+        if let Some(hooks) = hooks.as_mut() {
+            hooks.after_program(self.handle)?;
+        }
+
+        if self.verify != Verify::Skip {
+            self.steps.begin("verify");
+            if let Some(hooks) = hooks.as_mut() {
+                hooks.before_verify(self.handle)?;
+            }
+            let parts: Vec<(&[u8], u32)> = image
+                .segments()
+                .iter()
+                .map(|segment| (segment.data.as_slice(), segment.address))
+                .chain(provisioning.map(|(region, data)| (data.as_slice(), region.start())))
+                .collect();
+            for (i, &(data, address)) in parts.iter().enumerate() {
+                match self.verify {
+                    Verify::Readback => self.handle.verify_readback(data, address)?,
+                    _ => self.handle.verify(data, address)?,
+                }
+                report(Phase::Verify, i + 1, parts.len());
+            }
+            if let Some(hooks) = hooks.as_mut() {
+                hooks.after_verify(self.handle)?;
+            }
+        }
+
+        Ok(FlashSummary {
+            pages_erased,
+            bytes_programmed,
+            duration: start.elapsed(),
+        })
+    }
+}
+
+/// Runs an operation to completion, reporting progress after every step. Returns the operation's
+/// total.
+fn run_operation(
+    mut operation: impl Operation,
+    mut report: impl FnMut(usize, usize),
+) -> Result<usize> {
+    let total = operation.total();
+    for done in &mut operation {
+        report(done?, total);
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTarget;
+
+    #[test]
+    fn run_rejects_textual_image() {
+        let mut target_handle = MockTarget::new().open();
+        let base = target_handle.bootloader_info().unwrap().application_base;
+        let image = FirmwareImage::from_binary(
+            base,
+            b":100000000102030405060708090A0B0C0D0E0F1068\n".to_vec(),
+        );
+
+        let result = FlashSession::new(&mut target_handle).run(&image);
+        assert!(matches!(result, Err(Error::TextualImage)));
+        assert!(target_handle.transport().is_erased(base, 1024));
+    }
+
+    /// Records the hooks called and optionally vetoes programming.
+    struct Recorder<'r> {
+        calls: &'r mut Vec<&'static str>,
+        veto_program: bool,
+    }
+
+    impl<T: Transport> Hooks<T> for Recorder<'_> {
+        fn before_erase(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("before_erase");
+            Ok(())
+        }
+
+        fn after_erase(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("after_erase");
+            Ok(())
+        }
+
+        fn before_program(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("before_program");
+            if self.veto_program {
+                return Err(Error::InvalidRequest);
+            }
+            Ok(())
+        }
+
+        fn after_program(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("after_program");
+            Ok(())
+        }
+
+        fn before_verify(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("before_verify");
+            Ok(())
+        }
+
+        fn after_verify(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("after_verify");
+            Ok(())
+        }
+
+        fn before_exit(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("before_exit");
+            Ok(())
+        }
+
+        fn after_exit(&mut self, _: &mut TargetHandle<T>) -> Result<()> {
+            self.calls.push("after_exit");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hooks_run_around_each_step() {
+        let mut target_handle = MockTarget::new().open();
+        let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 256]);
+        let mut calls = Vec::new();
+
+        FlashSession::new(&mut target_handle)
+            .hooks(Recorder {
+                calls: &mut calls,
+                veto_program: false,
+            })
+            .exit(true)
+            .run(&image)
+            .unwrap();
+        assert_eq!(
+            calls,
+            [
+                "before_erase",
+                "after_erase",
+                "before_program",
+                "after_program",
+                "before_verify",
+                "after_verify",
+                "before_exit",
+                "after_exit"
+            ]
+        );
+        assert!(target_handle.transport().has_exited());
+    }
+
+    #[test]
+    fn hooks_can_veto_a_step() {
+        let mut target_handle = MockTarget::new().open();
+        let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 256]);
+        let mut calls = Vec::new();
+
+        let result = FlashSession::new(&mut target_handle)
+            .erase(EraseStrategy::Skip)
+            .hooks(Recorder {
+                calls: &mut calls,
+                veto_program: true,
+            })
+            .run(&image);
+        assert!(matches!(result, Err(Error::InvalidRequest)));
+        assert_eq!(calls, ["before_program"]);
+        assert!(target_handle.transport().is_erased(0x0800_1000, 256));
+    }
+
+    #[test]
+    fn report_records_the_failed_step() {
+        let mut target_handle = MockTarget::new().open();
+        let image = FirmwareImage::from_binary(0x0800_1000, vec![0x55; 256]);
+        let mut report = Report::new(target_handle.serial());
+        let mut calls = Vec::new();
+
+        let result = FlashSession::new(&mut target_handle)
+            .erase(EraseStrategy::Skip)
+            .hooks(Recorder {
+                calls: &mut calls,
+                veto_program: true,
+            })
+            .report(&mut report)
+            .run(&image);
+        assert!(result.is_err());
+        let steps: Vec<_> = report
+            .steps()
+            .iter()
+            .map(|step| (step.name.as_str(), step.error.clone()))
+            .collect();
+        assert_eq!(
+            steps,
+            [("check", None), ("program", Some(Error::InvalidRequest))]
+        );
+        assert!(report
+            .to_junit_xml()
+            .contains(r#"<property name="bootloader.identifier" value="MOCK"/>"#));
+    }
+}
@@ -0,0 +1,246 @@
+//! Contains the dual-slot (A/B) update strategy.
+
+use crate::error::{Error, Result};
+use crate::flash::Region;
+use crate::operation::Operation;
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use std::convert::TryInto;
+use std::fmt::{self, Display, Formatter};
+
+/// Marks a programmed boot configuration, `"PUNT"` in ASCII.
+const BOOT_CONFIG_MAGIC: u32 = 0x544e_5550;
+
+/// Size of the boot configuration in bytes.
+const BOOT_CONFIG_SIZE: usize = 12;
+
+/// Value of the pending slot word if no swap is pending.
+const NO_SLOT: u32 = 0xffff_ffff;
+
+/// One of the two application slots of a [`DualSlot`] layout.
+///
+/// [`DualSlot`]: struct.DualSlot.html
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Slot {
+    /// The first slot, active on a target without a boot configuration.
+    A,
+
+    /// The second slot.
+    B,
+}
+
+impl Slot {
+    /// Returns the respective other slot.
+    pub fn other(self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+
+    /// Returns the number identifying the slot in the boot configuration.
+    fn index(self) -> u32 {
+        match self {
+            Slot::A => 0,
+            Slot::B => 1,
+        }
+    }
+
+    /// Returns the slot with the given number, if valid.
+    fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(Slot::A),
+            1 => Some(Slot::B),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Slot {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Slot::A => fmt.write_str("A"),
+            Slot::B => fmt.write_str("B"),
+        }
+    }
+}
+
+/// Boot configuration selecting the slot an application-side boot stage starts.
+///
+/// In flash, the configuration consists of three 32-bit little-endian words at the start of the
+/// configuration region: the magic number `0x544e5550` (`"PUNT"`), the active slot (0 for A, 1
+/// for B) and the slot to swap to on the next boot (`0xffffffff` if none). An erased region
+/// stands for slot A being active without a pending swap.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BootConfig {
+    /// The slot currently booted.
+    pub active: Slot,
+
+    /// The slot to boot next, if a swap has been requested.
+    pub pending: Option<Slot>,
+}
+
+impl BootConfig {
+    /// Reads a configuration as stored in flash. Returns `None` if it is neither erased nor valid.
+    fn from_bytes(bytes: &[u8; BOOT_CONFIG_SIZE]) -> Option<Self> {
+        if bytes.iter().all(|&b| b == 0xff) {
+            return Some(Self {
+                active: Slot::A,
+                pending: None,
+            });
+        }
+
+        let word = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        if word(0) != BOOT_CONFIG_MAGIC {
+            return None;
+        }
+        Some(Self {
+            active: Slot::from_index(word(4))?,
+            pending: match word(8) {
+                NO_SLOT => None,
+                index => Some(Slot::from_index(index)?),
+            },
+        })
+    }
+
+    /// Returns the configuration as stored in flash.
+    fn to_bytes(self) -> [u8; BOOT_CONFIG_SIZE] {
+        let mut bytes = [0; BOOT_CONFIG_SIZE];
+        bytes[0..4].copy_from_slice(&BOOT_CONFIG_MAGIC.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.active.index().to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.pending.map_or(NO_SLOT, Slot::index).to_le_bytes());
+        bytes
+    }
+}
+
+/// Flash layout with two application slots and a boot configuration page, for updates which
+/// leave the running firmware intact until the new one is complete.
+///
+/// An update with [`update`] writes the image to the inactive slot, verifies it and only then
+/// records a pending swap in the boot configuration (see [`BootConfig`] for its format). Booting
+/// the pending slot and making it the active one is up to a boot stage in the application, which
+/// can fall back to the previous slot if the new firmware does not come up.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+/// use punt::slots::{DualSlot, Slot};
+/// use punt::Region;
+///
+/// # fn main() -> punt::Result<()> {
+/// let layout = DualSlot::new(
+///     Region::new(0x0800_2000, 0x6000).unwrap(),
+///     Region::new(0x0800_8000, 0x6000).unwrap(),
+///     Region::new(0x0800_1000, 0x400).unwrap(),
+/// )
+/// .unwrap();
+///
+/// let mut target_handle = MockTarget::new().open();
+/// assert_eq!(layout.boot_config(&mut target_handle)?.active, Slot::A);
+///
+/// let slot = layout.update(&mut target_handle, &[0x5a; 2048])?;
+/// assert_eq!(slot, Slot::B);
+/// assert_eq!(layout.boot_config(&mut target_handle)?.pending, Some(Slot::B));
+///
+/// target_handle.exit_bootloader()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`update`]: #method.update
+/// [`BootConfig`]: struct.BootConfig.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DualSlot {
+    slot_a: Region,
+    slot_b: Region,
+    config: Region,
+}
+
+impl DualSlot {
+    /// Declares a layout from the regions of both slots and the boot configuration. Returns `None`
+    /// if any of the regions overlap.
+    pub fn new(slot_a: Region, slot_b: Region, config: Region) -> Option<Self> {
+        let disjoint = |a: &Region, b: &Region| a.end() < b.start() || b.end() < a.start();
+        if disjoint(&slot_a, &slot_b) && disjoint(&slot_a, &config) && disjoint(&slot_b, &config) {
+            Some(Self {
+                slot_a,
+                slot_b,
+                config,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the region of the given slot.
+    pub fn region(&self, slot: Slot) -> &Region {
+        match slot {
+            Slot::A => &self.slot_a,
+            Slot::B => &self.slot_b,
+        }
+    }
+
+    /// Returns the region holding the boot configuration.
+    pub fn config_region(&self) -> &Region {
+        &self.config
+    }
+
+    /// Reads the boot configuration from the target. Returns [`Error::MalformedResponse`] if the
+    /// configuration region holds neither a valid configuration nor erased flash.
+    ///
+    /// [`Error::MalformedResponse`]: ../enum.Error.html#variant.MalformedResponse
+    pub fn boot_config<T: Transport>(&self, handle: &mut TargetHandle<T>) -> Result<BootConfig> {
+        let mut bytes = [0; BOOT_CONFIG_SIZE];
+        handle.read_at(&mut bytes, self.config.start())?.execute()?;
+        BootConfig::from_bytes(&bytes).ok_or(Error::MalformedResponse)
+    }
+
+    /// Writes a boot configuration to the target, erasing the configuration region first.
+    pub fn set_boot_config<T: Transport>(
+        &self,
+        handle: &mut TargetHandle<T>,
+        config: BootConfig,
+    ) -> Result<()> {
+        let bytes = config.to_bytes();
+        handle
+            .erase_area(self.config.start(), self.config.size())?
+            .execute()?;
+        handle.program_at(&bytes, self.config.start())?.execute()?;
+        handle.verify(&bytes, self.config.start())
+    }
+
+    /// Flashes an image to the slot not currently active, verifies it and marks it as pending, so
+    /// the application's boot stage swaps to it on the next start. Returns the slot written to.
+    ///
+    /// The image is raw binary data placed at the start of the slot. Returns
+    /// [`Error::InvalidRequest`] if it does not fit. The boot configuration is only changed once
+    /// the slot has been verified, so a failed update leaves the target booting the previous
+    /// firmware. Call [`TargetHandle::exit_bootloader`] afterwards to boot it.
+    ///
+    /// [`Error::InvalidRequest`]: ../enum.Error.html#variant.InvalidRequest
+    /// [`TargetHandle::exit_bootloader`]: ../struct.TargetHandle.html#method.exit_bootloader
+    pub fn update<T: Transport>(&self, handle: &mut TargetHandle<T>, image: &[u8]) -> Result<Slot> {
+        let config = self.boot_config(handle)?;
+        let slot = config.active.other();
+        let region = self.region(slot);
+        if image.len() > region.size() {
+            return Err(Error::InvalidRequest);
+        }
+
+        handle.erase_area(region.start(), image.len())?.execute()?;
+        handle.program_at(image, region.start())?.execute()?;
+        handle.verify(image, region.start())?;
+
+        self.set_boot_config(
+            handle,
+            BootConfig {
+                active: config.active,
+                pending: Some(slot),
+            },
+        )?;
+        Ok(slot)
+    }
+}
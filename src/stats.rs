@@ -0,0 +1,66 @@
+//! Contains statistics about the communication with a target.
+
+use std::time::Duration;
+
+/// Transfer statistics of a [`TargetHandle`], as returned by [`TargetHandle::stats`].
+///
+/// All values are accumulated since the handle was opened or [`TargetHandle::reset_stats`] was
+/// last called.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::mock::MockTarget;
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut target_handle = MockTarget::new().open();
+/// target_handle.flash_bytes(&[0x55; 4096], 0x0800_1000)?;
+///
+/// let stats = target_handle.stats();
+/// assert!(stats.bytes_written >= 4096);
+/// println!(
+///     "{} commands, {:.0} B/s",
+///     stats.commands,
+///     stats.throughput().unwrap_or(0.0)
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`TargetHandle`]: struct.TargetHandle.html
+/// [`TargetHandle::stats`]: struct.TargetHandle.html#method.stats
+/// [`TargetHandle::reset_stats`]: struct.TargetHandle.html#method.reset_stats
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransferStats {
+    /// Number of commands sent, not counting repetitions.
+    pub commands: u64,
+
+    /// Number of commands which failed after all repetitions.
+    pub failed_commands: u64,
+
+    /// Number of times a command was repeated after a transient error.
+    pub retries: u64,
+
+    /// Number of bytes written to the target, excluding the command codes.
+    pub bytes_written: u64,
+
+    /// Number of bytes read from the target.
+    pub bytes_read: u64,
+
+    /// Time spent on commands, including the delays between repetitions.
+    pub time: Duration,
+}
+
+impl TransferStats {
+    /// Returns the average number of bytes transferred in both directions per second, or `None`
+    /// if no time has been spent on commands yet.
+    pub fn throughput(&self) -> Option<f64> {
+        let seconds = self.time.as_secs_f64();
+        if seconds > 0.0 {
+            Some((self.bytes_written + self.bytes_read) as f64 / seconds)
+        } else {
+            None
+        }
+    }
+}
@@ -1,10 +1,51 @@
+use crate::bootloader_info::BootloaderInfo;
 use crate::context::UsbContext;
 use crate::error::{Error, Result};
 use crate::target_handle::TargetHandle;
 use crate::TIMEOUT;
-use rusb::Device;
+use rusb::{Device, DeviceHandle};
 use std::convert::TryFrom;
 
+// Constants used to identify the device. The shared VID:PID pair used here mandates a check for
+// the manufacturer and product strings
+const VENDOR_STRING: &str = "25120";
+const PRODUCT_STRING: &str = "punt";
+const VENDOR_ID: u16 = 0x16c0;
+const PRODUCT_ID: u16 = 0x05dc;
+
+/// USB descriptor values by which devices running a punt bootloader are recognised.
+///
+/// The default is the identity of the upstream bootloader. Bootloaders re-branded with a USB
+/// identity of their own can be used via a [`CustomContext`]. Since the default VID:PID pair is
+/// shared by many devices, the manufacturer and product strings are checked as well.
+///
+/// [`CustomContext`]: struct.CustomContext.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DeviceIdentity {
+    /// USB vendor ID.
+    pub vendor_id: u16,
+
+    /// USB product ID.
+    pub product_id: u16,
+
+    /// Manufacturer string descriptor.
+    pub manufacturer: String,
+
+    /// Product string descriptor.
+    pub product: String,
+}
+
+impl Default for DeviceIdentity {
+    fn default() -> Self {
+        Self {
+            vendor_id: VENDOR_ID,
+            product_id: PRODUCT_ID,
+            manufacturer: VENDOR_STRING.to_string(),
+            product: PRODUCT_STRING.to_string(),
+        }
+    }
+}
+
 /// Contains necessary information to connect to a target via USB.
 pub struct Target<T: UsbContext> {
     /// USB device for the low-level communication
@@ -12,6 +53,47 @@ pub struct Target<T: UsbContext> {
 }
 
 impl<T: UsbContext> Target<T> {
+    /// Returns the number of the USB bus the target is connected to.
+    pub fn bus_number(&self) -> u8 {
+        self.usb_device.bus_number()
+    }
+
+    /// Returns the target's address on its bus. Unlike the port, the address changes whenever the
+    /// target re-enumerates, e.g. after leaving the bootloader.
+    pub fn address(&self) -> u8 {
+        self.usb_device.address()
+    }
+
+    /// Returns the numbers of the hub ports between the root hub and the target, starting at the
+    /// root hub. They identify the physical slot a target is plugged into, which stays the same
+    /// across re-enumerations and does not depend on the serial number.
+    pub fn port_numbers(&self) -> Result<Vec<u8>> {
+        Ok(self.usb_device.port_numbers()?)
+    }
+
+    /// Returns the physical location of the target in the form Linux uses for USB devices, i.e.
+    /// the bus number followed by the dot-separated port numbers, e.g. `1-4.2` for port 2 of a
+    /// hub plugged into port 4 of the root hub of bus 1. None of this requires opening the target.
+    pub fn port_path(&self) -> Result<String> {
+        Ok(port_path(self.bus_number(), &self.port_numbers()?))
+    }
+
+    /// Collects everything known about the target into a record. The serial number and bootloader
+    /// information are left out if they cannot be read, e.g. because another program has the
+    /// target opened.
+    pub fn record(&self) -> TargetRecord {
+        TargetRecord {
+            serial: self.serial().ok(),
+            bus_number: self.bus_number(),
+            address: self.address(),
+            port_numbers: self.port_numbers().unwrap_or_default(),
+            bootloader_info: self
+                .open()
+                .and_then(|mut handle| handle.bootloader_info())
+                .ok(),
+        }
+    }
+
     /// Returns the serial number string the target reports via its USB descriptor.
     pub fn serial(&self) -> Result<String> {
         let device_handle = self.usb_device.open()?;
@@ -23,8 +105,11 @@ impl<T: UsbContext> Target<T> {
         Ok(device_handle.read_serial_number_string(language, &device_desc, TIMEOUT)?)
     }
 
-    /// Connects to a target. Fails when errors occurr during USB communication.
-    pub fn open(&self) -> Result<TargetHandle<T>> {
+    /// Connects to a target. Fails when errors occurr during USB communication, or with
+    /// [`Error::IncompatibleBootloader`] if the bootloader's version is not supported.
+    ///
+    /// [`Error::IncompatibleBootloader`]: enum.Error.html#variant.IncompatibleBootloader
+    pub fn open(&self) -> Result<TargetHandle<DeviceHandle<T>>> {
         // Fetch endpoint sizes
         let config_descriptor = self.usb_device.active_config_descriptor()?;
         let interface_descriptor = config_descriptor
@@ -48,12 +133,15 @@ impl<T: UsbContext> Target<T> {
         let mut device_handle = self.usb_device.open()?;
         device_handle.reset()?;
 
-        Ok(TargetHandle {
-            usb_device_handle: device_handle,
+        let mut target_handle = TargetHandle::new(
+            device_handle,
             in_buffer_length,
             out_buffer_length,
-            serial: self.serial()?,
-        })
+            self.serial()?,
+        );
+        // Fail early for bootloaders speaking an incompatible protocol
+        target_handle.bootloader_info()?;
+        Ok(target_handle)
     }
 }
 
@@ -65,16 +153,12 @@ impl<T: UsbContext> TryFrom<rusb::Device<T>> for Target<T> {
     ///
     /// [`Err(Error::UnsupportedTarget)`]: enum.Error.html#variant.UnsupportedTarget
     fn try_from(device: Device<T>) -> Result<Target<T>> {
-        // Constants used to identify the device. The shared VID:PID pair used here
-        // mandates a check for the manufacturer and product strings
-        const VENDOR_STRING: &str = "25120";
-        const PRODUCT_STRING: &str = "punt";
-        const VENDOR_ID: u16 = 0x16c0;
-        const PRODUCT_ID: u16 = 0x05dc;
-
         let device_desc = device.device_descriptor()?;
+        let identity = device.context().device_identity();
 
-        if device_desc.vendor_id() != VENDOR_ID || device_desc.product_id() != PRODUCT_ID {
+        if device_desc.vendor_id() != identity.vendor_id
+            || device_desc.product_id() != identity.product_id
+        {
             return Err(Error::UnsupportedTarget);
         }
 
@@ -87,10 +171,41 @@ impl<T: UsbContext> TryFrom<rusb::Device<T>> for Target<T> {
             device_handle.read_manufacturer_string(language, &device_desc, TIMEOUT)?;
         let product_string = device_handle.read_product_string(language, &device_desc, TIMEOUT)?;
 
-        if vendor_string != VENDOR_STRING || product_string != PRODUCT_STRING {
+        if vendor_string != identity.manufacturer || product_string != identity.product {
             return Err(Error::UnsupportedTarget);
         }
 
         Ok(Target { usb_device: device })
     }
 }
+
+/// Formats a bus number and hub port chain like `1-4.2`.
+pub(crate) fn port_path(bus_number: u8, port_numbers: &[u8]) -> String {
+    let ports: Vec<String> = port_numbers.iter().map(u8::to_string).collect();
+    format!("{}-{}", bus_number, ports.join("."))
+}
+
+/// Description of a connected target, as returned by [`UsbContext::list_targets`].
+///
+/// With the `serde` feature, records can be serialized, e.g. to JSON for scripts taking an
+/// inventory of the connected boards.
+///
+/// [`UsbContext::list_targets`]: trait.UsbContext.html#method.list_targets
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TargetRecord {
+    /// Serial number string of the target, if it could be read.
+    pub serial: Option<String>,
+
+    /// Number of the USB bus the target is connected to.
+    pub bus_number: u8,
+
+    /// Address of the target on its bus. It changes whenever the target re-enumerates.
+    pub address: u8,
+
+    /// Numbers of the hub ports between the root hub and the target, starting at the root hub.
+    pub port_numbers: Vec<u8>,
+
+    /// Information reported by the bootloader, if the target could be opened.
+    pub bootloader_info: Option<BootloaderInfo>,
+}
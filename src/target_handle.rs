@@ -1,12 +1,34 @@
-use crate::bootloader_info::{BootloaderInfo, Version};
+use crate::bootloader_info::{BootloaderInfo, OptionBytes, UniqueId, Version};
+use crate::cache::ReadCache;
+use crate::chunk_size::{AdaptiveChunkSizes, ChunkSize};
 use crate::context::UsbContext;
-use crate::error::{Error, Result};
-use crate::flash::Page;
-use crate::operation::{Erase, Program, Read};
+use crate::crc::Checksum;
+use crate::error::{CrcMismatch, Error, Result};
+use crate::firmware::{FirmwareImage, FlashSummary, Preflight, Segment};
+use crate::flash::{MemoryMap, Padding, Page, Region, FLASH_BASE, PAGE_SIZE};
+use crate::hotplug::POLL_INTERVAL;
+use crate::inhibit::SleepInhibitor;
+use crate::keep_alive::KeepAlive;
+use crate::manifest::CrcManifest;
+use crate::memory::TargetMemory;
+use crate::operation::{Dump, Erase, Operation, Program, Read, Update, Verification};
+use crate::retry::RetryPolicy;
+use crate::session::Verify;
+use crate::stats::TransferStats;
+use crate::trace::HexDump;
+use crate::transport::{Timeouts, Transport};
 use crate::TIMEOUT;
-use crc_any::CRC;
 use rusb::DeviceHandle;
+use std::borrow::Cow;
 use std::convert::{TryFrom, TryInto};
+use std::io::Write;
+use std::ops::Range;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Largest memory area covered by a single CRC request. The target computes the CRC before
+/// answering, so requests for larger areas risk running into the USB timeout.
+pub const MAX_CRC_LENGTH: usize = 0x8000;
 
 /// Splits the first four bytes of a slice off and interpret them as a little-endian u32.
 fn read_ne_u32(input: &mut &[u8]) -> u32 {
@@ -26,10 +48,19 @@ fn read_version(input: &mut &[u8]) -> Version {
     }
 }
 
+/// Typical time the target takes to erase a flash page, used for estimates.
+const ESTIMATED_ERASE_TIME: Duration = Duration::from_millis(25);
+
+/// Typical time the target takes to program a flash halfword, used for estimates.
+const ESTIMATED_HALFWORD_TIME: Duration = Duration::from_micros(60);
+
+/// Typical time a command takes on top of the flash operation itself, used for estimates.
+const ESTIMATED_COMMAND_TIME: Duration = Duration::from_millis(2);
+
 /// Contains a connected target and allows operations to be carried out.
-pub struct TargetHandle<T: UsbContext> {
-    // USB device handle for the raw communication.
-    pub(crate) usb_device_handle: DeviceHandle<T>,
+pub struct TargetHandle<T: Transport> {
+    // Link to the target for the raw communication.
+    pub(crate) transport: T,
 
     /// USB endpoint buffer size for the data in endpoint.
     pub(crate) in_buffer_length: u16,
@@ -39,16 +70,289 @@ pub struct TargetHandle<T: UsbContext> {
 
     /// Serial number string of the device.
     pub(crate) serial: String,
+
+    /// Cache for memory reads, if enabled.
+    pub(crate) read_cache: Option<ReadCache>,
+
+    /// Current chunk sizes, if adaptive chunk sizing is enabled.
+    pub(crate) adaptive_chunk_sizes: Option<AdaptiveChunkSizes>,
+
+    /// Time limits for the transfers.
+    pub(crate) timeouts: Timeouts,
+
+    /// Repetition of commands failing with transient errors.
+    pub(crate) retry_policy: RetryPolicy,
+
+    /// Bootloader information, once queried.
+    pub(crate) info: Option<BootloaderInfo>,
+
+    /// Statistics about the commands sent so far.
+    pub(crate) stats: TransferStats,
+
+    /// Whether commands modifying the target are skipped.
+    pub(crate) dry_run: bool,
+
+    /// Alignment program requests are padded to, if automatic padding is enabled.
+    pub(crate) padding: Option<Padding>,
+
+    /// How each chunk is checked right after programming it.
+    pub(crate) chunk_verification: Verify,
 }
 
-impl<T: UsbContext> TargetHandle<T> {
+impl<T: Transport> TargetHandle<T> {
+    /// Creates a handle for a target connected via the given transport.
+    ///
+    /// `in_buffer_length` and `out_buffer_length` are the largest amounts of data the target sends
+    /// and accepts in a single transfer, i.e. the USB endpoint buffer sizes for USB connections.
+    /// The serial number is only used to identify the target to the user.
+    pub fn new(
+        transport: T,
+        in_buffer_length: u16,
+        out_buffer_length: u16,
+        serial: impl Into<String>,
+    ) -> Self {
+        Self {
+            transport,
+            in_buffer_length,
+            out_buffer_length,
+            serial: serial.into(),
+            read_cache: None,
+            adaptive_chunk_sizes: None,
+            timeouts: Timeouts::default(),
+            retry_policy: RetryPolicy::default(),
+            info: None,
+            stats: TransferStats::default(),
+            dry_run: false,
+            padding: None,
+            chunk_verification: Verify::Skip,
+        }
+    }
+
+    /// Returns statistics about the commands sent to the target. See [`TransferStats`] for details.
+    ///
+    /// [`TransferStats`]: struct.TransferStats.html
+    pub fn stats(&self) -> TransferStats {
+        self.stats
+    }
+
+    /// Resets the statistics returned by [`stats`], e.g. to measure a single operation.
+    ///
+    /// [`stats`]: #method.stats
+    pub fn reset_stats(&mut self) {
+        self.stats = TransferStats::default();
+    }
+
+    /// Writes every packet exchanged with the target to the given sink as annotated hex dump, for
+    /// debugging the communication with the bootloader. See [`HexDump`] for the format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open().hex_dump(std::io::stderr());
+    /// target_handle.read_crc(0x0800_1000, 1024)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`HexDump`]: trace/struct.HexDump.html
+    pub fn hex_dump<W: Write>(self, sink: W) -> TargetHandle<HexDump<T, W>> {
+        self.map_transport(|inner| HexDump::new(inner, sink))
+    }
+
+    /// Replaces the transport with one derived from it, keeping all other state.
+    pub(crate) fn map_transport<U: Transport>(self, f: impl FnOnce(T) -> U) -> TargetHandle<U> {
+        TargetHandle {
+            transport: f(self.transport),
+            in_buffer_length: self.in_buffer_length,
+            out_buffer_length: self.out_buffer_length,
+            serial: self.serial,
+            read_cache: self.read_cache,
+            adaptive_chunk_sizes: self.adaptive_chunk_sizes,
+            timeouts: self.timeouts,
+            retry_policy: self.retry_policy,
+            info: self.info,
+            stats: self.stats,
+            dry_run: self.dry_run,
+            padding: self.padding,
+            chunk_verification: self.chunk_verification,
+        }
+    }
+
+    /// Returns the transport the target is connected by.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Returns the transport the target is connected by for modification. Transfers made directly
+    /// via the transport bypass the read cache.
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Returns the time limits for the transfers with the target.
+    pub fn timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+
+    /// Sets the time limits for the transfers with the target. See [`Timeouts`] for details.
+    ///
+    /// [`Timeouts`]: struct.Timeouts.html
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Returns how commands failing with transient errors are repeated.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Sets how commands failing with transient errors are repeated. See [`RetryPolicy`] for
+    /// details. By default, no command is repeated.
+    ///
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Returns whether commands modifying the target are skipped. See [`set_dry_run`].
+    ///
+    /// [`set_dry_run`]: #method.set_dry_run
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Enables or disables the dry-run mode. In dry-run mode, erase, program and exit commands are
+    /// not sent to the target but reported as successful. All checks are carried out as usual and
+    /// operations report their progress step by step, so flash plans and tooling can be tested
+    /// against a real target without modifying it. Commands which only query the target are still
+    /// sent, so verifying data which was programmed in dry-run mode fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::Operation;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// target_handle.set_dry_run(true);
+    ///
+    /// target_handle.erase_area(0x0800_1000, 1024)?.execute()?;
+    /// let steps = target_handle.program_at(&[0x55; 1024], 0x0800_1000)?.count();
+    /// assert!(steps > 1);
+    /// assert!(target_handle.transport().is_erased(0x0800_1000, 1024));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Returns the alignment program requests are padded to, if automatic padding is enabled.
+    pub fn padding(&self) -> Option<Padding> {
+        self.padding
+    }
+
+    /// Enables or disables automatic padding of program requests. With padding, [`program_at`],
+    /// [`program_image`] and [`update_image`] accept data at any address and of any length: the
+    /// start is moved back to the previous aligned address and the end is extended to the next
+    /// one, with the added bytes set to `0xff`. By default, padding is disabled and requests at odd
+    /// addresses are rejected.
+    ///
+    /// Flash can only be programmed once per halfword before it has to be erased again, so the
+    /// whole padded area has to be erased, including the added bytes. In particular, two requests
+    /// must not share an aligned unit, as the second one would program it a second time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::{Operation, Padding};
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// target_handle.set_padding(Some(Padding::Halfword));
+    ///
+    /// target_handle.erase_area(0x0800_1000, 4)?.execute()?;
+    /// target_handle.program_at(&[0x12, 0x34], 0x0800_1001)?.execute()?;
+    /// assert_eq!(
+    ///     target_handle.transport().memory(0x0800_1000, 4),
+    ///     &[0xff, 0x12, 0x34, 0xff]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`program_at`]: #method.program_at
+    /// [`program_image`]: #method.program_image
+    /// [`update_image`]: #method.update_image
+    pub fn set_padding(&mut self, padding: Option<Padding>) {
+        self.padding = padding;
+    }
+
+    /// Returns how each chunk is checked right after programming it. See
+    /// [`set_chunk_verification`].
+    ///
+    /// [`set_chunk_verification`]: #method.set_chunk_verification
+    pub fn chunk_verification(&self) -> Verify {
+        self.chunk_verification
+    }
+
+    /// Sets how each chunk is checked right after programming it, before the next one is sent.
+    /// This costs an additional round trip per chunk, but a corrupted transfer fails the
+    /// programming operation at the affected chunk instead of only surfacing at the final
+    /// verification. The default is [`Verify::Skip`]. Chunks are not checked in dry-run mode.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::{Operation, Verify};
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// target_handle.set_chunk_verification(Verify::Crc);
+    ///
+    /// let data = [0x5a; 256];
+    /// target_handle.erase_area(0x0800_1000, data.len())?.execute()?;
+    /// target_handle.program_at(&data, 0x0800_1000)?.execute()?;
+    /// assert_eq!(target_handle.transport().memory(0x0800_1000, data.len()), &data[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Verify::Skip`]: enum.Verify.html#variant.Skip
+    pub fn set_chunk_verification(&mut self, verification: Verify) {
+        self.chunk_verification = verification;
+    }
+
     /// Return the serial number string.
     pub fn serial(&self) -> &str {
         &self.serial
     }
 
-    /// Queries bootloader information from the target.
+    /// Returns the bootloader information. It is queried from the target on first use and kept
+    /// afterwards, so checking requests against the application area does not cost a round-trip
+    /// each time. Use [`refresh_info`] to query it again.
+    ///
+    /// [`refresh_info`]: #method.refresh_info
     pub fn bootloader_info(&mut self) -> Result<BootloaderInfo> {
+        match &self.info {
+            Some(info) => Ok(info.clone()),
+            None => self.refresh_info(),
+        }
+    }
+
+    /// Queries the bootloader information from the target, replacing the kept one. This is only
+    /// needed if the bootloader may have changed without the handle reconnecting, and doubles as a
+    /// check whether the target still responds. Returns [`Error::IncompatibleBootloader`] if the
+    /// bootloader's version is not supported by this crate.
+    ///
+    /// [`Error::IncompatibleBootloader`]: enum.Error.html#variant.IncompatibleBootloader
+    pub fn refresh_info(&mut self) -> Result<BootloaderInfo> {
         use std::ffi::CString;
 
         let mut info_packet = [0u8; 64];
@@ -67,6 +371,12 @@ impl<T: UsbContext> TargetHandle<T> {
         build_date.insert(4, '-');
 
         let version = read_version(&mut info_packet);
+        if !version.is_supported() {
+            return Err(Error::IncompatibleBootloader {
+                device: version,
+                supported: Version::SUPPORTED_MAJOR_VERSIONS,
+            });
+        }
 
         // Convert the remainder of the packet to a String
         let identifier = CString::new(info_packet)
@@ -74,18 +384,156 @@ impl<T: UsbContext> TargetHandle<T> {
             .into_string()
             .map_err(|_| Error::MalformedResponse)?;
 
-        Ok(BootloaderInfo {
+        let info = BootloaderInfo {
             build_number,
             build_date,
             application_base,
             application_size,
             version,
             identifier,
-        })
+        };
+        self.info = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Queries the layout of the target's flash from the bootloader. All address checks of the
+    /// handle are based on it.
+    pub fn memory_map(&mut self) -> Result<MemoryMap> {
+        Ok(self.bootloader_info()?.memory_map())
+    }
+
+    /// Reads the microcontroller's unique device identifier from its system memory.
+    ///
+    /// This bypasses the check restricting reads to the application flash, so it relies on the
+    /// bootloader permitting reads from the system memory area.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// println!("Unique ID: {}", target_handle.unique_id()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn unique_id(&mut self) -> Result<UniqueId> {
+        let mut bytes = [0u8; 12];
+        self.read_chunk_uncached(UniqueId::ADDRESS, &mut bytes)?;
+        Ok(UniqueId(bytes))
+    }
+
+    /// Reads the microcontroller's option bytes from its system memory, e.g. to check the readout
+    /// protection level. Returns [`Error::MalformedResponse`] if the stored complements do not
+    /// match, which indicates an option byte error on the target.
+    ///
+    /// Like [`unique_id`], this relies on the bootloader permitting reads from the system memory
+    /// area.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::ReadProtection;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// let option_bytes = target_handle.option_bytes()?;
+    /// assert_eq!(option_bytes.read_protection(), ReadProtection::Disabled);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::MalformedResponse`]: enum.Error.html#variant.MalformedResponse
+    /// [`unique_id`]: #method.unique_id
+    pub fn option_bytes(&mut self) -> Result<OptionBytes> {
+        let mut bytes = [0u8; OptionBytes::SIZE];
+        self.read_chunk_uncached(OptionBytes::ADDRESS, &mut bytes)?;
+        OptionBytes::from_bytes(&bytes).ok_or(Error::MalformedResponse)
     }
 
-    /// Queries a CRC32 from the target for a given memory area.
+    /// Returns the checksum algorithm the target answers CRC requests with. All punt bootloaders
+    /// calculate [`Checksum::Crc32Mpeg2`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::Checksum;
+    ///
+    /// let target_handle = MockTarget::new().open();
+    /// assert_eq!(target_handle.checksum(), Checksum::Crc32Mpeg2);
+    /// ```
+    ///
+    /// [`Checksum::Crc32Mpeg2`]: enum.Checksum.html#variant.Crc32Mpeg2
+    pub fn checksum(&self) -> Checksum {
+        Checksum::Crc32Mpeg2
+    }
+
+    /// Queries a CRC32 from the target for a given memory area, calculated with the algorithm
+    /// returned by [`checksum`].
+    ///
+    /// Areas larger than [`MAX_CRC_LENGTH`] are split into several requests to stay within the
+    /// USB timeout, with the partial results combined into the CRC32 of the whole area. Returns
+    /// [`Error::InvalidRequest`] if the area extends beyond the 32-bit address space.
+    ///
+    /// [`checksum`]: #method.checksum
+    /// [`MAX_CRC_LENGTH`]: constant.MAX_CRC_LENGTH.html
+    /// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
     pub fn read_crc(&mut self, start: u32, length: usize) -> Result<u32> {
+        u32::try_from(length)
+            .ok()
+            .and_then(|length| start.checked_add(length))
+            .ok_or(Error::InvalidRequest)?;
+        if length <= MAX_CRC_LENGTH {
+            return self.read_crc_segment(start, length);
+        }
+
+        let checksum = self.checksum();
+        let mut crc = self.read_crc_segment(start, MAX_CRC_LENGTH)?;
+        let mut offset = MAX_CRC_LENGTH;
+        while offset < length {
+            let segment_length = (length - offset).min(MAX_CRC_LENGTH);
+            let segment_crc = self.read_crc_segment(start + offset as u32, segment_length)?;
+            crc = checksum.combine(crc, segment_crc, segment_length);
+            offset += segment_length;
+        }
+        Ok(crc)
+    }
+
+    /// Returns whether the checksum of the target memory area beginning at `start` matches the
+    /// supplied buffer.
+    pub(crate) fn crc_matches(&mut self, start: u32, data: &[u8]) -> Result<bool> {
+        match self.check_crc(start, data) {
+            Ok(()) => Ok(true),
+            Err(Error::VerificationError(_)) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Compares the CRC32 of a buffer with the one the target reports for the memory area starting
+    /// at the given address. Fails with [`Error::VerificationError`] if they differ.
+    ///
+    /// [`Error::VerificationError`]: enum.Error.html#variant.VerificationError
+    pub(crate) fn check_crc(&mut self, start: u32, data: &[u8]) -> Result<()> {
+        let checksum = self.checksum();
+        let expected = checksum.compute(data);
+        let actual = self.read_crc(start, data.len())?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::VerificationError(CrcMismatch {
+                address: start,
+                length: data.len(),
+                expected,
+                actual,
+            }))
+        }
+    }
+
+    /// Queries a CRC32 from the target with a single request.
+    fn read_crc_segment(&mut self, start: u32, length: usize) -> Result<u32> {
         let mut request_packet = vec![0u8; 8];
         request_packet[0..4].copy_from_slice(&start.to_le_bytes());
         request_packet[4..8].copy_from_slice(&(length as u32).to_le_bytes());
@@ -98,14 +546,109 @@ impl<T: UsbContext> TargetHandle<T> {
         Ok(crc)
     }
 
+    /// Reads the CRC32 of every application flash page into a manifest. Comparing it with a
+    /// manifest taken earlier reveals which pages have been modified in between.
+    pub fn crc_manifest(&mut self) -> Result<CrcManifest> {
+        let mut manifest = CrcManifest::new();
+        let pages = self.memory_map()?.application_pages();
+        let (first, last): (u8, u8) = (pages.start().into(), pages.end().into());
+        for page in (first..=last).map(Page::from_index) {
+            let crc = self.read_crc(page.begin(), PAGE_SIZE as usize)?;
+            manifest.insert(page, crc);
+        }
+        Ok(manifest)
+    }
+
     /// Verifies the supplied buffer against the target memory region beginning at the supplied
-    /// address with a CRC32 check.
+    /// address with a CRC32 check. On a mismatch, the returned [`Error::VerificationError`] contains
+    /// both checksums.
+    ///
+    /// [`Error::VerificationError`]: enum.Error.html#variant.VerificationError
     pub fn verify(&mut self, data: &[u8], address: u32) -> Result<()> {
-        let crc = self.read_crc(address, data.len())?;
-        if crc == crc32(data) {
-            Ok(())
-        } else {
-            Err(Error::VerificationError)
+        let _inhibitor = SleepInhibitor::new();
+        self.check_crc(address, data)
+    }
+
+    /// Verifies the supplied buffer against the target memory region beginning at the supplied
+    /// address like [`verify`], but page by page as an operation for progress feedback. See
+    /// [`Verification`] for details.
+    ///
+    /// [`verify`]: #method.verify
+    /// [`Verification`]: operation/struct.Verification.html
+    pub fn verify_at<'d>(
+        &mut self,
+        data: &'d [u8],
+        address: u32,
+    ) -> Result<Verification<'d, '_, T>> {
+        if !self.memory_map()?.contains_flash(address, data.len()) {
+            return Err(Error::InvalidRequest);
+        }
+
+        Ok(Verification::at(self, data, address))
+    }
+
+    /// Reads back the target memory region beginning at the supplied address page by page and
+    /// compares it with the supplied buffer. Returns the address of the first differing byte or
+    /// `None` if the memory matches.
+    ///
+    /// Unlike [`verify`], this pinpoints the location of a difference and cannot be fooled by a
+    /// CRC collision, but it transfers the whole region.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// target_handle.flash_bytes(&[0x12, 0x34, 0x56, 0x78], 0x0800_1000)?;
+    ///
+    /// let mismatch = target_handle.first_mismatch(&[0x12, 0x34, 0x00, 0x78], 0x0800_1000)?;
+    /// assert_eq!(mismatch, Some(0x0800_1002));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`verify`]: #method.verify
+    pub fn first_mismatch(&mut self, data: &[u8], address: u32) -> Result<Option<u32>> {
+        Ok(self
+            .read_back(data, address)?
+            .map(|(mismatch_address, _)| mismatch_address))
+    }
+
+    /// Reads back the memory region page by page until a chunk differs from the buffer. Returns the
+    /// address of the first differing byte and the checksums of the differing chunk.
+    fn read_back(&mut self, data: &[u8], address: u32) -> Result<Option<(u32, CrcMismatch)>> {
+        let mut buffer = vec![0u8; PAGE_SIZE as usize];
+        let mut chunk_address = address;
+        for chunk in data.chunks(PAGE_SIZE as usize) {
+            let buffer = &mut buffer[..chunk.len()];
+            self.read_at(buffer, chunk_address)?.execute()?;
+            if let Some(offset) = chunk.iter().zip(buffer.iter()).position(|(a, b)| a != b) {
+                let checksum = self.checksum();
+                let mismatch = CrcMismatch {
+                    address: chunk_address,
+                    length: chunk.len(),
+                    expected: checksum.compute(chunk),
+                    actual: checksum.compute(buffer),
+                };
+                return Ok(Some((chunk_address + offset as u32, mismatch)));
+            }
+            chunk_address += chunk.len() as u32;
+        }
+        Ok(None)
+    }
+
+    /// Verifies the supplied buffer against the target memory region beginning at the supplied
+    /// address by reading it back. Returns [`Error::VerificationError`] for the first page-sized
+    /// chunk with a differing byte; use [`first_mismatch`] to find out the byte's address.
+    ///
+    /// [`Error::VerificationError`]: enum.Error.html#variant.VerificationError
+    /// [`first_mismatch`]: #method.first_mismatch
+    pub fn verify_readback(&mut self, data: &[u8], address: u32) -> Result<()> {
+        match self.read_back(data, address)? {
+            Some((_, mismatch)) => Err(Error::VerificationError(mismatch)),
+            None => Ok(()),
         }
     }
 
@@ -115,11 +658,196 @@ impl<T: UsbContext> TargetHandle<T> {
         self.in_buffer_length as usize
     }
 
+    /// Returns the largest chunk adaptive sizing reads with a single command. Unlike a program
+    /// request, which has to fit into one packet, a read request carries a 32-bit length and is
+    /// answered with as many packets as needed. The limit of a few pages keeps the time lost on a
+    /// failed transfer short.
+    fn max_adaptive_read_chunk_size(&self) -> usize {
+        (4 * PAGE_SIZE as usize).max(self.max_read_chunk_size())
+    }
+
+    /// Enables or disables adaptive chunk sizing for memory reads and flash programming.
+    ///
+    /// By default, reads and writes are split into chunks of the USB endpoint buffer size and any
+    /// transfer error aborts the operation. With adaptive sizing enabled, the throughput of each
+    /// chunk size is measured over a series of transfers. Reads are grown to larger chunks of up to
+    /// four pages for as long as this does not slow them down, which helps on fast connections.
+    /// Program requests have to fit into a single packet, so their chunks cannot grow beyond the
+    /// endpoint buffer. If larger chunks do not pay off, smaller ones are used for as long as they
+    /// turn out to be faster. A chunk failing with a transient USB error (e.g. a timeout) is
+    /// retried with a smaller chunk size, which is increased again as long as this does not slow
+    /// transfers down. This helps on slow or flaky connections. An operation only fails if the
+    /// chunk size cannot be reduced any further.
+    pub fn set_adaptive_chunk_size(&mut self, enabled: bool) {
+        self.adaptive_chunk_sizes = if enabled {
+            Some(AdaptiveChunkSizes {
+                read: ChunkSize::new(
+                    self.max_read_chunk_size(),
+                    self.max_adaptive_read_chunk_size(),
+                ),
+                program: ChunkSize::new(
+                    self.max_program_chunk_size(),
+                    self.max_program_chunk_size(),
+                ),
+            })
+        } else {
+            None
+        };
+    }
+
+    /// Reads the next chunk of memory into the beginning of the buffer, filling at most as many
+    /// bytes as the current chunk size allows. Returns the number of bytes read.
+    pub(crate) fn read_next_chunk(&mut self, start: u32, buffer: &mut [u8]) -> Result<usize> {
+        loop {
+            let length = match &self.adaptive_chunk_sizes {
+                Some(sizes) => sizes.read.get(),
+                None => self.max_read_chunk_size(),
+            }
+            .min(buffer.len());
+
+            let transfer_start = Instant::now();
+            match self.read_chunk(start, &mut buffer[..length]) {
+                Ok(()) => {
+                    if let Some(sizes) = &mut self.adaptive_chunk_sizes {
+                        sizes.read.success(length, transfer_start.elapsed());
+                    }
+                    return Ok(length);
+                }
+                Err(error) => {
+                    let retry = error.is_transient()
+                        && self
+                            .adaptive_chunk_sizes
+                            .as_mut()
+                            .is_some_and(|sizes| sizes.read.failure());
+                    if !retry {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Programs the next chunk of data from the beginning of the buffer, writing at most as many
+    /// bytes as the current chunk size allows. Returns the number of bytes programmed.
+    pub(crate) fn program_next_chunk(&mut self, start: u32, data: &[u8]) -> Result<usize> {
+        loop {
+            let length = match &self.adaptive_chunk_sizes {
+                Some(sizes) => sizes.program.get(),
+                None => self.max_program_chunk_size(),
+            }
+            .min(data.len());
+
+            let transfer_start = Instant::now();
+            match self.program_chunk(start, &data[..length]) {
+                Ok(()) => {
+                    let elapsed = transfer_start.elapsed();
+                    self.verify_chunk(start, &data[..length])?;
+                    if let Some(sizes) = &mut self.adaptive_chunk_sizes {
+                        sizes.program.success(length, elapsed);
+                    }
+                    return Ok(length);
+                }
+                Err(error) => {
+                    let retry = error.is_transient()
+                        && self
+                            .adaptive_chunk_sizes
+                            .as_mut()
+                            .is_some_and(|sizes| sizes.program.failure());
+                    if !retry {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks a freshly programmed chunk as set with [`set_chunk_verification`].
+    ///
+    /// [`set_chunk_verification`]: #method.set_chunk_verification
+    fn verify_chunk(&mut self, start: u32, data: &[u8]) -> Result<()> {
+        match self.chunk_verification {
+            _ if self.dry_run => Ok(()),
+            Verify::Crc => self.check_crc(start, data),
+            Verify::Readback => self.verify_readback(data, start),
+            Verify::Skip => Ok(()),
+        }
+    }
+
+    /// Enables the host-side read cache.
+    ///
+    /// With the cache enabled, memory is fetched from the target page-wise and kept on the host, so
+    /// subsequent reads of the same pages do not cause any USB transfers. Cached pages are
+    /// invalidated automatically when they are erased or programmed through this handle. Changes
+    /// made to the flash by other means (e.g. by the application after exiting the bootloader) are
+    /// not detected.
+    pub fn enable_read_cache(&mut self) {
+        if self.read_cache.is_none() {
+            self.read_cache = Some(ReadCache::default());
+        }
+    }
+
+    /// Disables the host-side read cache and discards all cached data.
+    pub fn disable_read_cache(&mut self) {
+        self.read_cache = None;
+    }
+
+    /// Discards all data held by the read cache. The cache stays enabled if it was before.
+    pub fn clear_read_cache(&mut self) {
+        if let Some(cache) = &mut self.read_cache {
+            cache.clear();
+        }
+    }
+
     /// Reads a single chunk of memory, starting at the given address. The maximum chunk size can be
-    /// queried with [`max_read_chunk_size`].
+    /// queried with [`max_read_chunk_size`]. If the read cache is enabled, the chunk is served from
+    /// the cache, fetching any missing pages first. Memory outside the flash is never cached.
     ///
     /// [`max_read_chunk_size`]: #method.max_read_chunk_size
     pub(crate) fn read_chunk(&mut self, start: u32, buffer: &mut [u8]) -> Result<()> {
+        let flash_end = FLASH_BASE as usize + 256 * PAGE_SIZE as usize;
+        if self.read_cache.is_none()
+            || start < FLASH_BASE
+            || start as usize + buffer.len() > flash_end
+        {
+            return self.read_chunk_uncached(start, buffer);
+        }
+
+        let mut offset = 0;
+        while offset < buffer.len() {
+            let address = start + offset as u32;
+            let page = Page::from_address(address);
+            let page_offset = (address - page.begin()) as usize;
+            let length = (buffer.len() - offset).min(PAGE_SIZE as usize - page_offset);
+            let target = &mut buffer[offset..offset + length];
+
+            match self.read_cache.as_ref().and_then(|cache| cache.get(&page)) {
+                Some(data) => target.copy_from_slice(&data[page_offset..page_offset + length]),
+                None => {
+                    let data = self.read_page(&page)?;
+                    target.copy_from_slice(&data[page_offset..page_offset + length]);
+                    if let Some(cache) = &mut self.read_cache {
+                        cache.insert(page, data);
+                    }
+                }
+            }
+
+            offset += length;
+        }
+        Ok(())
+    }
+
+    /// Reads the full contents of a flash page from the target, bypassing the read cache.
+    fn read_page(&mut self, page: &Page) -> Result<Vec<u8>> {
+        let mut data = vec![0u8; PAGE_SIZE as usize];
+        let chunk_size = self.max_read_chunk_size();
+        for (i, chunk) in data.chunks_mut(chunk_size).enumerate() {
+            self.read_chunk_uncached(page.begin() + (i * chunk_size) as u32, chunk)?;
+        }
+        Ok(data)
+    }
+
+    /// Reads a single chunk of memory directly from the target, bypassing the read cache.
+    fn read_chunk_uncached(&mut self, start: u32, buffer: &mut [u8]) -> Result<()> {
         let mut request_packet = vec![0u8; 8];
         request_packet[0..4].copy_from_slice(&start.to_le_bytes());
         request_packet[4..8].copy_from_slice(&(buffer.len() as u32).to_le_bytes());
@@ -130,6 +858,10 @@ impl<T: UsbContext> TargetHandle<T> {
 
     /// Erases a single flash page. Caution: The page index is unchecked.
     pub(crate) fn erase_page(&mut self, page: Page) -> Result<()> {
+        if let Some(cache) = &mut self.read_cache {
+            cache.invalidate_page(&page);
+        }
+
         let request_packet = [page.into()];
         let mut status_packet = [0u8];
         self.send_command(Command::ErasePage, &request_packet, &mut status_packet)?;
@@ -142,11 +874,8 @@ impl<T: UsbContext> TargetHandle<T> {
 
     /// Erases a number of pages.
     pub fn erase_pages(&mut self, pages: &[Page]) -> Result<Erase<'_, T>> {
-        let bootloader_info = self.bootloader_info()?;
-        if pages
-            .iter()
-            .any(|page| !bootloader_info.application_pages().contains(page))
-        {
+        let memory_map = self.memory_map()?;
+        if pages.iter().any(|page| !memory_map.contains_page(page)) {
             return Err(Error::InvalidRequest);
         }
 
@@ -158,11 +887,7 @@ impl<T: UsbContext> TargetHandle<T> {
     /// flash memory.
     pub fn erase_area(&mut self, start: u32, length: usize) -> Result<Erase<'_, T>> {
         // Ensure that the requested area is fully within application flash
-        let bootloader_info = self.bootloader_info()?;
-        if (bootloader_info.application_base > start)
-            || (bootloader_info.application_base as usize + bootloader_info.application_size
-                < start as usize + length)
-        {
+        if !self.memory_map()?.contains(start, length) {
             return Err(Error::InvalidRequest);
         }
 
@@ -183,6 +908,10 @@ impl<T: UsbContext> TargetHandle<T> {
     ///
     /// [`max_read_chunk_size`]: #method.max_program_chunk_size
     pub(crate) fn program_chunk(&mut self, start: u32, data: &[u8]) -> Result<()> {
+        if let Some(cache) = &mut self.read_cache {
+            cache.invalidate_area(start, data.len());
+        }
+
         let mut address_packet = vec![0u8; 4];
         address_packet[0..4].copy_from_slice(&start.to_le_bytes());
 
@@ -195,44 +924,732 @@ impl<T: UsbContext> TargetHandle<T> {
 
     /// Programs a buffer's contents into the microcontroller's flash at the given start address.
     /// The flash area must have been erased already for this operation to succeed.
+    ///
+    /// The address has to be halfword-aligned, unless automatic padding has been enabled with
+    /// [`set_padding`]. Returns [`Error::TextualImage`] if the buffer is programmed to the start of
+    /// the application flash and contains the text of an Intel HEX or Motorola S-record file
+    /// instead of raw binary data.
+    ///
+    /// [`set_padding`]: #method.set_padding
+    /// [`Error::TextualImage`]: enum.Error.html#variant.TextualImage
     pub fn program_at<'d>(&mut self, data: &'d [u8], address: u32) -> Result<Program<'d, '_, T>> {
-        // Ensure that the area to be written to is fully within application flash
-        let bootloader_info = self.bootloader_info()?;
-        if (bootloader_info.application_base > address)
-            || (bootloader_info.application_base as usize + bootloader_info.application_size
-                < address as usize + data.len())
-        {
+        let memory_map = self.memory_map()?;
+        if address == memory_map.application().start() && looks_like_text_image(data) {
+            return Err(Error::TextualImage);
+        }
+        let (address, data) = self.pad_program_request(&memory_map, data, address)?;
+        check_program_request(&memory_map, &data, address)?;
+
+        Ok(Program::at(self, data, address))
+    }
+
+    /// Pads a program request to the alignment set with [`set_padding`], if any. The added bytes
+    /// before the start address and after the end are filled with `0xff`.
+    ///
+    /// [`set_padding`]: #method.set_padding
+    fn pad_program_request<'d>(
+        &self,
+        memory_map: &MemoryMap,
+        data: &'d [u8],
+        address: u32,
+    ) -> Result<(u32, Cow<'d, [u8]>)> {
+        let alignment = match self.padding {
+            Some(padding) => padding.bytes(),
+            None => return Ok((address, Cow::Borrowed(data))),
+        };
+        let leading = (address % alignment) as usize;
+        let end = address as usize + data.len();
+        let trailing = (end.next_multiple_of(alignment as usize) - end) % alignment as usize;
+        if data.is_empty() || (leading == 0 && trailing == 0) {
+            return Ok((address, Cow::Borrowed(data)));
+        }
+        if !memory_map.contains(address, data.len()) {
+            return Err(Error::InvalidRequest);
+        }
+
+        let start = address - leading as u32;
+        let mut padded = vec![0xff; leading];
+        padded.extend_from_slice(data);
+        padded.resize(padded.len() + trailing, 0xff);
+        Ok((start, Cow::Owned(padded)))
+    }
+
+    /// Pads all segments of an image like [`pad_program_request`]. Segments which end up sharing
+    /// an aligned unit are rejected, since it could only be programmed once.
+    ///
+    /// [`pad_program_request`]: #method.pad_program_request
+    fn pad_image<'d>(
+        &self,
+        memory_map: &MemoryMap,
+        image: &'d FirmwareImage,
+    ) -> Result<Cow<'d, FirmwareImage>> {
+        if self.padding.is_none() {
+            return Ok(Cow::Borrowed(image));
+        }
+        let mut segments = Vec::new();
+        for segment in image.segments() {
+            let (address, data) =
+                self.pad_program_request(memory_map, &segment.data, segment.address)?;
+            segments.push(Segment {
+                address,
+                data: data.into_owned(),
+            });
+        }
+        image
+            .with_segments(segments)
+            .map(Cow::Owned)
+            .ok_or(Error::InvalidRequest)
+    }
+
+    /// Erases all pages containing data of the given firmware image. See [`erase_pages`] for
+    /// details.
+    ///
+    /// [`erase_pages`]: #method.erase_pages
+    pub fn erase_image(&mut self, image: &FirmwareImage) -> Result<Erase<'_, T>> {
+        let memory_map = self.memory_map()?;
+        self.erase_pages(&image_pages(&memory_map, image)?)
+    }
+
+    /// Programs all segments of a firmware image into already erased flash. The same checks as for
+    /// [`program_at`] apply to every segment. The returned operation's progress covers the whole
+    /// image.
+    ///
+    /// [`program_at`]: #method.program_at
+    pub fn program_image<'d>(&mut self, image: &'d FirmwareImage) -> Result<Program<'d, '_, T>> {
+        let memory_map = self.memory_map()?;
+        let parts: Vec<(u32, Cow<'d, [u8]>)> = match self.pad_image(&memory_map, image)? {
+            Cow::Borrowed(image) => image
+                .segments()
+                .iter()
+                .map(|segment| (segment.address, Cow::Borrowed(&segment.data[..])))
+                .collect(),
+            Cow::Owned(image) => image
+                .segments()
+                .iter()
+                .map(|segment| (segment.address, Cow::Owned(segment.data.clone())))
+                .collect(),
+        };
+        for (address, data) in &parts {
+            check_program_request(&memory_map, data, *address)?;
+        }
+
+        Ok(Program::parts(self, parts))
+    }
+
+    /// Erases, programs and verifies a firmware image like [`flash_firmware`], but as a single
+    /// operation for progress feedback. See [`Update`] for details.
+    ///
+    /// [`flash_firmware`]: #method.flash_firmware
+    /// [`Update`]: operation/struct.Update.html
+    pub fn update_image<'d>(&mut self, image: &'d FirmwareImage) -> Result<Update<'d, '_, T>> {
+        let memory_map = self.memory_map()?;
+        let image = self.pad_image(&memory_map, image)?;
+        let pages = image_pages(&memory_map, &image)?;
+        for segment in image.segments() {
+            check_program_request(&memory_map, &segment.data, segment.address)?;
+        }
+        check_binary_image(&memory_map, &image)?;
+
+        Ok(Update::new(self, image, pages))
+    }
+
+    /// Verifies all segments of a firmware image against the target memory with CRC32 checks.
+    pub fn verify_image(&mut self, image: &FirmwareImage) -> Result<()> {
+        for segment in image.segments() {
+            self.verify(&segment.data, segment.address)?;
+        }
+        Ok(())
+    }
+
+    /// Erases, programs and verifies a firmware image. Returns on the first error to occur.
+    ///
+    /// This is a shorthand for calling [`erase_image`], [`program_image`] and [`verify_image`] in
+    /// sequence, without progress feedback. Returns [`Error::TextualImage`] if the data at the
+    /// start of the application flash is the text of an Intel HEX or Motorola S-record file
+    /// instead of raw binary data.
+    ///
+    /// [`erase_image`]: #method.erase_image
+    /// [`program_image`]: #method.program_image
+    /// [`verify_image`]: #method.verify_image
+    /// [`Error::TextualImage`]: enum.Error.html#variant.TextualImage
+    pub fn flash_firmware(&mut self, image: &FirmwareImage) -> Result<FlashSummary> {
+        let start = Instant::now();
+        check_binary_image(&self.memory_map()?, image)?;
+
+        let mut erase = self.erase_image(image)?;
+        let pages_erased = erase.total();
+        erase.execute()?;
+
+        let mut program = self.program_image(image)?;
+        let bytes_programmed = program.total();
+        program.execute()?;
+
+        self.verify_image(image)?;
+
+        Ok(FlashSummary {
+            pages_erased,
+            bytes_programmed,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Erases, programs and verifies a buffer at the given address. See [`flash_firmware`] for
+    /// details.
+    ///
+    /// [`flash_firmware`]: #method.flash_firmware
+    pub fn flash_bytes(&mut self, data: &[u8], address: u32) -> Result<FlashSummary> {
+        self.flash_firmware(&FirmwareImage::from_binary(address, data.to_vec()))
+    }
+
+    /// Checks a firmware image against the target without modifying it, e.g. to show a summary
+    /// before flashing. Unlike [`flash_firmware`], this does not fail for images which do not fit
+    /// or are misaligned, but reports these problems. Determining which pages have to be erased
+    /// takes one CRC32 request per page.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::FirmwareImage;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// target_handle.flash_bytes(&[0x55; 1024], 0x0800_1000)?;
+    ///
+    /// let image = FirmwareImage::from_binary(0x0800_1000, vec![0xaa; 2048]);
+    /// let preflight = target_handle.preflight(&image)?;
+    /// assert!(preflight.is_ok());
+    /// assert_eq!(preflight.pages.len(), 2);
+    /// assert_eq!(preflight.pages_to_erase.len(), 1);
+    /// println!("Flashing will take about {:?}.", preflight.estimated_duration);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`flash_firmware`]: #method.flash_firmware
+    pub fn preflight(&mut self, image: &FirmwareImage) -> Result<Preflight> {
+        let memory_map = self.memory_map()?;
+        let segments = image.segments();
+        let fits = segments
+            .iter()
+            .all(|segment| memory_map.contains(segment.address, segment.data.len()));
+        let pages = segment_pages(
+            segments
+                .iter()
+                .filter(|segment| memory_map.contains_flash(segment.address, segment.data.len())),
+        );
+
+        let mut pages_to_erase = Vec::new();
+        for page in pages.iter().filter(|page| memory_map.contains_page(page)) {
+            if !self.crc_matches(page.begin(), &[0xff; PAGE_SIZE as usize])? {
+                pages_to_erase.push(page.clone());
+            }
+        }
+
+        let bytes: usize = segments.iter().map(|segment| segment.data.len()).sum();
+        let chunk_size = self.max_program_chunk_size();
+        let commands = pages_to_erase.len()
+            + segments
+                .iter()
+                .map(|segment| segment.data.len().div_ceil(chunk_size) + 1)
+                .sum::<usize>();
+        let estimated_duration = ESTIMATED_ERASE_TIME * pages_to_erase.len() as u32
+            + ESTIMATED_HALFWORD_TIME * bytes.div_ceil(2) as u32
+            + ESTIMATED_COMMAND_TIME * commands as u32;
+
+        Ok(Preflight {
+            fits,
+            aligned: segments
+                .iter()
+                .all(|segment| segment.address.is_multiple_of(2)),
+            textual: check_binary_image(&memory_map, image).is_err(),
+            pages,
+            pages_to_erase,
+            bytes,
+            estimated_duration,
+        })
+    }
+
+    /// Returns the pages containing data of the given firmware image whose contents on the target
+    /// differ from the image. The comparison uses one CRC32 request per page, so it is much faster
+    /// than reading back the flash. Parts of a page not covered by the image are expected to be
+    /// erased, as they would be after flashing the image.
+    pub fn changed_pages(&mut self, image: &FirmwareImage) -> Result<Vec<Page>> {
+        let memory_map = self.memory_map()?;
+        let mut changed = Vec::new();
+        for page in image_pages(&memory_map, image)? {
+            if !self.page_matches(image, &page)? {
+                changed.push(page);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Returns whether a page's contents on the target match the given firmware image.
+    fn page_matches(&mut self, image: &FirmwareImage, page: &Page) -> Result<bool> {
+        let mut expected = vec![0xff; PAGE_SIZE as usize];
+        for segment in image.segments() {
+            if let Some((address, data)) = segment_part(segment, page) {
+                let offset = (address - page.begin()) as usize;
+                expected[offset..offset + data.len()].copy_from_slice(data);
+            }
+        }
+        self.crc_matches(page.begin(), &expected)
+    }
+
+    /// Flashes a firmware image like [`flash_firmware`], but only erases and programs the pages
+    /// whose contents differ from the image, as determined by [`changed_pages`]. When only a small
+    /// part of the application has changed, this is considerably faster than reflashing it
+    /// completely. The returned summary only counts the pages and bytes actually written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use punt::mock::MockTarget;
+    /// use punt::FirmwareImage;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let mut target_handle = MockTarget::new().open();
+    /// let mut data = vec![0x55; 4096];
+    /// target_handle.flash_bytes(&data, 0x0800_1000)?;
+    ///
+    /// data[2000] = 0xaa;
+    /// let summary = target_handle.flash_changed(&FirmwareImage::from_binary(0x0800_1000, data))?;
+    /// assert_eq!(summary.pages_erased, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`flash_firmware`]: #method.flash_firmware
+    /// [`changed_pages`]: #method.changed_pages
+    pub fn flash_changed(&mut self, image: &FirmwareImage) -> Result<FlashSummary> {
+        let start = Instant::now();
+        self.check_image(image)?;
+        let pages = self.changed_pages(image)?;
+        self.flash_pages(image, &pages, start)
+    }
+
+    /// Continues flashing a firmware image after an earlier attempt has been interrupted, e.g. by
+    /// a disconnected cable.
+    ///
+    /// Flashing proceeds in ascending address order, so the pages are compared with the image from
+    /// the start until the first one differs. That page and all following ones are erased,
+    /// programmed and verified again; the pages before it are left untouched. If the image is
+    /// already complete, nothing is written. The returned summary only counts the pages and bytes
+    /// actually written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, FirmwareImage, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let image = FirmwareImage::from_ihex(&std::fs::read_to_string("firmware.hex")?)?;
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    ///
+    /// if target_handle.flash_firmware(&image).is_err() {
+    ///     target_handle.reconnect(Duration::from_secs(5))?;
+    ///     target_handle.resume_firmware(&image)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume_firmware(&mut self, image: &FirmwareImage) -> Result<FlashSummary> {
+        let start = Instant::now();
+        self.check_image(image)?;
+
+        let memory_map = self.memory_map()?;
+        let pages = image_pages(&memory_map, image)?;
+        let mut first_mismatch = pages.len();
+        for (i, page) in pages.iter().enumerate() {
+            if !self.page_matches(image, page)? {
+                first_mismatch = i;
+                break;
+            }
+        }
+
+        self.flash_pages(image, &pages[first_mismatch..], start)
+    }
+
+    /// Checks whether all segments of an image can be programmed.
+    fn check_image(&mut self, image: &FirmwareImage) -> Result<()> {
+        let memory_map = self.memory_map()?;
+        for segment in image.segments() {
+            check_program_request(&memory_map, &segment.data, segment.address)?;
+        }
+        check_binary_image(&memory_map, image)
+    }
+
+    /// Erases the given pages, then programs and verifies the parts of the image within them.
+    fn flash_pages(
+        &mut self,
+        image: &FirmwareImage,
+        pages: &[Page],
+        start: Instant,
+    ) -> Result<FlashSummary> {
+        self.erase_pages(pages)?.execute()?;
+
+        let mut parts = Vec::new();
+        for segment in image.segments() {
+            parts.extend(pages.iter().filter_map(|page| segment_part(segment, page)));
+        }
+
+        let borrowed = parts
+            .iter()
+            .map(|&(address, data)| (address, Cow::Borrowed(data)))
+            .collect();
+        let mut program = Program::parts(self, borrowed);
+        let bytes_programmed = program.total();
+        program.execute()?;
+
+        for (address, data) in parts {
+            self.verify(data, address)?;
+        }
+
+        Ok(FlashSummary {
+            pages_erased: pages.len(),
+            bytes_programmed,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Updates selected address ranges of the application flash from a complete image starting at
+    /// `address`, leaving the rest of the flash untouched.
+    ///
+    /// Each range is widened to full pages, since flash can only be erased page-wise. The contents
+    /// for the widened ranges are taken from the image as well, so the image has to cover every
+    /// requested range. Parts of affected pages lying outside the image are left erased. Each
+    /// contiguous run of affected pages is erased, programmed and verified in turn. Returns on the
+    /// first error to occur, and [`Error::InvalidRequest`] before writing anything if the image
+    /// does not lie fully within the application flash or a range is not covered by it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let image = vec![0u8; 0x1_0000];
+    /// let mut context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    /// let base = target_handle.bootloader_info()?.application_base;
+    ///
+    /// // Only rewrite the last 4 KiB of the image
+    /// let end = base + image.len() as u32;
+    /// target_handle.update_ranges(&image, base, &[end - 0x1000..end])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
+    pub fn update_ranges(
+        &mut self,
+        image: &[u8],
+        address: u32,
+        ranges: &[Range<u32>],
+    ) -> Result<()> {
+        if !self.memory_map()?.contains(address, image.len()) {
             return Err(Error::InvalidRequest);
         }
+        let image_end = address as usize + image.len();
+
+        let mut page_indices = Vec::new();
+        for range in ranges {
+            if range.start < address || range.end as usize > image_end || range.start > range.end {
+                return Err(Error::InvalidRequest);
+            }
+            if range.start == range.end {
+                continue;
+            }
+            let first: u8 = Page::from_address(range.start).into();
+            let last: u8 = Page::from_address(range.end - 1).into();
+            page_indices.extend(first..=last);
+        }
+        page_indices.sort_unstable();
+        page_indices.dedup();
+
+        // Split the affected pages into runs of consecutive pages
+        let mut runs: Vec<Vec<Page>> = Vec::new();
+        for index in page_indices {
+            match runs.last_mut() {
+                Some(run) if u8::from(run.last().unwrap()) + 1 == index => {
+                    run.push(Page::from_index(index))
+                }
+                _ => runs.push(vec![Page::from_index(index)]),
+            }
+        }
+
+        for run in runs {
+            let start = run[0].begin().max(address);
+            let end = (run[run.len() - 1].end() as usize + 1).min(image_end);
+            let data = &image[(start - address) as usize..end - address as usize];
+
+            self.erase_pages(&run)?.execute()?;
+            self.program_at(data, start)?.execute()?;
+            self.verify(data, start)?;
+        }
 
-        // Programing works halfword-wise and will crash if the address is not aligned
-        if address % 2 != 0 {
+        Ok(())
+    }
+
+    /// Writes an image, e.g. a filesystem, into a reserved flash region.
+    ///
+    /// The whole region is erased first, so no stale data from a previous image remains behind
+    /// the new one. The image is then programmed at the start of the region and verified.
+    /// Returns [`Error::InvalidRequest`] if the image does not fit into the region or the region
+    /// is not fully within the application flash.
+    ///
+    /// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
+    pub fn flash_region(&mut self, region: &Region, image: &[u8]) -> Result<()> {
+        if image.len() > region.size() {
             return Err(Error::InvalidRequest);
         }
 
-        Ok(Program::at(self, data, address))
+        self.erase_area(region.start(), region.size())?.execute()?;
+        self.program_at(image, region.start())?.execute()?;
+        self.verify(image, region.start())
     }
 
     /// Reads from the target's memory into a buffer.
     pub fn read_at<'d>(&mut self, buffer: &'d mut [u8], address: u32) -> Result<Read<'d, '_, T>> {
         // Ensure that the requested area is fully within application flash
-        let bootloader_info = self.bootloader_info()?;
-        if (bootloader_info.application_base > address)
-            || (bootloader_info.application_base as usize + bootloader_info.application_size
-                < address as usize + buffer.len())
-        {
+        if !self.memory_map()?.contains(address, buffer.len()) {
             return Err(Error::InvalidRequest);
         }
 
         Ok(Read::at(self, buffer, address))
     }
 
+    /// Provides access to the application flash via the standard I/O traits. See [`TargetMemory`]
+    /// for details.
+    ///
+    /// [`TargetMemory`]: struct.TargetMemory.html
+    pub fn application_memory(&mut self) -> Result<TargetMemory<'_, T>> {
+        let region = self.memory_map()?.application().clone();
+        Ok(TargetMemory::new(self, region))
+    }
+
+    /// Streams the complete application flash to a writer, e.g. to back up the current firmware
+    /// before an update. The operation's progress is expressed in bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, Operation, UsbContext};
+    /// use std::fs::File;
+    /// use std::io::BufWriter;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    ///
+    /// let file = BufWriter::new(File::create("backup.bin")?);
+    /// let mut dump = target_handle.dump_application(file)?;
+    /// let total = dump.total();
+    /// while let Some(done) = dump.next() {
+    ///     println!("{}/{} bytes", done?, total);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dump_application<W: Write>(&mut self, writer: W) -> Result<Dump<'_, T, W>> {
+        let application = self.memory_map()?.application().clone();
+        Ok(Dump::at(
+            self,
+            writer,
+            application.start(),
+            application.size(),
+        ))
+    }
+
+    /// Reads from an arbitrary area of the target's memory into a buffer, e.g. RAM, the system
+    /// memory or peripheral registers. Unlike [`read_at`], the area is not required to lie within
+    /// the application flash.
+    ///
+    /// Use with care: reading unmapped addresses makes the target fault, and reading some
+    /// peripheral registers has side effects.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, Operation, UsbContext};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    ///
+    /// // Dump the RAM
+    /// let mut ram = vec![0u8; 20 * 1024];
+    /// target_handle.unchecked_read_at(&mut ram, 0x2000_0000).execute()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`read_at`]: #method.read_at
+    pub fn unchecked_read_at<'d>(&mut self, buffer: &'d mut [u8], address: u32) -> Read<'d, '_, T> {
+        Read::at(self, buffer, address)
+    }
+
+    /// Reads `count` consecutive 32-bit words from the target's memory, starting at the given
+    /// address. The words are decoded as little-endian, matching the target's byte order.
+    pub fn read_u32s(&mut self, address: u32, count: usize) -> Result<Vec<u32>> {
+        let mut buffer = vec![0u8; count * std::mem::size_of::<u32>()];
+        self.read_at(&mut buffer, address)?.execute()?;
+        Ok(buffer
+            .chunks_exact(std::mem::size_of::<u32>())
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Reads `count` consecutive 16-bit halfwords from the target's memory, starting at the given
+    /// address. The halfwords are decoded as little-endian, matching the target's byte order.
+    pub fn read_u16s(&mut self, address: u32, count: usize) -> Result<Vec<u16>> {
+        let mut buffer = vec![0u8; count * std::mem::size_of::<u16>()];
+        self.read_at(&mut buffer, address)?.execute()?;
+        Ok(buffer
+            .chunks_exact(std::mem::size_of::<u16>())
+            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+
     /// Lets the target exit from the bootloader and start its application.
     pub fn exit_bootloader(&mut self) -> Result<()> {
         self.send_command(Command::Exit, &[0; 0], &mut [0; 0])
             .map(|_| ())
     }
 
+    /// Lets the target exit from the bootloader like [`exit_bootloader`], then waits at most
+    /// `timeout` for the application to start. Returns whether it did.
+    ///
+    /// The application counts as started once the bootloader has disconnected from the bus and,
+    /// if `application` is given, a device with this USB vendor and product ID has enumerated on
+    /// the same port. In dry-run mode, nothing is sent and `false` is returned right away.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    /// if !target_handle.exit_and_wait(Some((0x1209, 0x0001)), Duration::from_secs(5))? {
+    ///     eprintln!("The application did not start.");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`exit_bootloader`]: #method.exit_bootloader
+    pub fn exit_and_wait(
+        &mut self,
+        application: Option<(u16, u16)>,
+        timeout: Duration,
+    ) -> Result<bool> {
+        if self.dry_run {
+            return Ok(false);
+        }
+        self.exit_bootloader()?;
+        self.transport.wait_for_application(application, timeout)
+    }
+
+    /// Lets the target exit from the bootloader like [`exit_bootloader`], then waits at most
+    /// `timeout` for the bootloader to enumerate again on the same port and reconnects to it. This
+    /// is useful with applications which return to the bootloader by themselves, e.g. test
+    /// firmware reporting its results.
+    ///
+    /// [`exit_bootloader`]: #method.exit_bootloader
+    pub fn exit_bootloader_and_reconnect(&mut self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        self.exit_bootloader()?;
+        loop {
+            self.reconnect(deadline.saturating_duration_since(Instant::now()))?;
+            // The old device may still be listed until the target has actually left the
+            // bootloader, so the connection is only accepted once the bootloader answers
+            if self.refresh_info().is_ok() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::TargetNotFound);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Re-opens the connection to the same physical target, waiting at most `timeout` for it to
+    /// reappear. This restores a handle which became unusable because the target was reset or
+    /// re-enumerated. USB targets are found again by the port they are connected to, so this also
+    /// works for targets without a unique serial number.
+    ///
+    /// Returns [`Error::TargetNotFound`] if the target does not reappear in time and
+    /// `IoError(NotSupported)` if the transport cannot reconnect.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    /// target_handle.transport_mut().reset()?;
+    /// target_handle.reconnect(Duration::from_secs(5))?;
+    /// target_handle.bootloader_info()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::TargetNotFound`]: enum.Error.html#variant.TargetNotFound
+    pub fn reconnect(&mut self, timeout: Duration) -> Result<()> {
+        self.transport = self.transport.reconnect(timeout)?;
+        // The memory contents may have changed while the target was out of reach
+        self.clear_read_cache();
+        // The target may have been updated to a different bootloader
+        self.info = None;
+        Ok(())
+    }
+
+    /// Resets the connection to the target and re-opens it if the target re-enumerates, waiting at
+    /// most `timeout` for it to reappear. This recovers from stuck endpoint states, e.g. after
+    /// another program crashed in the middle of a transfer, while keeping the handle and its
+    /// settings.
+    ///
+    /// Returns `IoError(NotSupported)` if the transport cannot be reset.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> punt::Result<()> {
+    /// let context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    /// if target_handle.bootloader_info().is_err() {
+    ///     target_handle.reenumerate(Duration::from_secs(5))?;
+    ///     target_handle.bootloader_info()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reenumerate(&mut self, timeout: Duration) -> Result<()> {
+        match self.transport.reset_device() {
+            Ok(()) => {
+                // A pending partial transfer may have hit the target before the reset
+                self.clear_read_cache();
+                Ok(())
+            }
+            Err(Error::IoError(rusb::Error::NotFound | rusb::Error::NoDevice)) => {
+                self.reconnect(timeout)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
     /// Sends a command to the target, optionally send data and optionally read data back. Returns a
     /// tuple with the data length written and read.
     fn send_command(
@@ -241,47 +1658,111 @@ impl<T: UsbContext> TargetHandle<T> {
         write_data: &[u8],
         read_data: &mut [u8],
     ) -> Result<(usize, usize)> {
-        self.usb_device_handle.claim_interface(0)?;
-        self.usb_device_handle.write_control(
-            rusb::request_type(
-                rusb::Direction::Out,
-                rusb::RequestType::Vendor,
-                rusb::Recipient::Device,
-            ),
-            cmd as u8,
-            0,
-            0,
-            &[0u8; 0],
-            TIMEOUT,
-        )?;
-
-        let mut written = 0;
-        let mut read = 0;
-
-        // If there is data to send, send it via bulk endpoint 2
-        if !write_data.is_empty() {
-            written = self
-                .usb_device_handle
-                .write_bulk(0x02, write_data, TIMEOUT)?;
-        }
-
-        // If some bytes should be read back, read them from bulk endpoint 1
-        if !read_data.is_empty() {
-            read = self.usb_device_handle.read_bulk(0x81, read_data, TIMEOUT)?;
-        }
-
-        self.usb_device_handle.release_interface(0)?;
-        Ok((written, read))
+        if self.dry_run && matches!(cmd, Command::ErasePage | Command::Program | Command::Exit) {
+            // A zeroed read buffer is a success status for the erase command
+            debug!("{:?} skipped in dry-run mode", cmd);
+            read_data.fill(0);
+            return Ok((write_data.len(), read_data.len()));
+        }
+
+        // The target is gone after exiting, so there is nothing to repeat the command on. A failed
+        // program command may have been partly received, so sending it again could desynchronise
+        // the bootloader, and halfwords which were already written cannot be programmed again
+        // without erasing them.
+        let mut attempts_left = match cmd {
+            Command::Exit | Command::Program => 0,
+            _ => self.retry_policy.attempts,
+        };
+        let mut delay = self.retry_policy.backoff;
+        let start = Instant::now();
+        loop {
+            match self
+                .transport
+                .send_command(cmd as u8, write_data, read_data, &self.timeouts)
+            {
+                Err(error) if attempts_left > 0 && self.retry_policy.is_retryable(&error) => {
+                    debug!("{:?} failed ({}), retrying in {:?}", cmd, error, delay);
+                    self.stats.retries += 1;
+                    attempts_left -= 1;
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(self.retry_policy.backoff_factor);
+                }
+                Ok((written, read)) => {
+                    self.record_command(start, written, read);
+                    debug!(
+                        "{:?}: {} bytes written, {} bytes read in {:?}",
+                        cmd,
+                        written,
+                        read,
+                        start.elapsed()
+                    );
+                    return Ok((written, read));
+                }
+                Err(error) => {
+                    self.record_command(start, 0, 0);
+                    self.stats.failed_commands += 1;
+                    debug!("{:?} failed after {:?}: {}", cmd, start.elapsed(), error);
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Adds a command started at the given time to the statistics.
+    fn record_command(&mut self, start: Instant, written: usize, read: usize) {
+        self.stats.commands += 1;
+        self.stats.bytes_written += written as u64;
+        self.stats.bytes_read += read as u64;
+        self.stats.time += start.elapsed();
+    }
+
+    /// Keeps the bootloader busy while the handle is otherwise idle, e.g. while waiting for user
+    /// input.
+    ///
+    /// Some bootloader configurations start the application after a period of inactivity. As long
+    /// as the returned guard exists, a background thread queries the bootloader information every
+    /// `interval` to prevent this. The guard borrows the handle mutably, so the handle cannot be
+    /// used until the guard is dropped or [`KeepAlive::stop`] is called.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use punt::{Context, UsbContext};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut context = Context::new()?;
+    /// let mut target_handle = context.open_target(None)?;
+    ///
+    /// let keep_alive = target_handle.keep_alive(Duration::from_secs(1))?;
+    /// println!("Press enter to continue");
+    /// std::io::stdin().read_line(&mut String::new())?;
+    /// keep_alive.stop()?;
+    ///
+    /// target_handle.exit_bootloader()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`KeepAlive::stop`]: struct.KeepAlive.html#method.stop
+    pub fn keep_alive(&mut self, interval: Duration) -> Result<KeepAlive<'_, T>>
+    where
+        T: Send + 'static,
+    {
+        // The background thread uses its own connection, since this one stays borrowed by the
+        // guard
+        let transport = self.transport.try_clone()?;
+        Ok(KeepAlive::start(self, transport, interval))
     }
 }
 
-impl<T: UsbContext> TryFrom<rusb::DeviceHandle<T>> for TargetHandle<T> {
+impl<T: UsbContext> TryFrom<DeviceHandle<T>> for TargetHandle<DeviceHandle<T>> {
     type Error = Error;
 
     /// Convert a raw USB device handle into a target handle. Note that this does only check if the
     /// needed endpoints exist and cannot guarantee that the USB device actually is running a punt
     /// bootloader.
-    fn try_from(mut handle: rusb::DeviceHandle<T>) -> Result<Self> {
+    fn try_from(mut handle: DeviceHandle<T>) -> Result<Self> {
         // Fetch endpoint sizes
         let device = handle.device();
         let config_descriptor = device.active_config_descriptor()?;
@@ -310,34 +1791,226 @@ impl<T: UsbContext> TryFrom<rusb::DeviceHandle<T>> for TargetHandle<T> {
 
         handle.reset()?;
 
-        Ok(TargetHandle {
-            usb_device_handle: handle,
-            in_buffer_length,
-            out_buffer_length,
-            serial,
-        })
+        let mut target_handle =
+            TargetHandle::new(handle, in_buffer_length, out_buffer_length, serial);
+        // Fail early for bootloaders speaking an incompatible protocol
+        target_handle.bootloader_info()?;
+        Ok(target_handle)
+    }
+}
+
+/// Returns all pages containing data of the given firmware image in ascending order. Returns
+/// [`Error::InvalidRequest`] if a segment does not lie fully within the application flash.
+///
+/// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
+fn image_pages(memory_map: &MemoryMap, image: &FirmwareImage) -> Result<Vec<Page>> {
+    if image
+        .segments()
+        .iter()
+        .any(|segment| !memory_map.contains(segment.address, segment.data.len()))
+    {
+        return Err(Error::InvalidRequest);
+    }
+    Ok(segment_pages(image.segments().iter()))
+}
+
+/// Returns all pages containing data of the given segments in ascending order. The segments have
+/// to lie within the flash.
+fn segment_pages<'s>(segments: impl Iterator<Item = &'s Segment>) -> Vec<Page> {
+    let mut indices: Vec<u8> = Vec::new();
+    for segment in segments.filter(|segment| !segment.data.is_empty()) {
+        let first: u8 = Page::from_address(segment.address).into();
+        let last: u8 = Page::from_address(segment.address + segment.data.len() as u32 - 1).into();
+        indices.extend(first..=last);
+    }
+    indices.sort_unstable();
+    indices.dedup();
+
+    indices.into_iter().map(Page::from_index).collect()
+}
+
+/// Returns the part of a segment lying within a page, with its address, if there is one.
+fn segment_part<'s>(segment: &'s Segment, page: &Page) -> Option<(u32, &'s [u8])> {
+    let start = segment.address.max(page.begin());
+    let end = (segment.address as usize + segment.data.len()).min(page.end() as usize + 1);
+    if start as usize >= end {
+        return None;
+    }
+    let offset = (start - segment.address) as usize;
+    Some((start, &segment.data[offset..end - segment.address as usize]))
+}
+
+/// Checks whether a program request is fully within the application flash and properly aligned.
+fn check_program_request(memory_map: &MemoryMap, data: &[u8], address: u32) -> Result<()> {
+    // Ensure that the area is fully within application flash. Programing works halfword-wise and
+    // will crash if the address is not aligned.
+    if !memory_map.can_program(address, data.len()) {
+        return Err(Error::InvalidRequest);
+    }
+
+    Ok(())
+}
+
+/// Catches HEX or SREC files which have been read in verbatim instead of being converted. Only the
+/// segment at the start of the application flash is checked, as it holds the vector table of a
+/// real firmware image, while other data may well be text.
+pub(crate) fn check_binary_image(memory_map: &MemoryMap, image: &FirmwareImage) -> Result<()> {
+    let start = memory_map.application().start();
+    if image
+        .segments()
+        .iter()
+        .any(|segment| segment.address == start && looks_like_text_image(&segment.data))
+    {
+        return Err(Error::TextualImage);
     }
+
+    Ok(())
 }
 
-/// Calculates a CRC32 checksum of a byte buffer in the way the punt target does it.
-pub(crate) fn crc32(buff: &[u8]) -> u32 {
-    let mut crc = CRC::crc32mpeg2();
-    for bytes in buff.chunks(4) {
-        let mut word = vec![0u8; 4];
-        word[..bytes.len()].copy_from_slice(bytes);
-        word.reverse();
-        crc.digest(&word);
+/// Checks whether a buffer starts with a line of an Intel HEX (`:0B0010...`) or Motorola S-record
+/// (`S1130000...`) file. Raw firmware binaries start with the initial stack pointer, which never
+/// consists of printable characters only.
+fn looks_like_text_image(data: &[u8]) -> bool {
+    let text = &data[..data.len().min(512)];
+    if !text
+        .iter()
+        .all(|&b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+    {
+        return false;
     }
-    crc.get_crc() as u32
+
+    let first_line = text
+        .split(|&b| b == b'\n' || b == b'\r')
+        .find(|line| !line.is_empty())
+        .unwrap_or(&[]);
+    let digits = match first_line {
+        [b':', rest @ ..] if rest.len() >= 10 => rest,
+        [b'S', b'0'..=b'9', rest @ ..] if rest.len() >= 6 => rest,
+        _ => return false,
+    };
+    digits.iter().all(u8::is_ascii_hexdigit)
 }
 
 /// Commands understood by the Punt bootloader. See `commands.h` in the C implementation of the
 /// bootloader for further details about each command.
-enum Command {
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    /// Queries the bootloader information.
     BootloaderInfo = 0x01,
+
+    /// Calculates the CRC32 of a memory area.
     ReadCrc = 0x02,
+
+    /// Reads a memory area.
     ReadMemory = 0x03,
+
+    /// Erases a flash page.
     ErasePage = 0x04,
+
+    /// Programs data into erased flash.
     Program = 0x05,
+
+    /// Leaves the bootloader and starts the application.
     Exit = 0xff,
 }
+
+impl Command {
+    /// Returns the command with the given code, if there is one.
+    pub(crate) fn from_code(code: u8) -> Option<Command> {
+        [
+            Command::BootloaderInfo,
+            Command::ReadCrc,
+            Command::ReadMemory,
+            Command::ErasePage,
+            Command::Program,
+            Command::Exit,
+        ]
+        .iter()
+        .copied()
+        .find(|&command| command as u8 == code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTarget;
+
+    const HEX_LINE: &[u8] = b":100000000102030405060708090A0B0C0D0E0F1068\n";
+
+    #[test]
+    fn program_at_rejects_text_at_application_start() {
+        let mut target_handle = MockTarget::new().open();
+        let base = target_handle.bootloader_info().unwrap().application_base;
+
+        assert!(matches!(
+            target_handle.program_at(HEX_LINE, base),
+            Err(Error::TextualImage)
+        ));
+        assert!(target_handle.transport().is_erased(base, HEX_LINE.len()));
+
+        // Text elsewhere in the application flash is ordinary data
+        target_handle
+            .program_at(b"S1234567", base + 0x1000)
+            .unwrap()
+            .execute()
+            .unwrap();
+        assert_eq!(
+            target_handle.transport().memory(base + 0x1000, 8),
+            b"S1234567"
+        );
+    }
+
+    #[test]
+    fn update_image_pads_like_program_image() {
+        let mut target_handle = MockTarget::new().open();
+        target_handle.set_padding(Some(Padding::Halfword));
+        let image = FirmwareImage::from_binary(0x0800_1001, vec![0x12, 0x34]);
+
+        target_handle
+            .update_image(&image)
+            .unwrap()
+            .execute()
+            .unwrap();
+        assert_eq!(
+            target_handle.transport().memory(0x0800_1000, 4),
+            &[0xff, 0x12, 0x34, 0xff]
+        );
+    }
+
+    #[test]
+    fn padding_rejects_segments_sharing_a_word() {
+        let mut target_handle = MockTarget::new().open();
+        target_handle.set_padding(Some(Padding::Word));
+        let image = FirmwareImage::default()
+            .with_segments(vec![
+                Segment {
+                    address: 0x0800_1000,
+                    data: vec![0x12],
+                },
+                Segment {
+                    address: 0x0800_1002,
+                    data: vec![0x34],
+                },
+            ])
+            .unwrap();
+
+        assert!(matches!(
+            target_handle.program_image(&image),
+            Err(Error::InvalidRequest)
+        ));
+    }
+
+    #[test]
+    fn read_crc_rejects_areas_beyond_the_address_space() {
+        let mut target_handle = MockTarget::new().open();
+        let length = 2 * MAX_CRC_LENGTH;
+
+        assert_eq!(
+            target_handle.read_crc(u32::MAX - MAX_CRC_LENGTH as u32, length),
+            Err(Error::InvalidRequest)
+        );
+        assert_eq!(target_handle.stats().commands, 0);
+    }
+}
@@ -0,0 +1,512 @@
+//! Contains recording and replaying of the raw communication with a target.
+//!
+//! A [`Recorder`] captures every command, write and read exchanged with a target into a
+//! [`Trace`], which can be stored in a simple text format. A [`Replay`] serves a stored trace
+//! back, so code can be tested against the exact behaviour of a real device, and bug reports can
+//! be reproduced without the hardware. A [`HexDump`] writes the packets as annotated hex dump
+//! instead, for reading rather than replaying.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use punt::mock::MockTarget;
+//! use punt::trace::{Recorder, Replay, Trace};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut target_handle = Recorder::record(MockTarget::new().open());
+//! let info = target_handle.bootloader_info()?;
+//!
+//! let mut file = Vec::new();
+//! target_handle.transport().trace().write_to(&mut file)?;
+//!
+//! let mut replayed = Replay::open(Trace::read_from(file.as_slice())?);
+//! assert_eq!(replayed.bootloader_info()?.identifier, info.identifier);
+//! assert!(replayed.transport().is_finished());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`HexDump`]: struct.HexDump.html
+//! [`Recorder`]: struct.Recorder.html
+//! [`Replay`]: struct.Replay.html
+//! [`Trace`]: struct.Trace.html
+
+use crate::error::{usb_error_from_name, usb_error_name, EraseError, Error, Result};
+use crate::firmware::decode_hex;
+use crate::target_handle::{Command, TargetHandle};
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+/// A single transfer between host and target, together with its outcome.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event {
+    /// A command was sent.
+    Command {
+        /// The command code.
+        command: u8,
+        /// The result of sending it.
+        result: Result<()>,
+    },
+
+    /// Data was written to the target.
+    Write {
+        /// The data written.
+        data: Vec<u8>,
+        /// The number of bytes the target accepted or the error which occurred.
+        result: Result<usize>,
+    },
+
+    /// Data was read from the target.
+    Read {
+        /// The size of the read buffer.
+        length: usize,
+        /// The data received or the error which occurred.
+        result: Result<Vec<u8>>,
+    },
+}
+
+/// Recorded communication with a target.
+///
+/// In the text format, the target's endpoint buffer sizes and serial number are followed by one
+/// line per event. Data is written in hexadecimal, errors as `!` followed by the libusb error
+/// name:
+///
+/// ```text
+/// in 64
+/// out 64
+/// serial 0123456789
+/// C 01
+/// R 64 !Timeout
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Trace {
+    /// USB endpoint buffer size for the data in endpoint.
+    pub in_buffer_length: u16,
+
+    /// USB endpoint buffer size for the data out endpoint.
+    pub out_buffer_length: u16,
+
+    /// Serial number string of the target.
+    pub serial: String,
+
+    /// The transfers in the order they occurred.
+    pub events: Vec<Event>,
+}
+
+impl Trace {
+    /// Writes the trace in its text format.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "in {}", self.in_buffer_length)?;
+        writeln!(writer, "out {}", self.out_buffer_length)?;
+        writeln!(writer, "serial {}", self.serial)?;
+        for event in &self.events {
+            match event {
+                Event::Command { command, result } => {
+                    write!(writer, "C {:02x}", command)?;
+                    if let Err(error) = result {
+                        write!(writer, " {}", format_error(error))?;
+                    }
+                }
+                Event::Write { data, result } => {
+                    write!(writer, "W {} ", format_data(data))?;
+                    match result {
+                        Ok(written) => write!(writer, "{}", written)?,
+                        Err(error) => write!(writer, "{}", format_error(error))?,
+                    }
+                }
+                Event::Read { length, result } => {
+                    write!(writer, "R {} ", length)?;
+                    match result {
+                        Ok(data) => write!(writer, "{}", format_data(data))?,
+                        Err(error) => write!(writer, "{}", format_error(error))?,
+                    }
+                }
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a trace in its text format. Empty lines are ignored.
+    pub fn read_from(reader: impl io::Read) -> io::Result<Self> {
+        let mut lines = io::BufReader::new(reader)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()));
+        let mut header = |key: &str| -> io::Result<String> {
+            let line = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+            line.strip_prefix(key)
+                .and_then(|value| value.strip_prefix(' '))
+                .map(String::from)
+                .ok_or_else(|| invalid_data(&line))
+        };
+        let in_buffer_length = header("in")?;
+        let out_buffer_length = header("out")?;
+        let serial = header("serial")?;
+
+        let mut trace = Trace {
+            in_buffer_length: in_buffer_length
+                .parse()
+                .map_err(|_| invalid_data(&in_buffer_length))?,
+            out_buffer_length: out_buffer_length
+                .parse()
+                .map_err(|_| invalid_data(&out_buffer_length))?,
+            serial,
+            events: Vec::new(),
+        };
+        for line in lines {
+            let line = line?;
+            let event = parse_event(&line).ok_or_else(|| invalid_data(&line))?;
+            trace.events.push(event);
+        }
+        Ok(trace)
+    }
+}
+
+/// Parses a single event line.
+fn parse_event(line: &str) -> Option<Event> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields.as_slice() {
+        ["C", command] => Some(Event::Command {
+            command: u8::from_str_radix(command, 16).ok()?,
+            result: Ok(()),
+        }),
+        ["C", command, error] => Some(Event::Command {
+            command: u8::from_str_radix(command, 16).ok()?,
+            result: Err(parse_error(error)?),
+        }),
+        ["W", data, result] => Some(Event::Write {
+            data: parse_data(data)?,
+            result: match parse_error(result) {
+                Some(error) => Err(error),
+                None => Ok(result.parse().ok()?),
+            },
+        }),
+        ["R", length, result] => Some(Event::Read {
+            length: length.parse().ok()?,
+            result: match parse_error(result) {
+                Some(error) => Err(error),
+                None => Ok(parse_data(result)?),
+            },
+        }),
+        _ => None,
+    }
+}
+
+/// Formats data as hexadecimal digits, or `-` if empty.
+fn format_data(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "-".to_string();
+    }
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_data(field: &str) -> Option<Vec<u8>> {
+    match field {
+        "-" => Some(Vec::new()),
+        _ => decode_hex(field),
+    }
+}
+
+/// Formats an error by its libusb error name. Other errors cannot occur during transfers and are
+/// recorded as `Other`.
+fn format_error(error: &Error) -> String {
+    match error {
+        Error::IoError(error) => format!("!{}", usb_error_name(error)),
+        _ => format!("!{}", usb_error_name(&rusb::Error::Other)),
+    }
+}
+
+fn parse_error(field: &str) -> Option<Error> {
+    usb_error_from_name(field.strip_prefix('!')?).map(Error::IoError)
+}
+
+fn invalid_data(text: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed trace entry: {}", text),
+    )
+}
+
+/// Transport wrapper recording all transfers into a [`Trace`].
+///
+/// [`Trace`]: struct.Trace.html
+pub struct Recorder<T: Transport> {
+    inner: T,
+    trace: Trace,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Starts recording all transfers made via the given handle.
+    pub fn record(handle: TargetHandle<T>) -> TargetHandle<Self> {
+        let trace = Trace {
+            in_buffer_length: handle.in_buffer_length,
+            out_buffer_length: handle.out_buffer_length,
+            serial: handle.serial.clone(),
+            events: Vec::new(),
+        };
+        handle.map_transport(|inner| Self { inner, trace })
+    }
+
+    /// Returns the transfers recorded so far.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Stops recording, returning the wrapped transport and the recorded trace.
+    pub fn into_parts(self) -> (T, Trace) {
+        (self.inner, self.trace)
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn command(&mut self, command: u8, timeout: Duration) -> Result<()> {
+        let result = self.inner.command(command, timeout);
+        self.trace.events.push(Event::Command {
+            command,
+            result: result.clone(),
+        });
+        result
+    }
+
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize> {
+        let result = self.inner.write(data, timeout);
+        self.trace.events.push(Event::Write {
+            data: data.to_vec(),
+            result: result.clone(),
+        });
+        result
+    }
+
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize> {
+        let result = self.inner.read(buffer, timeout);
+        self.trace.events.push(Event::Read {
+            length: buffer.len(),
+            result: result.clone().map(|read| buffer[..read].to_vec()),
+        });
+        result
+    }
+}
+
+/// Transport wrapper writing every packet to a sink as annotated hex dump. Created by
+/// [`TargetHandle::hex_dump`].
+///
+/// Each transfer is written as a header line, starting with `>` for data sent to the target, `<`
+/// for data received and `!` for failed transfers, followed by the data with 16 bytes per line.
+/// Headers name the command and decode the request and response fields, so the output can be
+/// compared with `commands.h` of the bootloader:
+///
+/// ```text
+/// > command 0x02 ReadCrc
+/// > 8 bytes: address 0x08001000, length 1024
+///   0000  00 10 00 08 00 04 00 00
+/// < 4 of 4 bytes: CRC 0xd000a3e2
+///   0000  e2 a3 00 d0
+/// ```
+///
+/// Errors writing to the sink are ignored, so dumping never interferes with the communication.
+///
+/// [`TargetHandle::hex_dump`]: ../struct.TargetHandle.html#method.hex_dump
+pub struct HexDump<T: Transport, W: Write> {
+    inner: T,
+    sink: W,
+    command: Option<Command>,
+}
+
+impl<T: Transport, W: Write> HexDump<T, W> {
+    pub(crate) fn new(inner: T, sink: W) -> Self {
+        Self {
+            inner,
+            sink,
+            command: None,
+        }
+    }
+
+    /// Returns the sink the packets are written to.
+    pub fn sink(&self) -> &W {
+        &self.sink
+    }
+
+    /// Stops dumping, returning the wrapped transport and the sink.
+    pub fn into_parts(self) -> (T, W) {
+        (self.inner, self.sink)
+    }
+
+    /// Writes a header line and the data as hex dump, ignoring errors.
+    fn dump(&mut self, header: &str, data: &[u8]) {
+        let _ = writeln!(self.sink, "{}", header);
+        for (i, line) in data.chunks(16).enumerate() {
+            let bytes: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let _ = writeln!(self.sink, "  {:04x}  {}", i * 16, bytes.join(" "));
+        }
+    }
+
+    fn dump_error(&mut self, transfer: &str, error: &Error) {
+        let _ = writeln!(self.sink, "! {} failed: {}", transfer, error);
+    }
+}
+
+impl<T: Transport, W: Write> Transport for HexDump<T, W> {
+    fn command(&mut self, command: u8, timeout: Duration) -> Result<()> {
+        self.command = Command::from_code(command);
+        let name = match self.command {
+            Some(command) => format!("{:?}", command),
+            None => "unknown".to_string(),
+        };
+        self.dump(&format!("> command 0x{:02x} {}", command, name), &[]);
+        let result = self.inner.command(command, timeout);
+        if let Err(error) = &result {
+            self.dump_error("command", error);
+        }
+        result
+    }
+
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize> {
+        let mut header = format!("> {} bytes", data.len());
+        if let Some(fields) = describe_request(self.command, data) {
+            header = format!("{}: {}", header, fields);
+        }
+        self.dump(&header, data);
+        let result = self.inner.write(data, timeout);
+        match &result {
+            Ok(written) if *written != data.len() => {
+                let _ = writeln!(self.sink, "! only {} bytes written", written);
+            }
+            Ok(_) => (),
+            Err(error) => self.dump_error("write", error),
+        }
+        result
+    }
+
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize> {
+        let result = self.inner.read(buffer, timeout);
+        match &result {
+            Ok(read) => {
+                let data = &buffer[..*read];
+                let mut header = format!("< {} of {} bytes", read, buffer.len());
+                if let Some(fields) = describe_response(self.command, data) {
+                    header = format!("{}: {}", header, fields);
+                }
+                self.dump(&header, data);
+            }
+            Err(error) => self.dump_error("read", error),
+        }
+        result
+    }
+}
+
+/// Decodes the fields of a request packet for the given command.
+fn describe_request(command: Option<Command>, data: &[u8]) -> Option<String> {
+    let word = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    match command? {
+        Command::ReadCrc | Command::ReadMemory => {
+            Some(format!("address 0x{:08x}, length {}", word(0)?, word(4)?))
+        }
+        Command::ErasePage => Some(format!("page {}", data.first()?)),
+        Command::Program => Some(format!(
+            "address 0x{:08x}, {} bytes of data",
+            word(0)?,
+            data.len() - 4
+        )),
+        _ => None,
+    }
+}
+
+/// Decodes the fields of a response packet for the given command.
+fn describe_response(command: Option<Command>, data: &[u8]) -> Option<String> {
+    match command? {
+        Command::ReadCrc => Some(format!(
+            "CRC 0x{:08x}",
+            u32::from_le_bytes(data.try_into().ok()?)
+        )),
+        Command::ErasePage => Some(match data.first()? {
+            0 => "status 0 (success)".to_string(),
+            status => format!("status {} ({:?})", status, EraseError::from(*status)),
+        }),
+        Command::BootloaderInfo => Some("bootloader information".to_string()),
+        _ => None,
+    }
+}
+
+/// Transport serving the transfers of a recorded [`Trace`].
+///
+/// Each transfer has to match the next recorded one: commands and written data have to be
+/// identical, and reads have to use the same buffer size. The recorded result is then returned.
+/// Once the transfers deviate from the trace or the trace is exhausted, all transfers fail with
+/// `IoError(Other)`.
+///
+/// [`Trace`]: struct.Trace.html
+pub struct Replay {
+    events: VecDeque<Event>,
+}
+
+impl Replay {
+    /// Creates a handle for a target serving the given trace.
+    pub fn open(trace: Trace) -> TargetHandle<Self> {
+        TargetHandle::new(
+            Self {
+                events: trace.events.into(),
+            },
+            trace.in_buffer_length,
+            trace.out_buffer_length,
+            trace.serial,
+        )
+    }
+
+    /// Returns whether all recorded transfers have been served.
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Takes the next event if it matches the predicate. Otherwise, all remaining events are
+    /// discarded, since the replay cannot continue.
+    fn next_event(&mut self, matches: impl FnOnce(&Event) -> bool) -> Result<Event> {
+        match self.events.pop_front() {
+            Some(event) if matches(&event) => Ok(event),
+            _ => {
+                self.events.clear();
+                Err(Error::IoError(rusb::Error::Other))
+            }
+        }
+    }
+}
+
+impl Transport for Replay {
+    fn command(&mut self, command: u8, _timeout: Duration) -> Result<()> {
+        match self.next_event(|event| {
+            matches!(event, Event::Command { command: recorded, .. } if *recorded == command)
+        })? {
+            Event::Command { result, .. } => result,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, data: &[u8], _timeout: Duration) -> Result<usize> {
+        match self.next_event(
+            |event| matches!(event, Event::Write { data: recorded, .. } if recorded == data),
+        )? {
+            Event::Write { result, .. } => result,
+            _ => unreachable!(),
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8], _timeout: Duration) -> Result<usize> {
+        let length = buffer.len();
+        match self.next_event(
+            |event| matches!(event, Event::Read { length: recorded, .. } if *recorded == length),
+        )? {
+            Event::Read { result, .. } => {
+                let data = result?;
+                buffer[..data.len()].copy_from_slice(&data);
+                Ok(data.len())
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Contains the abstraction over the link a target is connected by.
+
+use crate::context::UsbContext;
+use crate::error::{Error, Result};
+use crate::hotplug::POLL_INTERVAL;
+use crate::TIMEOUT;
+use rusb::DeviceHandle;
+use std::convert::TryFrom;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time limits for the individual transfers with a target.
+///
+/// The defaults suit targets connected directly or via a few hubs. Slow hubs or long cables may
+/// need longer timeouts, while tests may use shorter ones to detect failures quickly.
+///
+/// The limits for bulk transfers are extended by [`per_byte`] for every byte sent or requested,
+/// so large transfers are given more time than small ones without raising the limit for all of
+/// them.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use punt::{Context, Timeouts, UsbContext};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let context = Context::new()?;
+/// let mut target_handle = context.open_target(None)?;
+/// target_handle.set_timeouts(Timeouts {
+///     bulk_in: Duration::from_secs(2),
+///     per_byte: Duration::from_micros(20),
+///     ..Timeouts::default()
+/// });
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`per_byte`]: #structfield.per_byte
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Timeouts {
+    /// Limit for sending a command.
+    pub control: Duration,
+
+    /// Limit for receiving data from the target.
+    pub bulk_in: Duration,
+
+    /// Limit for sending data to the target.
+    pub bulk_out: Duration,
+
+    /// Additional time allowed for bulk transfers per byte transferred.
+    pub per_byte: Duration,
+}
+
+impl Timeouts {
+    /// Uses the same limit for all transfers, regardless of their size.
+    pub fn uniform(timeout: Duration) -> Self {
+        Self {
+            control: timeout,
+            bulk_in: timeout,
+            bulk_out: timeout,
+            per_byte: Duration::from_secs(0),
+        }
+    }
+
+    /// Returns the limit for receiving `length` bytes from the target.
+    pub fn read_timeout(&self, length: usize) -> Duration {
+        self.bulk_in.saturating_add(self.scaled(length))
+    }
+
+    /// Returns the limit for sending `length` bytes to the target.
+    pub fn write_timeout(&self, length: usize) -> Duration {
+        self.bulk_out.saturating_add(self.scaled(length))
+    }
+
+    fn scaled(&self, length: usize) -> Duration {
+        self.per_byte
+            .saturating_mul(u32::try_from(length).unwrap_or(u32::MAX))
+    }
+}
+
+impl Default for Timeouts {
+    /// Allows 500 ms for every transfer, regardless of its size.
+    fn default() -> Self {
+        Self::uniform(TIMEOUT)
+    }
+}
+
+/// Link over which commands are exchanged with a target.
+///
+/// A [`TargetHandle`] is generic over its transport, so the same bootloader logic works regardless
+/// of how the target is connected. Raw libusb device handles of [`rusb`] implement this trait and
+/// are used by default; other links, e.g. a UART, only have to provide the three primitives
+/// [`command`], [`write`] and [`read`]. Handles for such links are created with
+/// [`TargetHandle::new`].
+///
+/// # Examples
+///
+/// A transport for a serial port which frames each command as a single byte:
+///
+/// ```rust
+/// use punt::Transport;
+/// use std::io::{Read, Write};
+/// use std::time::Duration;
+///
+/// // The port's own timeouts apply
+/// struct Serial<P: Read + Write>(P);
+///
+/// impl<P: Read + Write> Transport for Serial<P> {
+///     fn command(&mut self, command: u8, _timeout: Duration) -> punt::Result<()> {
+///         Ok(self.0.write_all(&[command])?)
+///     }
+///
+///     fn write(&mut self, data: &[u8], _timeout: Duration) -> punt::Result<usize> {
+///         self.0.write_all(data)?;
+///         Ok(data.len())
+///     }
+///
+///     fn read(&mut self, buffer: &mut [u8], _timeout: Duration) -> punt::Result<usize> {
+///         Ok(self.0.read(buffer)?)
+///     }
+/// }
+/// ```
+///
+/// [`TargetHandle`]: struct.TargetHandle.html
+/// [`TargetHandle::new`]: struct.TargetHandle.html#method.new
+/// [`command`]: #tymethod.command
+/// [`write`]: #tymethod.write
+/// [`read`]: #tymethod.read
+/// [`rusb`]: https://docs.rs/rusb
+pub trait Transport {
+    /// Signals the start of a command to the target. Fails with `IoError(Timeout)` if this does
+    /// not complete within `timeout`.
+    fn command(&mut self, command: u8, timeout: Duration) -> Result<()>;
+
+    /// Sends data belonging to the current command. Returns the number of bytes sent. Fails with
+    /// `IoError(Timeout)` if this does not complete within `timeout`.
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize>;
+
+    /// Receives the target's response to the current command into a buffer. Returns the number of
+    /// bytes received. Fails with `IoError(Timeout)` if no response arrives within `timeout`.
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize>;
+
+    /// Sends a command to the target, then sends `write_data` and reads back into `read_data`.
+    /// Either of these is skipped if the respective buffer is empty. Returns a tuple with the data
+    /// lengths written and read.
+    fn send_command(
+        &mut self,
+        command: u8,
+        write_data: &[u8],
+        read_data: &mut [u8],
+        timeouts: &Timeouts,
+    ) -> Result<(usize, usize)> {
+        exchange(self, command, write_data, read_data, timeouts)
+    }
+
+    /// Opens an additional, independent connection to the same target. This is needed for
+    /// [`TargetHandle::keep_alive`]. By default, it fails with `IoError(NotSupported)`.
+    ///
+    /// [`TargetHandle::keep_alive`]: struct.TargetHandle.html#method.keep_alive
+    fn try_clone(&self) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(Error::IoError(rusb::Error::NotSupported))
+    }
+
+    /// Resets the connection to the target, e.g. with a USB port reset, clearing stuck endpoint
+    /// states. Fails with `IoError(NotFound)` or `IoError(NoDevice)` if the target re-enumerated
+    /// and has to be found again with [`reconnect`]. This is needed for
+    /// [`TargetHandle::reenumerate`]. By default, it fails with `IoError(NotSupported)`.
+    ///
+    /// [`reconnect`]: #method.reconnect
+    /// [`TargetHandle::reenumerate`]: struct.TargetHandle.html#method.reenumerate
+    fn reset_device(&mut self) -> Result<()> {
+        Err(Error::IoError(rusb::Error::NotSupported))
+    }
+
+    /// Opens a new connection to the same physical target after the current one became unusable,
+    /// e.g. because the target was reset, waiting at most `timeout` for it to reappear. This is
+    /// needed for [`TargetHandle::reconnect`]. By default, it fails with `IoError(NotSupported)`.
+    ///
+    /// [`TargetHandle::reconnect`]: struct.TargetHandle.html#method.reconnect
+    fn reconnect(&self, timeout: Duration) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let _ = timeout;
+        Err(Error::IoError(rusb::Error::NotSupported))
+    }
+
+    /// Waits at most `timeout` for the target to disconnect after leaving the bootloader and, if
+    /// given, for a device with the application's USB vendor and product ID to appear in its
+    /// place. Returns whether this happened in time. This is needed for
+    /// [`TargetHandle::exit_and_wait`]. By default, it fails with `IoError(NotSupported)`.
+    ///
+    /// [`TargetHandle::exit_and_wait`]: struct.TargetHandle.html#method.exit_and_wait
+    fn wait_for_application(
+        &self,
+        application: Option<(u16, u16)>,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let _ = (application, timeout);
+        Err(Error::IoError(rusb::Error::NotSupported))
+    }
+}
+
+/// Carries out the framing of a single command exchange via the transport's primitives.
+fn exchange<T: Transport + ?Sized>(
+    transport: &mut T,
+    command: u8,
+    write_data: &[u8],
+    read_data: &mut [u8],
+    timeouts: &Timeouts,
+) -> Result<(usize, usize)> {
+    trace!("Command 0x{:02x}", command);
+    transport.command(command, timeouts.control)?;
+
+    let mut written = 0;
+    let mut read = 0;
+
+    if !write_data.is_empty() {
+        written = transport.write(write_data, timeouts.write_timeout(write_data.len()))?;
+        trace!("Bulk write: {} of {} bytes", written, write_data.len());
+    }
+
+    if !read_data.is_empty() {
+        read = transport.read(read_data, timeouts.read_timeout(read_data.len()))?;
+        trace!("Bulk read: {} of {} bytes", read, read_data.len());
+    }
+
+    Ok((written, read))
+}
+
+impl<T: UsbContext> Transport for DeviceHandle<T> {
+    /// Claims the interface, if not already done, and sends the command as a vendor request.
+    fn command(&mut self, command: u8, timeout: Duration) -> Result<()> {
+        self.claim_interface(0)?;
+        self.write_control(
+            rusb::request_type(
+                rusb::Direction::Out,
+                rusb::RequestType::Vendor,
+                rusb::Recipient::Device,
+            ),
+            command,
+            0,
+            0,
+            &[0u8; 0],
+            timeout,
+        )?;
+        Ok(())
+    }
+
+    /// Sends data via bulk endpoint 2.
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize> {
+        Ok(self.write_bulk(0x02, data, timeout)?)
+    }
+
+    /// Reads data from bulk endpoint 1.
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize> {
+        Ok(self.read_bulk(0x81, buffer, timeout)?)
+    }
+
+    fn send_command(
+        &mut self,
+        command: u8,
+        write_data: &[u8],
+        read_data: &mut [u8],
+        timeouts: &Timeouts,
+    ) -> Result<(usize, usize)> {
+        // The interface is only kept claimed for the duration of a command, so other handles to
+        // the same device can be used in between
+        let lengths = exchange(self, command, write_data, read_data, timeouts)?;
+        self.release_interface(0)?;
+        Ok(lengths)
+    }
+
+    fn try_clone(&self) -> Result<Self> {
+        Ok(self.device().open()?)
+    }
+
+    fn reset_device(&mut self) -> Result<()> {
+        Ok(self.reset()?)
+    }
+
+    /// Waits for a target on the same bus and hub port chain and opens it. USB addresses change on
+    /// re-enumeration, but the port a device is plugged into does not.
+    fn reconnect(&self, timeout: Duration) -> Result<Self> {
+        let device = self.device();
+        let bus_number = device.bus_number();
+        let port_numbers = device.port_numbers()?;
+        let identity = self.context().device_identity();
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            for device in self.context().devices()?.iter() {
+                if device.bus_number() != bus_number || device.port_numbers()? != port_numbers {
+                    continue;
+                }
+                let device_desc = device.device_descriptor()?;
+                if device_desc.vendor_id() == identity.vendor_id
+                    && device_desc.product_id() == identity.product_id
+                {
+                    // The device may not be ready to be opened right after enumeration
+                    if let Ok(handle) = device.open() {
+                        return Ok(handle);
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::TargetNotFound);
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Waits for the device address to disappear from the bus, then for the application on the
+    /// same bus and hub port chain. The application may use the bootloader's vendor and product
+    /// ID, so the bootloader is only considered gone once its address is no longer in use.
+    fn wait_for_application(
+        &self,
+        application: Option<(u16, u16)>,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let device = self.device();
+        let bus_number = device.bus_number();
+        let address = device.address();
+        let port_numbers = device.port_numbers()?;
+
+        let deadline = Instant::now() + timeout;
+        let mut disconnected = false;
+        loop {
+            let devices = self.context().devices()?;
+            if !disconnected {
+                disconnected = !devices
+                    .iter()
+                    .any(|device| device.bus_number() == bus_number && device.address() == address);
+            }
+            if disconnected {
+                let (vendor_id, product_id) = match application {
+                    Some(application) => application,
+                    None => return Ok(true),
+                };
+                for device in devices.iter() {
+                    if device.bus_number() != bus_number || device.port_numbers()? != port_numbers {
+                        continue;
+                    }
+                    let device_desc = device.device_descriptor()?;
+                    if device_desc.vendor_id() == vendor_id
+                        && device_desc.product_id() == product_id
+                    {
+                        return Ok(true);
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Contains a queue for many small writes to flash memory, which are carried out with as few
+//! page rewrites as possible.
+
+use crate::error::{Error, Result};
+use crate::flash::{is_page_addressable, Page, PAGE_SIZE};
+use crate::operation::Operation;
+use crate::target_handle::TargetHandle;
+use crate::transport::Transport;
+use std::collections::BTreeMap;
+
+/// Collects small patches to flash memory and applies them with one read-modify-write cycle per
+/// affected page.
+///
+/// Since flash can only be erased page-wise, changing a few bytes means reading the page, erasing
+/// it and programming it again. Queueing all patches first means each page is rewritten at most
+/// once, no matter how many patches touch it. Overlapping patches are merged, with later ones
+/// taking precedence.
+///
+/// # Examples
+///
+/// ```rust
+/// use punt::{Page, WriteQueue};
+///
+/// # fn main() -> punt::Result<()> {
+/// let mut queue = WriteQueue::new();
+/// queue.push(0x0800_1000, &[0x01, 0x02])?;
+/// queue.push(0x0800_1010, &0xdead_beefu32.to_le_bytes())?;
+/// queue.push(0x0800_13ff, &[0xaa, 0xbb])?;
+///
+/// // Three patches, but only two pages to rewrite
+/// assert_eq!(
+///     queue.pages(),
+///     vec![Page::from_address(0x0800_1000), Page::from_address(0x0800_1400)]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct WriteQueue {
+    bytes: BTreeMap<u32, u8>,
+}
+
+impl WriteQueue {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues writing `data` at the given address. Bytes already queued for the same addresses are
+    /// overwritten. Returns [`Error::InvalidRequest`] without queueing anything if the patch does
+    /// not lie within the flash.
+    ///
+    /// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
+    pub fn push(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        if !is_page_addressable(address, data.len()) {
+            return Err(Error::InvalidRequest);
+        }
+        for (i, byte) in data.iter().enumerate() {
+            self.bytes.insert(address + i as u32, *byte);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if no writes are queued.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns all pages affected by the queued writes in ascending order.
+    pub fn pages(&self) -> Vec<Page> {
+        let mut pages: Vec<Page> = self
+            .bytes
+            .keys()
+            .map(|&address| Page::from_address(address))
+            .collect();
+        pages.dedup();
+        pages
+    }
+
+    /// Discards all queued writes.
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    /// Applies all queued writes to the target.
+    ///
+    /// Every affected page is read, patched, erased, programmed and verified. Pages whose contents
+    /// already match the patched data are skipped entirely. Returns [`Error::InvalidRequest`]
+    /// before touching the target if a patch lies outside the application flash. Otherwise,
+    /// returns on the first error to occur, in which case the page being rewritten may be left
+    /// erased or partially programmed. The queue is left unchanged, so the writes can be applied
+    /// again.
+    ///
+    /// [`Error::InvalidRequest`]: enum.Error.html#variant.InvalidRequest
+    pub fn apply<T: Transport>(&self, handle: &mut TargetHandle<T>) -> Result<()> {
+        let memory_map = handle.memory_map()?;
+        let mut addresses = self.bytes.keys();
+        if let (Some(&first), Some(&last)) = (addresses.next(), addresses.next_back()) {
+            if !memory_map.contains(first, (last - first) as usize + 1) {
+                return Err(Error::InvalidRequest);
+            }
+        }
+
+        for page in self.pages() {
+            let mut contents = vec![0u8; PAGE_SIZE as usize];
+            handle.read_at(&mut contents, page.begin())?.execute()?;
+
+            let mut patched = contents.clone();
+            for (&address, &byte) in self.bytes.range(page.begin()..=page.end()) {
+                patched[(address - page.begin()) as usize] = byte;
+            }
+            if patched == contents {
+                continue;
+            }
+
+            let begin = page.begin();
+            handle.erase_pages(&[page])?.execute()?;
+            handle.program_at(&patched, begin)?.execute()?;
+            handle.verify(&patched, begin)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTarget;
+
+    #[test]
+    fn push_rejects_patches_outside_the_flash() {
+        let mut queue = WriteQueue::new();
+        assert!(matches!(
+            queue.push(u32::MAX, &[0x01, 0x02]),
+            Err(Error::InvalidRequest)
+        ));
+        assert!(matches!(
+            queue.push(0x0000_1000, &[0x01]),
+            Err(Error::InvalidRequest)
+        ));
+        assert!(matches!(
+            queue.push(0x0804_0000, &[0x01]),
+            Err(Error::InvalidRequest)
+        ));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn apply_rejects_patches_outside_the_application() {
+        let mut target_handle = MockTarget::new().open();
+        let mut queue = WriteQueue::new();
+        queue.push(0x0800_1000, &[0x01]).unwrap();
+        // Within the bootloader
+        queue.push(0x0800_0010, &[0x02]).unwrap();
+
+        assert!(matches!(
+            queue.apply(&mut target_handle),
+            Err(Error::InvalidRequest)
+        ));
+        assert!(target_handle.transport().is_erased(0x0800_1000, 1));
+        assert_eq!(target_handle.transport().memory(0x0800_0010, 1), &[0]);
+    }
+}